@@ -3,20 +3,42 @@ use bevy::{
     ecs::{
         bundle::Bundle,
         entity::Entity,
-        system::{Commands, EntityCommands},
+        event::EventReader,
+        query::With,
+        schedule::IntoSystemConfigs,
+        system::{Commands, EntityCommands, Local, Query, Res, ResMut},
+        world::World,
     },
+    window::{PrimaryWindow, Window},
 };
 use systems::SolidWhenSelected;
 
 use crate::{
+    gestures::Gesture,
     hidden::Hidden,
+    history::History,
+    input_map::{Action, ActionInput},
     position::Position,
-    rendering::{point::Point, primitives, Stroke},
-    selection::{Connection, Draggable, Hoverable, Selectable},
+    rendering::{point::Point, primitives, Camera, Color, Size, Stroke},
+    selection::{
+        cursor_world_position, Connected, Connection, Constraint, Draggable, HoverShape, Hoverable,
+        Selectable, SelectionSystems, Tool,
+    },
 };
 
+mod autosave;
+mod clipboard;
 mod components;
+mod convert;
+mod document;
+mod history;
+mod path;
+mod plotter;
+mod svg;
 mod systems;
+mod trajectory;
+
+pub use convert::run_convert_command;
 
 /// Calculates a point t along a bezier curve
 ///
@@ -41,6 +63,38 @@ fn bezier(
     t1 * start_point + t2 * start_handle + t3 * end_handle + t4 * end_point
 }
 
+/// Calculates the direction a bezier curve is heading in at t, which
+/// isn't normalized. A handle on top of its terminal point makes the
+/// derivative zero there, so the direction towards the next control
+/// point that is somewhere else is used instead
+///
+/// # Panics
+/// panics if 0 <= t <= 1 is not true
+fn bezier_tangent(control_points: &[Position; 4], t: f64) -> Position {
+    assert!(t >= 0.0);
+    assert!(t <= 1.0);
+
+    let [start_point, start_handle, end_handle, end_point] = *control_points;
+    let t_inv = 1.0 - t;
+    let tangent = 3.0 * t_inv.powi(2) * (start_handle - start_point)
+        + 6.0 * t_inv * t * (end_handle - start_handle)
+        + 3.0 * t.powi(2) * (end_point - end_handle);
+    if tangent.distance_squared(&Position::default()) > f32::EPSILON {
+        return tangent;
+    }
+
+    let fallback = if t < 0.5 {
+        end_handle - start_point
+    } else {
+        end_point - start_handle
+    };
+    if fallback.distance_squared(&Position::default()) > f32::EPSILON {
+        fallback
+    } else {
+        end_point - start_point
+    }
+}
+
 fn generate_bezier_points(control_points: &[Position; 4]) -> impl Iterator<Item = Position> {
     generate_bezier_points_with_offset(control_points, None, None, true)
 }
@@ -105,6 +159,13 @@ fn split_bezier(control_points: &[Position; 4], t: f64) -> ([Position; 4], [Posi
     )
 }
 
+// Handles can sit right on top of their terminal point
+// so they win the hover to stay grabbable. Curve bodies
+// are below both so their ends can still be grabbed
+const CURVE_HOVER_PRIORITY: u8 = 0;
+const TERMINAL_HOVER_PRIORITY: u8 = 1;
+const HANDLE_HOVER_PRIORITY: u8 = 2;
+
 #[derive(Bundle)]
 struct BaseControlPointBundle {
     position: Position,
@@ -115,11 +176,18 @@ struct BaseControlPointBundle {
 }
 
 impl BaseControlPointBundle {
-    fn new(position: Position) -> Self {
+    fn new(position: Position, hover_priority: u8) -> Self {
         Self {
             position,
-            point: Point { size: 15.0 },
-            hoverable: Hoverable { radius: 20.0 },
+            // Control points are always the same size on screen
+            // so they are just as easy to grab at any zoom
+            point: Point {
+                size: Size::Screen(15.0),
+            },
+            hoverable: Hoverable {
+                radius: Size::Screen(20.0),
+                priority: hover_priority,
+            },
             draggable: Draggable,
             stroke: Stroke::Outline,
         }
@@ -132,7 +200,7 @@ fn create_handle<'c>(
     curve: Entity,
 ) -> EntityCommands<'c> {
     commands.spawn((
-        BaseControlPointBundle::new(position),
+        BaseControlPointBundle::new(position, HANDLE_HOVER_PRIORITY),
         Hidden,
         components::BezierHandle(curve),
     ))
@@ -150,10 +218,10 @@ fn create_terminal_point<'c>(
     start_point_curve: Option<Entity>,
 ) -> EntityCommands<'c> {
     let mut commands = commands.spawn((
-        BaseControlPointBundle::new(position),
+        BaseControlPointBundle::new(position, TERMINAL_HOVER_PRIORITY),
         Selectable,
         SolidWhenSelected,
-        Connection(Vec::from(connections)),
+        Connection::rigid(connections.iter().copied()),
     ));
 
     if let Some(entity) = end_point_curve {
@@ -173,7 +241,7 @@ fn create_bezier_curve(
     start_handle: Position,
     end_handle: Position,
     end_point: Position,
-) {
+) -> Entity {
     let curve_1 = commands.spawn_empty().id();
 
     let start_handle_1 = create_handle(commands, start_handle, curve_1).id();
@@ -192,7 +260,9 @@ fn create_bezier_curve(
         create_terminal_point(commands, end_point, &[end_handle_1], Some(curve_1), None).id();
 
     let curve_primitives = primitives::Primatives::new(&[], primitives::Type::LineStrip, 2.0);
-    let curve_primitives = commands.spawn(curve_primitives).id();
+    let curve_primitives = commands
+        .spawn((curve_primitives, components::BezierCurvePrimitives(curve_1)))
+        .id();
 
     let bezier_curve = components::BezierCurve {
         start_point: start_point_1,
@@ -202,7 +272,140 @@ fn create_bezier_curve(
         curve_primitives,
     };
 
-    commands.entity(curve_1).insert(bezier_curve);
+    commands.entity(curve_1).insert((
+        bezier_curve,
+        primitives::StrokeStyle::default(),
+        // The shape is filled in along with the curve's primitives
+        HoverShape::default(),
+        Hoverable {
+            radius: Size::Screen(8.0),
+            priority: CURVE_HOVER_PRIORITY,
+        },
+    ));
+
+    curve_1
+}
+
+/// Spawns count points that are evenly spaced along path
+/// and continuously loop around it
+fn create_path_followers(commands: &mut Commands, path: Entity, count: usize) {
+    for i in 0..count {
+        let start_offset = i as f32 / count as f32;
+        commands.spawn(path_follower_bundle(components::FollowPath::new(
+            path,
+            100.0,
+            components::FollowMode::Loop,
+            start_offset,
+        )));
+    }
+}
+
+fn path_follower_bundle(follow_path: components::FollowPath) -> impl Bundle {
+    (
+        Position::default(),
+        Point {
+            size: Size::World(10.0),
+        },
+        follow_path,
+    )
+}
+
+/// Connects the end point of first and the start point of second
+/// so that the join between them moves as one point
+fn join_curves(world: &mut World, first: Entity, second: Entity) {
+    let (Some(first), Some(second)) = (
+        world.get::<components::BezierCurve>(first),
+        world.get::<components::BezierCurve>(second),
+    ) else {
+        return;
+    };
+
+    for (host, other) in [
+        (first.end_point, second.start_point),
+        (second.start_point, first.end_point),
+    ] {
+        if let Some(mut connection) = world.get_mut::<Connection>(host) {
+            connection.0.push(Connected {
+                entity: other,
+                constraint: Constraint::Rigid,
+            });
+        }
+    }
+}
+
+#[derive(Default)]
+struct PenChain {
+    /// Where the last click was
+    last_point: Option<Position>,
+    /// The curve the last click added, which the next one is joined to
+    last_curve: Option<Entity>,
+}
+
+/// Each click with [Tool::Pen] adds a straight curve from the last
+/// clicked point to the new one, joined to the curve before it.
+/// Double clicking, [Action::Cancel] or changing tool finishes
+/// the chain of curves
+#[allow(clippy::too_many_arguments)]
+fn pen_tool_system(
+    mut commands: Commands,
+    tool: Res<Tool>,
+    input: ActionInput,
+    camera: Camera,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut gestures: EventReader<Gesture>,
+    mut lines: primitives::Lines,
+    mut history: ResMut<History>,
+    mut chain: Local<PenChain>,
+) {
+    if *tool != Tool::Pen || input.just_pressed(Action::Cancel) {
+        gestures.clear();
+        *chain = PenChain::default();
+        return;
+    }
+
+    let mut gestures = gestures.read().peekable();
+    while let Some(gesture) = gestures.next() {
+        match *gesture {
+            Gesture::Click { button, position } if input.is_button(Action::Select, button) => {
+                // The second click of a double click only finishes the chain
+                if matches!(
+                    gestures.peek(),
+                    Some(Gesture::DoubleClick { button: other, .. }) if *other == button
+                ) {
+                    continue;
+                }
+
+                if let Some(start) = chain.last_point {
+                    let curve = create_bezier_curve(
+                        &mut commands,
+                        start,
+                        Position::lerp(start, position, 1.0 / 3.0),
+                        Position::lerp(start, position, 2.0 / 3.0),
+                        position,
+                    );
+                    if let Some(last_curve) = chain.last_curve {
+                        commands
+                            .add(move |world: &mut World| join_curves(world, last_curve, curve));
+                    }
+                    history.record(history::CurveCreated::new(curve));
+                    chain.last_curve = Some(curve);
+                }
+                chain.last_point = Some(position);
+            }
+            Gesture::DoubleClick { button, .. } if input.is_button(Action::Select, button) => {
+                *chain = PenChain::default();
+            }
+            _ => {}
+        }
+    }
+
+    // Preview the curve the next click would add
+    if let (Some(start), Some(cursor)) = (
+        chain.last_point,
+        cursor_world_position(&window_query, &camera),
+    ) {
+        lines.draw_line(start, cursor);
+    }
 }
 
 fn initialize_bezier_curve(mut commands: Commands) {
@@ -212,7 +415,7 @@ fn initialize_bezier_curve(mut commands: Commands) {
     let end_handle = Position::new(400.0, 24.0);
     let end_point = Position::new(600.0, 240.0);
 
-    create_bezier_curve(
+    let curve_1 = create_bezier_curve(
         &mut commands,
         start_point,
         start_handle,
@@ -231,35 +434,89 @@ fn initialize_bezier_curve(mut commands: Commands) {
         ],
         0.3,
     );
-    create_bezier_curve(
+    let curve_2 = create_bezier_curve(
         &mut commands,
         start_point,
         start_handle,
         end_handle,
         end_point,
     );
-    create_bezier_curve(
+    let curve_3 = create_bezier_curve(
         &mut commands,
         start_point_2,
         start_handle_2,
         end_handle_2,
         end_point_2,
     );
+
+    // Give the second path its own style so the two can be told apart
+    let style = primitives::StrokeStyle {
+        color: Color::new_with_alpha(1.0, 0.6, 0.0, 0.8),
+        width: 4.0,
+        join: primitives::LineJoin::Round,
+        cap: primitives::LineCap::Round,
+    };
+    commands.entity(curve_2).insert(style);
+    commands.entity(curve_3).insert(style);
+
+    let path_1 = commands.spawn(components::BezierPath(vec![curve_1])).id();
+    let path_2 = commands
+        .spawn(components::BezierPath(vec![curve_2, curve_3]))
+        .id();
+    create_path_followers(&mut commands, path_1, 10);
+    create_path_followers(&mut commands, path_2, 10);
 }
 
 pub struct BezierPlugin;
 
 impl Plugin for BezierPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.add_systems(Startup, initialize_bezier_curve);
-        app.add_systems(Update, systems::solid_when_selected_system);
-        app.add_systems(PostUpdate, systems::update_bezier_curve_system);
+        app.add_event::<history::SetStrokeStyle>();
+        app.init_resource::<clipboard::Clipboard>();
+        app.init_resource::<document::DocumentPath>();
+        app.init_resource::<autosave::Autosave>();
+        app.add_systems(
+            Startup,
+            (initialize_bezier_curve, autosave::check_for_recovery_system),
+        );
+        app.add_systems(
+            Update,
+            (
+                history::delete_selected_curves_system,
+                history::split_curve_system,
+                clipboard::clipboard_system,
+                document::save_load_system,
+                svg::export_svg_system,
+                svg::import_svg_system,
+                autosave::autosave_system,
+            )
+                .before(SelectionSystems),
+        );
+        app.add_systems(
+            Update,
+            (
+                systems::solid_when_selected_system,
+                systems::follow_path_system,
+                pen_tool_system,
+                history::set_stroke_style_system,
+            ),
+        );
+        app.add_systems(
+            PostUpdate,
+            (
+                systems::repair_bezier_curve_system,
+                systems::despawn_orphaned_curve_parts_system,
+                systems::update_bezier_curve_system,
+            )
+                .chain(),
+        );
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use bevy::ecs::{schedule::Schedule, system::RunSystemOnce, world::World};
 
     #[derive(Debug)]
     #[allow(dead_code)]
@@ -358,6 +615,104 @@ mod tests {
         }
     }
 
+    fn spawn_test_curve(world: &mut World) -> Entity {
+        world.run_system_once(|mut commands: Commands| {
+            create_bezier_curve(
+                &mut commands,
+                Position::new(0.0, 0.0),
+                Position::new(10.0, 10.0),
+                Position::new(20.0, 10.0),
+                Position::new(30.0, 0.0),
+            )
+        })
+    }
+
+    fn lifecycle_schedule() -> Schedule {
+        let mut schedule = Schedule::default();
+        schedule.add_systems(
+            (
+                systems::repair_bezier_curve_system,
+                systems::despawn_orphaned_curve_parts_system,
+            )
+                .chain(),
+        );
+        schedule
+    }
+
+    #[test]
+    fn despawn_curve_removes_parts() {
+        let mut world = World::new();
+        let mut schedule = lifecycle_schedule();
+        let curve = spawn_test_curve(&mut world);
+        assert_eq!(world.entities().len(), 6);
+
+        world.despawn(curve);
+        schedule.run(&mut world);
+
+        assert_eq!(world.entities().len(), 0);
+    }
+
+    #[test]
+    fn despawn_terminal_point_removes_curve() {
+        let mut world = World::new();
+        let mut schedule = lifecycle_schedule();
+        let curve = spawn_test_curve(&mut world);
+        let start_point = world
+            .get::<components::BezierCurve>(curve)
+            .unwrap()
+            .start_point;
+
+        world.despawn(start_point);
+        // The first run removes the curve and the second its parts
+        schedule.run(&mut world);
+        schedule.run(&mut world);
+
+        assert_eq!(world.entities().len(), 0);
+    }
+
+    #[test]
+    fn despawn_handle_repairs_curve() {
+        let mut world = World::new();
+        let mut schedule = lifecycle_schedule();
+        let curve = spawn_test_curve(&mut world);
+        let old_curve = world.get::<components::BezierCurve>(curve).unwrap().clone();
+
+        world.despawn(old_curve.start_handle);
+        schedule.run(&mut world);
+
+        let new_curve = world.get::<components::BezierCurve>(curve).unwrap();
+        assert_ne!(new_curve.start_handle, old_curve.start_handle);
+        assert_eq!(
+            *world.get::<Position>(new_curve.start_handle).unwrap(),
+            Position::new(0.0, 0.0)
+        );
+
+        let connection = world.get::<Connection>(old_curve.start_point).unwrap();
+        let connections: Vec<_> = connection.entities().collect();
+        assert_eq!(connections, vec![new_curve.start_handle]);
+        assert_eq!(world.entities().len(), 6);
+    }
+
+    #[test]
+    fn draws_handles_for_large_selection() {
+        use crate::{rendering::primitives::LinesData, selection::Selected};
+
+        let mut world = World::new();
+        world.init_resource::<LinesData>();
+        // More than the immediate lines used to have room for,
+        // which a marquee around a busy drawing easily selects
+        let count = 150;
+        for _ in 0..count {
+            let curve = spawn_test_curve(&mut world);
+            let curve = world.get::<components::BezierCurve>(curve).unwrap().clone();
+            world.entity_mut(curve.start_point).insert(Selected);
+            world.entity_mut(curve.end_point).insert(Selected);
+        }
+
+        world.run_system_once(systems::update_bezier_curve_system);
+        assert_eq!(world.resource::<LinesData>().line_count(), count * 2);
+    }
+
     #[test]
     #[ignore]
     fn split_fuzz() {
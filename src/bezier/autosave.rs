@@ -0,0 +1,252 @@
+use std::path::{Path, PathBuf};
+
+use bevy::ecs::{
+    system::{Resource, SystemState},
+    world::World,
+};
+
+use crate::{
+    history::History,
+    input_map::{Action, ActionInput, InputMap},
+    my_time::Time,
+    title::TitleMessages,
+};
+
+use super::document::{load_document, save_document, DocumentPath};
+
+/// Seconds between writes of the recovery file while there are unsaved changes
+const AUTOSAVE_INTERVAL: f64 = 30.0;
+
+/// Key of the [TitleMessages] entry that offers to restore the recovery file
+const RECOVERY_MESSAGE: &str = "recovery";
+
+/// Keeps a recovery file up to date with unsaved changes so
+/// that they aren't lost if the app is closed or crashes
+#[derive(Resource, Default)]
+pub struct Autosave {
+    /// The [History::revision] when the document was last saved or loaded,
+    /// none when the document has never matched a file
+    saved_revision: Option<u64>,
+    /// The [History::revision] when the recovery file was last written
+    autosaved_revision: Option<u64>,
+    /// Elapsed seconds when the recovery file was last written
+    last_autosave: f64,
+    /// Recovery file left by an earlier session that hasn't
+    /// been restored or dismissed yet
+    pending_recovery: Option<PathBuf>,
+}
+
+impl Autosave {
+    pub fn is_dirty(&self, history: &History) -> bool {
+        self.saved_revision != Some(history.revision())
+    }
+}
+
+/// Where unsaved changes to the document at document_path are kept
+pub fn recovery_path(document_path: &Path) -> PathBuf {
+    document_path.with_extension("recovery.ron")
+}
+
+/// The recovery file for document_path if there is one with
+/// changes that are newer than the document itself
+pub fn find_recovery(document_path: &Path) -> Option<PathBuf> {
+    let recovery_path = recovery_path(document_path);
+    let recovery_modified = std::fs::metadata(&recovery_path).ok()?.modified().ok()?;
+    let newer = match std::fs::metadata(document_path).and_then(|metadata| metadata.modified()) {
+        Ok(document_modified) => recovery_modified > document_modified,
+        // Anything is newer than a document that was never saved
+        Err(_) => true,
+    };
+
+    newer.then_some(recovery_path)
+}
+
+/// Call after the document has been saved to or loaded from document_path
+/// so that it is clean and the recovery file isn't offered again
+pub fn document_saved(world: &mut World, document_path: &Path) {
+    let revision = world.resource::<History>().revision();
+    if let Some(mut autosave) = world.get_resource_mut::<Autosave>() {
+        autosave.saved_revision = Some(revision);
+        autosave.autosaved_revision = Some(revision);
+        autosave.pending_recovery = None;
+    }
+    if let Some(mut messages) = world.get_resource_mut::<TitleMessages>() {
+        messages.clear(RECOVERY_MESSAGE);
+    }
+    // There is no recovery file when the last save was clean
+    let _ = std::fs::remove_file(recovery_path(document_path));
+}
+
+/// Writes the recovery file if there are changes that haven't
+/// been written to it and it hasn't been written to recently
+fn autosave(world: &mut World) {
+    let elapsed = world
+        .get_resource::<Time>()
+        .map(|time| time.elapsed)
+        .unwrap_or_default();
+    let revision = world.resource::<History>().revision();
+    let autosave = world.resource::<Autosave>();
+    let due = elapsed - autosave.last_autosave >= AUTOSAVE_INTERVAL;
+    if !due
+        || autosave.pending_recovery.is_some()
+        || !autosave.is_dirty(world.resource::<History>())
+        || autosave.autosaved_revision == Some(revision)
+    {
+        return;
+    }
+
+    let path = recovery_path(&world.resource::<DocumentPath>().0);
+    if let Err(error) = save_document(world, &path) {
+        eprintln!("Couldn't autosave to {}: {error}", path.display());
+    }
+    // Failing is also waited out so a broken path isn't retried every frame
+    let mut autosave = world.resource_mut::<Autosave>();
+    autosave.autosaved_revision = Some(revision);
+    autosave.last_autosave = elapsed;
+}
+
+/// Offers to restore changes that weren't saved the last time the app ran
+pub fn check_for_recovery_system(world: &mut World) {
+    // The scene the app starts with doesn't need recovering
+    let revision = world.resource::<History>().revision();
+    world.resource_mut::<Autosave>().saved_revision = Some(revision);
+
+    let document_path = world.resource::<DocumentPath>().0.clone();
+    let Some(recovery_path) = find_recovery(&document_path) else {
+        return;
+    };
+
+    let input_map = world.resource::<InputMap>();
+    let message = format!(
+        "Unsaved changes from the last session: {} restores them, {} discards them",
+        input_map.describe(Action::RestoreRecovery),
+        input_map.describe(Action::Cancel),
+    );
+    world
+        .resource_mut::<TitleMessages>()
+        .set(RECOVERY_MESSAGE, message);
+    world.resource_mut::<Autosave>().pending_recovery = Some(recovery_path);
+}
+
+pub fn autosave_system(world: &mut World, input_state: &mut SystemState<ActionInput>) {
+    let input = input_state.get(world);
+    let restore = input.just_pressed(Action::RestoreRecovery);
+    let dismiss = input.just_pressed(Action::Cancel);
+
+    if let Some(recovery_path) = world.resource::<Autosave>().pending_recovery.clone() {
+        if restore {
+            // The restored changes still haven't been saved to the document
+            // so the recovery file is kept until they are
+            if let Err(error) = load_document(world, &recovery_path) {
+                eprintln!("Couldn't restore {}: {error}", recovery_path.display());
+            }
+        } else if dismiss {
+            // Otherwise the same changes would be offered again next time
+            if let Err(error) = std::fs::remove_file(&recovery_path) {
+                eprintln!("Couldn't discard {}: {error}", recovery_path.display());
+            }
+        } else {
+            return;
+        }
+
+        world.resource_mut::<Autosave>().pending_recovery = None;
+        world
+            .resource_mut::<TitleMessages>()
+            .clear(RECOVERY_MESSAGE);
+        return;
+    }
+
+    autosave(world);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::{Edit, EntityRemap};
+
+    struct Nothing;
+
+    impl Edit for Nothing {
+        fn undo(&mut self, _world: &mut World, _remap: &mut EntityRemap) {}
+        fn redo(&mut self, _world: &mut World, _remap: &mut EntityRemap) {}
+        fn remap_entities(&mut self, _remap: &EntityRemap) {}
+    }
+
+    #[test]
+    fn autosaves_unsaved_changes() {
+        let directory = std::env::temp_dir().join(format!("autosave-test-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let document_path = directory.join("scene.ron");
+        let recovery = recovery_path(&document_path);
+
+        let mut world = World::new();
+        world.init_resource::<History>();
+        world.init_resource::<Autosave>();
+        world.init_resource::<Time>();
+        world.insert_resource(DocumentPath(document_path.clone()));
+        document_saved(&mut world, &document_path);
+
+        // Clean documents aren't autosaved
+        world.resource_mut::<Time>().elapsed = AUTOSAVE_INTERVAL;
+        autosave(&mut world);
+        assert!(!recovery.exists());
+
+        world.resource_mut::<History>().record(Nothing);
+        autosave(&mut world);
+        assert!(recovery.exists());
+        // The document was never saved so the recovery file is newer
+        assert_eq!(find_recovery(&document_path), Some(recovery.clone()));
+
+        // Saving gets rid of the recovery file
+        save_document(&mut world, &document_path).unwrap();
+        document_saved(&mut world, &document_path);
+        assert!(!recovery.exists());
+        assert_eq!(find_recovery(&document_path), None);
+        assert!(!world
+            .resource::<Autosave>()
+            .is_dirty(world.resource::<History>()));
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn dismissing_recovery_discards_it() {
+        use bevy::{
+            ecs::system::RunSystemOnce,
+            input::{keyboard::KeyCode, mouse::MouseButton, ButtonInput},
+        };
+
+        let directory = std::env::temp_dir().join(format!("recovery-test-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let document_path = directory.join("scene.ron");
+        let recovery = recovery_path(&document_path);
+
+        let mut world = World::new();
+        world.init_resource::<History>();
+        world.init_resource::<Autosave>();
+        world.init_resource::<Time>();
+        world.init_resource::<TitleMessages>();
+        world.init_resource::<InputMap>();
+        world.init_resource::<ButtonInput<KeyCode>>();
+        world.init_resource::<ButtonInput<MouseButton>>();
+        world.insert_resource(DocumentPath(document_path.clone()));
+        save_document(&mut world, &recovery).unwrap();
+
+        // The offer is shown in the app until it is answered
+        world.run_system_once(check_for_recovery_system);
+        let title = || world.resource::<TitleMessages>().title("Editor");
+        assert!(title().contains("Ctrl+R restores them, Escape discards them"));
+        world.run_system_once(autosave_system);
+        assert!(recovery.exists());
+
+        world
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(KeyCode::Escape);
+        world.run_system_once(autosave_system);
+        assert_eq!(world.resource::<TitleMessages>().title("Editor"), "Editor");
+        assert!(!recovery.exists());
+        assert_eq!(find_recovery(&document_path), None);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,306 @@
+use bevy::{
+    ecs::{
+        entity::Entity,
+        query::With,
+        system::{Query, Res, Resource, SystemState},
+        world::World,
+    },
+    window::{PrimaryWindow, Window},
+};
+
+use crate::{
+    history::{EntityRemap, History, Inverse},
+    input_map::{Action, ActionInput},
+    position::Position,
+    rendering::Camera,
+    selection::{cursor_world_position, SelectionMode, SelectionSet},
+};
+
+use super::{
+    components::{BezierCurve, BezierPath},
+    history::{selected_curves, CurveSnapshot, CurvesDeleted},
+};
+
+/// How far pasted and duplicated curves are moved
+/// from the curves they were copied from
+const PASTE_OFFSET: Position = Position::new(20.0, 20.0);
+
+/// Curves that have been copied or cut, ready to be pasted
+#[derive(Resource, Default)]
+pub struct Clipboard {
+    curves: Vec<CurveSnapshot>,
+    /// Added to the curves before they are next pasted,
+    /// so that each paste lands next to the last one
+    offset: Position,
+}
+
+impl Clipboard {
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.curves.is_empty()
+    }
+
+    fn set(&mut self, curves: Vec<CurveSnapshot>, offset: Position) {
+        self.curves = curves;
+        self.offset = offset;
+    }
+
+    /// Moves the curves ready for the next paste and returns them
+    fn next_paste(&mut self, center: Option<Position>) -> Vec<CurveSnapshot> {
+        let offset = match (center, bounding_center(&self.curves)) {
+            (Some(center), Some(current)) => center - current,
+            _ => self.offset,
+        };
+        for curve in self.curves.iter_mut() {
+            curve.translate(offset);
+        }
+        self.offset = PASTE_OFFSET;
+
+        self.curves.clone()
+    }
+}
+
+/// The center of the box around every control point of curves
+fn bounding_center(curves: &[CurveSnapshot]) -> Option<Position> {
+    let mut positions = curves.iter().flat_map(CurveSnapshot::positions);
+    let first = positions.next()?;
+    let (min, max) = positions.fold((first, first), |(min, max), position| {
+        (
+            Position::new(min.x().min(position.x()), min.y().min(position.y())),
+            Position::new(max.x().max(position.x()), max.y().max(position.y())),
+        )
+    });
+
+    Some(Position::lerp(min, max, 0.5))
+}
+
+/// Snapshots of curves that can be spawned any number of times
+/// without affecting the curves or the paths they are in. Joins
+/// between the curves are kept while joins to anything else aren't
+pub fn copy_curves(world: &mut World, curves: &[Entity]) -> Vec<CurveSnapshot> {
+    // Sorted so that the copies are spawned in order along their paths
+    let CurvesDeleted(mut snapshots) = CurvesDeleted::snapshot(world, curves);
+    let points: Vec<_> = snapshots
+        .iter()
+        .flat_map(CurveSnapshot::control_points)
+        .collect();
+    for snapshot in snapshots.iter_mut() {
+        snapshot.retain_joins_between(&points);
+    }
+
+    snapshots
+}
+
+/// Spawns new curves from snapshots, records it so that it can be
+/// undone and selects the new curves in place of the old selection
+pub fn paste_curves(world: &mut World, mut curves: Vec<CurveSnapshot>) -> Vec<Entity> {
+    // Copies of curves from the same path make up a new path of their own
+    let mut paths = EntityRemap::default();
+    for path in curves.iter().flat_map(CurveSnapshot::paths) {
+        paths
+            .entry(path)
+            .or_insert_with(|| world.spawn(BezierPath(Vec::new())).id());
+    }
+    for curve in curves.iter_mut() {
+        curve.remap_entities(&paths);
+    }
+
+    // The snapshots have already been spawned once, so the
+    // entities they replace are the originals which shouldn't change
+    let mut remap = EntityRemap::default();
+    CurveSnapshot::spawn_all(&mut curves, world, &mut remap);
+
+    let terminal_points: Vec<_> = curves
+        .iter()
+        .flat_map(CurveSnapshot::terminal_points)
+        .collect();
+    world
+        .resource_mut::<SelectionSet>()
+        .apply(SelectionMode::Replace, terminal_points);
+
+    let pasted = curves.iter().map(CurveSnapshot::curve).collect();
+    world
+        .resource_mut::<History>()
+        .record(Inverse(CurvesDeleted(curves)));
+
+    pasted
+}
+
+/// Copies, cuts, pastes and duplicates every curve
+/// that has a selected control point
+#[allow(clippy::type_complexity)]
+pub fn clipboard_system(
+    world: &mut World,
+    state: &mut SystemState<(
+        ActionInput,
+        Res<SelectionSet>,
+        Query<(Entity, &BezierCurve)>,
+        Camera,
+        Query<&Window, With<PrimaryWindow>>,
+    )>,
+) {
+    let (input, selection_set, curves_query, camera, window_query) = state.get(world);
+    let selected = selected_curves(&selection_set, &curves_query);
+
+    if input.just_pressed(Action::Duplicate) {
+        let mut curves = copy_curves(world, &selected);
+        for curve in curves.iter_mut() {
+            curve.translate(PASTE_OFFSET);
+        }
+        if !curves.is_empty() {
+            paste_curves(world, curves);
+        }
+    } else if input.just_pressed(Action::Cut) {
+        if selected.is_empty() {
+            return;
+        }
+        let curves = copy_curves(world, &selected);
+        // Pasting what was cut puts it back where it was
+        world
+            .resource_mut::<Clipboard>()
+            .set(curves, Position::default());
+        let deleted = CurvesDeleted::apply(world, &selected);
+        world.resource_mut::<History>().record(deleted);
+    } else if input.just_pressed(Action::Copy) {
+        if selected.is_empty() {
+            return;
+        }
+        let curves = copy_curves(world, &selected);
+        world.resource_mut::<Clipboard>().set(curves, PASTE_OFFSET);
+    } else if input.just_pressed(Action::PasteAtCursor) || input.just_pressed(Action::Paste) {
+        let center = input
+            .just_pressed(Action::PasteAtCursor)
+            .then(|| cursor_world_position(&window_query, &camera))
+            .flatten();
+        let curves = world.resource_mut::<Clipboard>().next_paste(center);
+        if !curves.is_empty() {
+            paste_curves(world, curves);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::selection::Connection;
+    use bevy::ecs::{
+        system::{Commands, RunSystemOnce},
+        world::Mut,
+    };
+
+    fn spawn_curve(world: &mut World) -> Entity {
+        world.run_system_once(|mut commands: Commands| {
+            super::super::create_bezier_curve(
+                &mut commands,
+                Position::new(0.0, 0.0),
+                Position::new(0.0, 10.0),
+                Position::new(30.0, 10.0),
+                Position::new(30.0, 0.0),
+            )
+        })
+    }
+
+    fn positions(world: &mut World, curve: Entity) -> [Position; 4] {
+        CurveSnapshot::take(world, curve).unwrap().positions()
+    }
+
+    #[test]
+    fn pasted_curves_are_independent() {
+        let mut world = World::new();
+        world.init_resource::<History>();
+        world.init_resource::<SelectionSet>();
+        let mut clipboard = Clipboard::default();
+        let curve = spawn_curve(&mut world);
+        let original = positions(&mut world, curve);
+
+        clipboard.set(copy_curves(&mut world, &[curve]), PASTE_OFFSET);
+        let first = paste_curves(&mut world, clipboard.next_paste(None));
+        let second = paste_curves(&mut world, clipboard.next_paste(None));
+        assert_eq!(positions(&mut world, first[0])[0], PASTE_OFFSET);
+        assert_eq!(
+            positions(&mut world, second[0])[0],
+            PASTE_OFFSET + PASTE_OFFSET
+        );
+
+        // Only the latest paste is selected
+        let pasted = world.get::<BezierCurve>(second[0]).unwrap().clone();
+        let selection_set = world.resource::<SelectionSet>();
+        assert_eq!(selection_set.len(), 2);
+        assert!(selection_set.contains(pasted.start_point));
+        assert!(selection_set.contains(pasted.end_point));
+
+        // Moving a copy leaves the original alone
+        *world.get_mut::<Position>(pasted.start_handle).unwrap() = Position::new(-5.0, -5.0);
+        assert_eq!(positions(&mut world, curve), original);
+
+        let center = Position::new(100.0, 100.0);
+        let at_cursor = paste_curves(&mut world, clipboard.next_paste(Some(center)));
+        let [start_point, .., end_point] = positions(&mut world, at_cursor[0]);
+        assert_eq!(
+            Position::lerp(start_point, end_point, 0.5),
+            center - Position::new(0.0, 5.0)
+        );
+
+        // Undoing a paste despawns the copies, and redoing brings them back
+        world.resource_scope(|world, mut history: Mut<History>| history.undo(world));
+        assert!(world.get_entity(at_cursor[0]).is_none());
+        // Three curves with six entities each
+        assert_eq!(world.entities().len(), 18);
+        world.resource_scope(|world, mut history: Mut<History>| history.redo(world));
+        assert_eq!(world.entities().len(), 24);
+    }
+
+    #[test]
+    fn copies_keep_joins_and_paths() {
+        let mut world = World::new();
+        world.init_resource::<History>();
+        world.init_resource::<SelectionSet>();
+        let first = spawn_curve(&mut world);
+        let second = spawn_curve(&mut world);
+        let unrelated = spawn_curve(&mut world);
+        super::super::join_curves(&mut world, first, second);
+        super::super::join_curves(&mut world, second, unrelated);
+        let path = world.spawn(BezierPath(vec![second, first])).id();
+
+        let copied = copy_curves(&mut world, &[first, second]);
+        let pasted = paste_curves(&mut world, copied);
+        // Copies are spawned in the order they are in along their path
+        let [second_copy, first_copy] = pasted[..] else {
+            panic!("Expected two pasted curves");
+        };
+        let curve = |world: &World, entity| world.get::<BezierCurve>(entity).unwrap().clone();
+        let connected = |world: &World, entity| -> Vec<Entity> {
+            world
+                .get::<Connection>(entity)
+                .unwrap()
+                .entities()
+                .collect()
+        };
+
+        // The copies are joined to each other in the same way
+        // as the originals but not to anything else
+        let (first_end, second_start) = (
+            curve(&world, first_copy).end_point,
+            curve(&world, second_copy).start_point,
+        );
+        assert!(connected(&world, first_end).contains(&second_start));
+        assert!(connected(&world, second_start).contains(&first_end));
+        assert_eq!(
+            connected(&world, curve(&world, second_copy).end_point).len(),
+            1
+        );
+
+        // They are in a path of their own in the same order
+        let copied_path = world
+            .query::<(Entity, &BezierPath)>()
+            .iter(&world)
+            .find(|(entity, _)| *entity != path)
+            .map(|(_, path)| path.0.clone())
+            .unwrap();
+        assert_eq!(copied_path, vec![second_copy, first_copy]);
+        assert_eq!(
+            world.get::<BezierPath>(path).unwrap().0,
+            vec![second, first]
+        );
+    }
+}
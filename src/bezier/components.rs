@@ -1,19 +1,22 @@
 use bevy::ecs::{component::Component, entity::Entity};
+use serde::{Deserialize, Serialize};
 
 // Components that exist for reverse lookup of a curve from a point
 #[derive(Component)]
-#[allow(dead_code)]
 pub struct BezierHandle(pub Entity);
 
 // Start and end points are different components so a mid point
 // of a spline can have both
 #[derive(Component)]
-#[allow(dead_code)]
 pub struct BezierStartPoint(pub Entity);
 #[derive(Component)]
-#[allow(dead_code)]
 pub struct BezierEndPoint(pub Entity);
 
+// Reverse lookup of a curve from the entity that holds its
+// rendered primitives
+#[derive(Component)]
+pub struct BezierCurvePrimitives(pub Entity);
+
 #[derive(Component, Clone)]
 pub struct BezierCurve {
     pub start_point: Entity,
@@ -23,3 +26,97 @@ pub struct BezierCurve {
 
     pub curve_primitives: Entity,
 }
+
+impl BezierCurve {
+    /// The control point entities of this curve in the
+    /// order they are passed to the bezier function
+    pub fn control_points(&self) -> [Entity; 4] {
+        [
+            self.start_point,
+            self.start_handle,
+            self.end_handle,
+            self.end_point,
+        ]
+    }
+}
+
+/// An ordered chain of [BezierCurve] entities that
+/// can be followed as a single path
+#[derive(Component)]
+pub struct BezierPath(pub Vec<Entity>);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[allow(dead_code)]
+pub enum FollowMode {
+    /// Jump back to the start after reaching the end
+    Loop,
+    /// Reverse direction at either end of the path
+    PingPong,
+    /// Stop at the end of the path
+    Once,
+}
+
+/// Drives the [Position](crate::position::Position) of this entity
+/// along a [BezierPath] at a constant speed
+#[derive(Component)]
+pub struct FollowPath {
+    /// Entity with the [BezierPath] component to follow
+    pub path: Entity,
+    /// Speed in world units per second
+    pub speed: f32,
+    pub mode: FollowMode,
+    /// How far along the path the entity is from 0 to 1,
+    /// kept as a fraction so that editing the path does not
+    /// make the entity jump
+    pub progress: f32,
+    /// Whether the entity is currently moving from the end
+    /// of the path back to the start
+    pub reversed: bool,
+}
+
+impl FollowPath {
+    /// Creates a follower that starts start_offset (from 0 to 1)
+    /// along the path
+    pub fn new(path: Entity, speed: f32, mode: FollowMode, start_offset: f32) -> Self {
+        Self {
+            path,
+            speed,
+            mode,
+            progress: start_offset.clamp(0.0, 1.0),
+            reversed: false,
+        }
+    }
+
+    /// Moves progress forward by distance along a path that
+    /// is length long, handling the ends of the path based on mode
+    pub fn advance(&mut self, distance: f32, length: f32) {
+        if length <= 0.0 {
+            return;
+        }
+
+        let step = distance / length;
+        let progress = if self.reversed {
+            self.progress - step
+        } else {
+            self.progress + step
+        };
+
+        self.progress = match self.mode {
+            FollowMode::Loop => progress.rem_euclid(1.0),
+            FollowMode::Once => progress.clamp(0.0, 1.0),
+            FollowMode::PingPong => {
+                // Reflect off of either end, a single frame shouldn't ever
+                // be able to cover the whole path more than once
+                if progress > 1.0 {
+                    self.reversed = !self.reversed;
+                    (2.0 - progress).max(0.0)
+                } else if progress < 0.0 {
+                    self.reversed = !self.reversed;
+                    (-progress).min(1.0)
+                } else {
+                    progress
+                }
+            }
+        };
+    }
+}
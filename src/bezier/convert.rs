@@ -0,0 +1,424 @@
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use super::{
+    document::{Document, DocumentError},
+    plotter::{export_gcode, export_hpgl, Origin, PlotterSettings, Units},
+    svg::{export_svg, import_svg},
+    trajectory::{
+        plan_trajectory, trajectory_csv, TrajectorySample, TrajectorySettings, VelocityProfile,
+    },
+};
+
+const USAGE: &str = "usage: gl-test convert <input> <output> [options]
+
+Converts a scene without opening a window. The input can be a saved scene
+(.ron or .json) or an SVG. The output format is taken from the extension
+of the output unless --format is given.
+
+options:
+  --format svg|json|ron|points|gcode|hpgl|trajectory|trajectory-json
+  --samples <n>         segments each curve is split into for points output,
+                        so n + 1 points with both ends included, which is CSV
+                        with a row for each point on every path where curves
+                        that follow on from each other share their end points
+  --tolerance <world>   how far plotter lines can stray from the curves
+  --scale <units>       plotter units for every world unit
+  --units mm|in         plotter units
+  --origin bottom-left|top-left|center|world
+                        the point of the drawing at the plotter origin
+  --feed-rate <units>   G-code drawing speed in units per minute
+  --pen-up <gcode>      G-code that lifts the pen
+  --pen-down <gcode>    G-code that lowers the pen
+  --path <n>            which path a trajectory follows, starting from 0
+  --spacing <world>     distance between trajectory samples
+  --profile trapezoidal|s-curve
+                        how the trajectory speeds up and slows down
+  --max-speed <world>   trajectory speed limit per second
+  --max-acceleration <world>
+  --max-jerk <world>    how fast acceleration can change for s-curve
+  --max-lateral-acceleration <world>
+                        slows the trajectory down around bends
+  --corner-speed <world>
+                        trajectory speed where curves meet at an angle,
+                        0 by default so that corners are taken from rest";
+
+/// How many points are sampled along each curve by default, the same
+/// number that curves are drawn with
+const DEFAULT_SAMPLES: usize = 60;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    Svg,
+    Json,
+    Ron,
+    /// CSV of points sampled along every path
+    Points,
+    Gcode,
+    Hpgl,
+    /// CSV of timed samples along a path
+    Trajectory,
+    TrajectoryJson,
+}
+
+impl Format {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "svg" => Some(Format::Svg),
+            "json" => Some(Format::Json),
+            "ron" => Some(Format::Ron),
+            "points" | "csv" => Some(Format::Points),
+            "gcode" | "nc" | "gc" => Some(Format::Gcode),
+            "hpgl" | "plt" => Some(Format::Hpgl),
+            "trajectory" => Some(Format::Trajectory),
+            "trajectory-json" => Some(Format::TrajectoryJson),
+            _ => None,
+        }
+    }
+
+    fn from_path(path: &Path) -> Option<Self> {
+        Self::from_name(path.extension()?.to_str()?)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ConvertArgs {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    pub format: Format,
+    pub samples: usize,
+    pub plotter: PlotterSettings,
+    /// Index into [Document::curve_paths] of the path a trajectory follows
+    pub path: usize,
+    pub trajectory: TrajectorySettings,
+}
+
+/// The value after an option, parsed as T
+fn option_value<'a, T: FromStr>(
+    args: &mut impl Iterator<Item = &'a String>,
+    option: &str,
+) -> Result<T, String> {
+    let value = args
+        .next()
+        .ok_or_else(|| format!("{option} needs a value"))?;
+    value
+        .parse()
+        .map_err(|_| format!("{value} isn't a valid value for {option}"))
+}
+
+/// The value after an option, which has to be more than 0
+fn positive_value<'a>(
+    args: &mut impl Iterator<Item = &'a String>,
+    option: &str,
+) -> Result<f32, String> {
+    let value: f32 = option_value(args, option)?;
+    if value > 0.0 {
+        Ok(value)
+    } else {
+        Err(format!("{option} needs to be more than 0"))
+    }
+}
+
+impl ConvertArgs {
+    /// Parses the arguments that come after `convert`
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut paths = Vec::new();
+        let mut format = None;
+        let mut samples = DEFAULT_SAMPLES;
+        let mut plotter = PlotterSettings::default();
+        let mut path = 0;
+        let mut trajectory = TrajectorySettings::default();
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--format" => {
+                    let name: String = option_value(&mut args, arg)?;
+                    format = Some(
+                        Format::from_name(&name).ok_or_else(|| format!("unknown format {name}"))?,
+                    );
+                }
+                "--samples" => {
+                    samples = option_value(&mut args, arg)?;
+                    if samples == 0 {
+                        return Err("--samples needs to be more than 0".to_owned());
+                    }
+                }
+                "--tolerance" => plotter.tolerance = positive_value(&mut args, arg)?,
+                "--scale" => plotter.scale = positive_value(&mut args, arg)?,
+                "--feed-rate" => plotter.feed_rate = positive_value(&mut args, arg)?,
+                "--pen-up" => plotter.pen_up = option_value(&mut args, arg)?,
+                "--pen-down" => plotter.pen_down = option_value(&mut args, arg)?,
+                "--units" => {
+                    plotter.units = match option_value::<String>(&mut args, arg)?.as_str() {
+                        "mm" => Units::Millimeters,
+                        "in" => Units::Inches,
+                        units => return Err(format!("unknown units {units}")),
+                    }
+                }
+                "--origin" => {
+                    plotter.origin = match option_value::<String>(&mut args, arg)?.as_str() {
+                        "bottom-left" => Origin::BottomLeft,
+                        "top-left" => Origin::TopLeft,
+                        "center" => Origin::Center,
+                        "world" => Origin::World,
+                        origin => return Err(format!("unknown origin {origin}")),
+                    }
+                }
+                "--path" => path = option_value(&mut args, arg)?,
+                "--spacing" => trajectory.spacing = positive_value(&mut args, arg)?,
+                "--max-speed" => trajectory.max_speed = positive_value(&mut args, arg)?,
+                "--max-acceleration" => {
+                    trajectory.max_acceleration = positive_value(&mut args, arg)?
+                }
+                "--max-jerk" => trajectory.max_jerk = positive_value(&mut args, arg)?,
+                "--max-lateral-acceleration" => {
+                    trajectory.max_lateral_acceleration = positive_value(&mut args, arg)?
+                }
+                "--corner-speed" => {
+                    trajectory.corner_speed = option_value(&mut args, arg)?;
+                    if trajectory.corner_speed < 0.0 {
+                        return Err(format!("{arg} can't be less than 0"));
+                    }
+                }
+                "--profile" => {
+                    trajectory.profile = match option_value::<String>(&mut args, arg)?.as_str() {
+                        "trapezoidal" => VelocityProfile::Trapezoidal,
+                        "s-curve" => VelocityProfile::SCurve,
+                        profile => return Err(format!("unknown profile {profile}")),
+                    }
+                }
+                _ if arg.starts_with("--") => return Err(format!("unknown option {arg}")),
+                _ => paths.push(PathBuf::from(arg)),
+            }
+        }
+
+        let [input, output] = <[PathBuf; 2]>::try_from(paths)
+            .map_err(|_| "expected an input and an output file".to_owned())?;
+        let format = format
+            .or_else(|| Format::from_path(&output))
+            .ok_or("can't tell the output format from its extension, use --format")?;
+
+        Ok(Self {
+            input,
+            output,
+            format,
+            samples,
+            plotter,
+            path,
+            trajectory,
+        })
+    }
+}
+
+fn read_document(path: &Path) -> Result<Document, DocumentError> {
+    let text = std::fs::read_to_string(path)?;
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("svg") => import_svg(&text),
+        Some("json") => Document::from_json(&text),
+        _ => Document::from_ron(&text),
+    }
+}
+
+/// Samples samples + 1 points along each curve of every path in the
+/// document, with curves that aren't in a path treated as paths of their own
+pub fn sample_points(document: &Document, samples: usize) -> String {
+    let curves = document.curve_positions();
+    let mut csv = String::from("path,x,y\n");
+    for (path, indices) in document.curve_paths().iter().enumerate() {
+        for (i, index) in indices.iter().enumerate() {
+            // Every curve after the first starts where the last one ended
+            let points = super::generate_bezier_points_with_offset(
+                &curves[*index],
+                Some(samples),
+                None,
+                true,
+            );
+            for point in points.skip(if i == 0 { 0 } else { 1 }) {
+                writeln!(csv, "{path},{},{}", point.x(), point.y()).unwrap();
+            }
+        }
+    }
+    csv
+}
+
+/// Plans a trajectory along the path at index in [Document::curve_paths]
+fn document_trajectory(
+    document: &Document,
+    path: usize,
+    settings: &TrajectorySettings,
+) -> Result<Vec<TrajectorySample>, DocumentError> {
+    let curves = document.curve_positions();
+    let segments = document
+        .curve_paths()
+        .get(path)
+        .ok_or(DocumentError::MissingPath(path))?
+        .iter()
+        .map(|index| curves[*index])
+        .collect();
+    Ok(plan_trajectory(segments, settings))
+}
+
+pub fn convert(args: &ConvertArgs) -> Result<(), DocumentError> {
+    let document = read_document(&args.input)?;
+    let output = match args.format {
+        Format::Svg => export_svg(&document),
+        Format::Json => document.to_json()?,
+        Format::Ron => document.to_ron()?,
+        Format::Points => sample_points(&document, args.samples),
+        Format::Gcode => export_gcode(&document, &args.plotter),
+        Format::Hpgl => export_hpgl(&document, &args.plotter),
+        Format::Trajectory => trajectory_csv(&document_trajectory(
+            &document,
+            args.path,
+            &args.trajectory,
+        )?),
+        Format::TrajectoryJson => serde_json::to_string_pretty(&document_trajectory(
+            &document,
+            args.path,
+            &args.trajectory,
+        )?)?,
+    };
+    std::fs::write(&args.output, output)?;
+    Ok(())
+}
+
+/// Runs the `convert` command with the arguments that come
+/// after it, returning the exit code for the process
+pub fn run_convert_command(args: &[String]) -> i32 {
+    let args = match ConvertArgs::parse(args) {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("{error}\n\n{USAGE}");
+            return 2;
+        }
+    };
+
+    match convert(&args) {
+        Ok(()) => 0,
+        Err(error) => {
+            eprintln!(
+                "Couldn't convert {} to {}: {error}",
+                args.input.display(),
+                args.output.display()
+            );
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bezier::document::PathData, position::Position, rendering::primitives::StrokeStyle,
+    };
+
+    fn args(args: &[&str]) -> Result<ConvertArgs, String> {
+        let args: Vec<_> = args.iter().map(|arg| arg.to_string()).collect();
+        ConvertArgs::parse(&args)
+    }
+
+    #[test]
+    fn parse_args() {
+        let parsed = args(&["scene.ron", "out.SVG"]).unwrap();
+        assert_eq!(parsed.format, Format::Svg);
+        assert_eq!(parsed.samples, DEFAULT_SAMPLES);
+
+        let parsed = args(&["--samples", "4", "in.svg", "out.txt", "--format", "points"]).unwrap();
+        assert_eq!(parsed.format, Format::Points);
+        assert_eq!(parsed.samples, 4);
+        assert_eq!(parsed.input, PathBuf::from("in.svg"));
+
+        assert!(args(&["in.svg", "out.txt"]).is_err());
+        assert!(args(&["in.svg"]).is_err());
+        assert!(args(&["in.svg", "out.svg", "--samples", "0"]).is_err());
+
+        let parsed = args(&[
+            "in.svg", "out.nc", "--units", "in", "--origin", "center", "--pen-up", "M5",
+        ])
+        .unwrap();
+        assert_eq!(parsed.format, Format::Gcode);
+        assert_eq!(parsed.plotter.units, Units::Inches);
+        assert_eq!(parsed.plotter.origin, Origin::Center);
+        assert_eq!(parsed.plotter.pen_up, "M5");
+        assert!(args(&["in.svg", "out.plt", "--feed-rate", "fast"]).is_err());
+        for value in ["0", "-100", "NaN"] {
+            assert!(args(&["in.svg", "out.nc", "--feed-rate", value]).is_err());
+            assert!(args(&["in.svg", "out.plt", "--scale", value]).is_err());
+        }
+
+        let parsed = args(&[
+            "in.svg",
+            "out.json",
+            "--format",
+            "trajectory-json",
+            "--path",
+            "2",
+            "--profile",
+            "s-curve",
+            "--max-speed",
+            "50",
+            "--corner-speed",
+            "0.5",
+        ])
+        .unwrap();
+        assert_eq!(parsed.format, Format::TrajectoryJson);
+        assert_eq!(parsed.path, 2);
+        assert_eq!(parsed.trajectory.profile, VelocityProfile::SCurve);
+        assert_eq!(parsed.trajectory.max_speed, 50.0);
+        assert_eq!(parsed.trajectory.corner_speed, 0.5);
+        assert!(args(&["in.svg", "out.csv", "--max-jerk", "-1"]).is_err());
+        assert!(args(&["in.svg", "out.csv", "--corner-speed", "-1"]).is_err());
+        assert!(args(&["in.svg", "out.csv", "--corner-speed", "0"]).is_ok());
+    }
+
+    #[test]
+    fn samples_paths() {
+        let mut document = Document::default();
+        let line = |from: f32, to: f32| [from, from, to, to].map(|x| Position::new(x, 0.0));
+        let first = document.push_curve(line(0.0, 10.0), StrokeStyle::default());
+        let second = document.push_curve(line(10.0, 20.0), StrokeStyle::default());
+        document.push_curve(line(50.0, 60.0), StrokeStyle::default());
+        document.paths.push(PathData {
+            curves: vec![first, second],
+            followers: Vec::new(),
+        });
+
+        let csv = sample_points(&document, 2);
+        let rows: Vec<_> = csv.lines().collect();
+        assert_eq!(rows[0], "path,x,y");
+        // The shared point between the curves is only sampled once
+        assert_eq!(
+            &rows[1..6],
+            ["0,0,0", "0,5,0", "0,10,0", "0,15,0", "0,20,0"]
+        );
+        assert_eq!(&rows[6..], ["1,50,0", "1,55,0", "1,60,0"]);
+    }
+
+    #[test]
+    fn converts_between_formats() {
+        let directory = std::env::temp_dir().join(format!("convert-test-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let svg = directory.join("in.svg");
+        let json = directory.join("scene.json");
+        std::fs::write(&svg, r#"<svg><path d="M0 0 L10 0 L10 10"/></svg>"#).unwrap();
+
+        let to_json = args(&[svg.to_str().unwrap(), json.to_str().unwrap()]).unwrap();
+        convert(&to_json).unwrap();
+        let document = Document::from_json(&std::fs::read_to_string(&json).unwrap()).unwrap();
+        assert_eq!(document.curves.len(), 2);
+        assert_eq!(document.paths.len(), 1);
+
+        // Reading the JSON back gives the same document
+        let ron = directory.join("scene.ron");
+        convert(&args(&[json.to_str().unwrap(), ron.to_str().unwrap()]).unwrap()).unwrap();
+        let text = std::fs::read_to_string(&ron).unwrap();
+        assert_eq!(Document::from_ron(&text).unwrap(), document);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,885 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use bevy::{
+    ecs::{
+        entity::Entity,
+        query::{Or, With},
+        system::{CommandQueue, Commands, Resource, SystemState},
+        world::World,
+    },
+    utils::{HashMap, HashSet},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    history::History,
+    input_map::{Action, ActionInput},
+    position::Position,
+    rendering::{primitives::StrokeStyle, CameraPosition, CameraZoom},
+    selection::{Connected, Connection, Constraint, SelectionSet},
+    title::TitleMessages,
+};
+
+use super::{
+    components::{BezierCurve, BezierPath, FollowMode, FollowPath},
+    history::despawn_curve,
+};
+
+/// Version written to new documents. Bump it whenever a change to the
+/// format means older versions of the app can't read new documents
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Points are referred to by an id that is only meaningful within a
+/// document, each one is given a new entity when the document is loaded
+pub type PointId = u32;
+
+/// Everything in a scene that is saved to and loaded from a file
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Document {
+    pub version: u32,
+    #[serde(default)]
+    pub camera: CameraData,
+    #[serde(default)]
+    pub points: Vec<PointData>,
+    #[serde(default)]
+    pub curves: Vec<CurveData>,
+    #[serde(default)]
+    pub paths: Vec<PathData>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct CameraData {
+    pub position: Position,
+    pub zoom: f32,
+}
+
+impl Default for CameraData {
+    fn default() -> Self {
+        Self {
+            position: Position::default(),
+            zoom: CameraZoom::default().0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct PointData {
+    pub id: PointId,
+    pub position: Position,
+    #[serde(default)]
+    pub connections: Vec<ConnectionData>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ConnectionData {
+    pub point: PointId,
+    pub constraint: ConstraintData,
+}
+
+/// A [Constraint] with entities swapped for [PointId]s
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ConstraintData {
+    Rigid,
+    Mirror(PointId),
+}
+
+/// A curve along with the role each of its points has in it
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct CurveData {
+    pub start_point: PointId,
+    pub start_handle: PointId,
+    pub end_handle: PointId,
+    pub end_point: PointId,
+    #[serde(default)]
+    pub style: StrokeStyle,
+}
+
+impl CurveData {
+    pub fn control_points(&self) -> [PointId; 4] {
+        [
+            self.start_point,
+            self.start_handle,
+            self.end_handle,
+            self.end_point,
+        ]
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct PathData {
+    /// Indices into [Document::curves] in the order they are followed
+    pub curves: Vec<usize>,
+    #[serde(default)]
+    pub followers: Vec<FollowerData>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct FollowerData {
+    pub speed: f32,
+    pub mode: FollowMode,
+    pub progress: f32,
+}
+
+/// Key of the [TitleMessages] entry that tells how the last
+/// save, load, import or export went
+pub const FILE_MESSAGE: &str = "file";
+
+#[derive(Debug)]
+pub enum DocumentError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    Serialize(ron::Error),
+    Json(serde_json::Error),
+    /// An SVG being imported isn't valid XML
+    Xml(roxmltree::Error),
+    /// The document was written by a newer version of the app
+    UnsupportedVersion(u32),
+    /// The camera can't be zoomed by this much
+    InvalidZoom(f32),
+    /// Two points in the document have the same id
+    DuplicatePoint(PointId),
+    /// A curve or connection refers to a point that isn't in the document
+    MissingPoint(PointId),
+    /// A point is used as more than one control point, since
+    /// every curve owns its own control points
+    SharedPoint(PointId),
+    /// A path refers to a curve that isn't in the document
+    MissingCurve(usize),
+    /// A path was asked for by index but the document has fewer paths
+    MissingPath(usize),
+}
+
+impl fmt::Display for DocumentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DocumentError::Io(error) => write!(f, "{error}"),
+            DocumentError::Parse(error) => write!(f, "{error}"),
+            DocumentError::Serialize(error) => write!(f, "{error}"),
+            DocumentError::Json(error) => write!(f, "{error}"),
+            DocumentError::Xml(error) => write!(f, "{error}"),
+            DocumentError::UnsupportedVersion(version) => write!(
+                f,
+                "version {version} is newer than the supported version {FORMAT_VERSION}"
+            ),
+            DocumentError::InvalidZoom(zoom) => {
+                write!(f, "camera zoom {zoom} isn't a positive number")
+            }
+            DocumentError::DuplicatePoint(id) => write!(f, "point {id} appears more than once"),
+            DocumentError::MissingPoint(id) => write!(f, "point {id} doesn't exist"),
+            DocumentError::SharedPoint(id) => {
+                write!(f, "point {id} is used as more than one control point")
+            }
+            DocumentError::MissingCurve(index) => write!(f, "curve {index} doesn't exist"),
+            DocumentError::MissingPath(index) => write!(f, "path {index} doesn't exist"),
+        }
+    }
+}
+
+impl std::error::Error for DocumentError {}
+
+impl From<std::io::Error> for DocumentError {
+    fn from(error: std::io::Error) -> Self {
+        DocumentError::Io(error)
+    }
+}
+
+impl From<ron::error::SpannedError> for DocumentError {
+    fn from(error: ron::error::SpannedError) -> Self {
+        DocumentError::Parse(error)
+    }
+}
+
+impl From<serde_json::Error> for DocumentError {
+    fn from(error: serde_json::Error) -> Self {
+        DocumentError::Json(error)
+    }
+}
+
+impl From<roxmltree::Error> for DocumentError {
+    fn from(error: roxmltree::Error) -> Self {
+        DocumentError::Xml(error)
+    }
+}
+
+impl From<ron::Error> for DocumentError {
+    fn from(error: ron::Error) -> Self {
+        DocumentError::Serialize(error)
+    }
+}
+
+impl Document {
+    /// Takes everything in the world that is part of the document
+    pub fn capture(world: &mut World) -> Self {
+        let mut curves: Vec<_> = world
+            .query::<(Entity, &BezierCurve, Option<&StrokeStyle>)>()
+            .iter(world)
+            .map(|(entity, curve, style)| (entity, curve.clone(), style.copied()))
+            .collect();
+        // Sorted so that saving the same scene twice gives the same file
+        curves.sort_by_key(|(entity, ..)| *entity);
+
+        let point_ids: HashMap<Entity, PointId> = curves
+            .iter()
+            .flat_map(|(_, curve, _)| curve.control_points())
+            .enumerate()
+            .map(|(id, entity)| (entity, id as PointId))
+            .collect();
+        let curve_indices: HashMap<Entity, usize> = curves
+            .iter()
+            .enumerate()
+            .map(|(index, (entity, ..))| (*entity, index))
+            .collect();
+
+        let mut points = Vec::with_capacity(point_ids.len());
+        for entity in curves
+            .iter()
+            .flat_map(|(_, curve, _)| curve.control_points())
+        {
+            let connections = world
+                .get::<Connection>(entity)
+                .map(|connection| {
+                    connection
+                        .0
+                        .iter()
+                        .filter_map(|connected| connection_data(connected, &point_ids))
+                        .collect()
+                })
+                .unwrap_or_default();
+            points.push(PointData {
+                id: point_ids[&entity],
+                position: world.get::<Position>(entity).copied().unwrap_or_default(),
+                connections,
+            });
+        }
+
+        let mut paths: Vec<_> = world
+            .query::<(Entity, &BezierPath)>()
+            .iter(world)
+            .map(|(entity, path)| (entity, path.0.clone()))
+            .collect();
+        paths.sort_by_key(|(entity, _)| *entity);
+        let mut followers = world.query::<&FollowPath>();
+        let paths = paths
+            .into_iter()
+            .map(|(path, curves)| PathData {
+                curves: curves
+                    .iter()
+                    .filter_map(|curve| curve_indices.get(curve).copied())
+                    .collect(),
+                followers: followers
+                    .iter(world)
+                    .filter(|follower| follower.path == path)
+                    .map(|follower| FollowerData {
+                        speed: follower.speed,
+                        mode: follower.mode,
+                        progress: follower.progress,
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        let camera = CameraData {
+            position: world
+                .get_resource::<CameraPosition>()
+                .map(|position| position.0)
+                .unwrap_or_default(),
+            zoom: world
+                .get_resource::<CameraZoom>()
+                .copied()
+                .unwrap_or_default()
+                .0,
+        };
+
+        Self {
+            version: FORMAT_VERSION,
+            camera,
+            points,
+            curves: curves
+                .into_iter()
+                .map(|(_, curve, style)| CurveData {
+                    start_point: point_ids[&curve.start_point],
+                    start_handle: point_ids[&curve.start_handle],
+                    end_handle: point_ids[&curve.end_handle],
+                    end_point: point_ids[&curve.end_point],
+                    style: style.unwrap_or_default(),
+                })
+                .collect(),
+            paths,
+        }
+    }
+
+    /// The positions of the control points of every curve
+    pub fn curve_positions(&self) -> Vec<[Position; 4]> {
+        let positions: HashMap<PointId, Position> = self
+            .points
+            .iter()
+            .map(|point| (point.id, point.position))
+            .collect();
+        self.curves
+            .iter()
+            .map(|curve| {
+                curve
+                    .control_points()
+                    .map(|id| positions.get(&id).copied().unwrap_or_default())
+            })
+            .collect()
+    }
+
+    /// The indices of the curves in every path, followed by every
+    /// curve that isn't in a path as a path of its own
+    pub fn curve_paths(&self) -> Vec<Vec<usize>> {
+        let mut in_path = vec![false; self.curves.len()];
+        let mut paths = Vec::new();
+        for path in self.paths.iter() {
+            for index in path.curves.iter() {
+                in_path[*index] = true;
+            }
+            paths.push(path.curves.clone());
+        }
+        paths.extend(
+            (0..self.curves.len())
+                .filter(|index| !in_path[*index])
+                .map(|index| vec![index]),
+        );
+        paths
+    }
+
+    /// Parses a document and checks that it can be spawned
+    pub fn from_ron(text: &str) -> Result<Self, DocumentError> {
+        let document: Document = ron::from_str(text)?;
+        document.validate()?;
+        Ok(document)
+    }
+
+    pub fn to_ron(&self) -> Result<String, DocumentError> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+
+    /// Parses a document written with [Document::to_json] and
+    /// checks that it can be spawned
+    pub fn from_json(text: &str) -> Result<Self, DocumentError> {
+        let document: Document = serde_json::from_str(text)?;
+        document.validate()?;
+        Ok(document)
+    }
+
+    pub fn to_json(&self) -> Result<String, DocumentError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    fn validate(&self) -> Result<(), DocumentError> {
+        if self.version > FORMAT_VERSION {
+            return Err(DocumentError::UnsupportedVersion(self.version));
+        }
+
+        let zoom = self.camera.zoom;
+        if !zoom.is_finite() || zoom <= 0.0 {
+            return Err(DocumentError::InvalidZoom(zoom));
+        }
+
+        let mut ids = HashSet::new();
+        for point in self.points.iter() {
+            if !ids.insert(point.id) {
+                return Err(DocumentError::DuplicatePoint(point.id));
+            }
+        }
+        let referenced = self
+            .curves
+            .iter()
+            .flat_map(CurveData::control_points)
+            .chain(self.points.iter().flat_map(|point| {
+                point.connections.iter().flat_map(|connection| {
+                    let mirrored = match connection.constraint {
+                        ConstraintData::Mirror(other) => Some(other),
+                        _ => None,
+                    };
+                    [Some(connection.point), mirrored].into_iter().flatten()
+                })
+            }));
+        for id in referenced {
+            if !ids.contains(&id) {
+                return Err(DocumentError::MissingPoint(id));
+            }
+        }
+
+        // Curves that share points would be spawned with points of
+        // their own, so they would silently come apart when loaded
+        let mut control_points = HashSet::new();
+        for id in self.curves.iter().flat_map(CurveData::control_points) {
+            if !control_points.insert(id) {
+                return Err(DocumentError::SharedPoint(id));
+            }
+        }
+
+        for path in self.paths.iter() {
+            if let Some(index) = path
+                .curves
+                .iter()
+                .find(|index| **index >= self.curves.len())
+            {
+                return Err(DocumentError::MissingCurve(*index));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Spawns every curve and path in the document into the world
+    /// alongside anything that is already there
+    pub fn spawn(&self, world: &mut World) -> Spawned {
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, world);
+        let curves: Vec<_> = self
+            .curves
+            .iter()
+            .zip(self.curve_positions())
+            .map(|(curve, positions)| {
+                let [start_point, start_handle, end_handle, end_point] = positions;
+                let entity = super::create_bezier_curve(
+                    &mut commands,
+                    start_point,
+                    start_handle,
+                    end_handle,
+                    end_point,
+                );
+                commands.entity(entity).insert(curve.style);
+                entity
+            })
+            .collect();
+
+        for path in self.paths.iter() {
+            let path_entity = commands
+                .spawn(BezierPath(
+                    path.curves.iter().map(|index| curves[*index]).collect(),
+                ))
+                .id();
+            for follower in path.followers.iter() {
+                commands.spawn(super::path_follower_bundle(FollowPath::new(
+                    path_entity,
+                    follower.speed,
+                    follower.mode,
+                    follower.progress,
+                )));
+            }
+        }
+        queue.apply(world);
+
+        let mut entities = HashMap::new();
+        for (curve, entity) in self.curves.iter().zip(curves.iter().copied()) {
+            let control_points = world.get::<BezierCurve>(entity).unwrap().control_points();
+            entities.extend(curve.control_points().into_iter().zip(control_points));
+        }
+
+        // The curves come with the default connections,
+        // which are replaced by the ones that were saved
+        for point in self.points.iter() {
+            let Some(entity) = entities.get(&point.id).copied() else {
+                continue;
+            };
+            let connected: Vec<_> = point
+                .connections
+                .iter()
+                .filter_map(|connection| connected(connection, &entities))
+                .collect();
+            if connected.is_empty() {
+                world.entity_mut(entity).remove::<Connection>();
+            } else {
+                world.entity_mut(entity).insert(Connection(connected));
+            }
+        }
+
+        Spawned {
+            points: entities,
+            curves,
+        }
+    }
+
+    fn apply_camera(&self, world: &mut World) {
+        if let Some(mut position) = world.get_resource_mut::<CameraPosition>() {
+            position.0 = self.camera.position;
+        }
+        if let Some(mut zoom) = world.get_resource_mut::<CameraZoom>() {
+            zoom.0 = self.camera.zoom;
+        }
+    }
+
+    /// Adds a curve with the same connections that new curves are
+    /// given, where each terminal point carries its handle along.
+    /// Returns the index of the new curve
+    pub fn push_curve(&mut self, positions: [Position; 4], style: StrokeStyle) -> usize {
+        let first_id = self
+            .points
+            .iter()
+            .map(|point| point.id + 1)
+            .max()
+            .unwrap_or_default();
+        let ids = [0, 1, 2, 3].map(|offset| first_id + offset);
+        let rigid = |point| {
+            vec![ConnectionData {
+                point,
+                constraint: ConstraintData::Rigid,
+            }]
+        };
+        let connections = [rigid(ids[1]), Vec::new(), Vec::new(), rigid(ids[2])];
+
+        for ((id, position), connections) in ids.into_iter().zip(positions).zip(connections) {
+            self.points.push(PointData {
+                id,
+                position,
+                connections,
+            });
+        }
+        self.curves.push(CurveData {
+            start_point: ids[0],
+            start_handle: ids[1],
+            end_handle: ids[2],
+            end_point: ids[3],
+            style,
+        });
+        self.curves.len() - 1
+    }
+
+    /// Connects the end point of the first curve and the start point of
+    /// the second in both directions, the same as curves joined by the pen
+    pub fn join_curves(&mut self, first: usize, second: usize) {
+        let end_point = self.curves[first].end_point;
+        let start_point = self.curves[second].start_point;
+        for (host, other) in [(end_point, start_point), (start_point, end_point)] {
+            // Curves are usually joined right after they are pushed
+            // so their points are near the end
+            if let Some(point) = self.points.iter_mut().rev().find(|point| point.id == host) {
+                point.connections.push(ConnectionData {
+                    point: other,
+                    constraint: ConstraintData::Rigid,
+                });
+            }
+        }
+    }
+
+    /// Makes the end handle of the first curve and the start handle of the
+    /// second mirror each other around the end point of the first, for
+    /// curves that are joined smoothly
+    pub fn mirror_handles(&mut self, first: usize, second: usize) {
+        let CurveData {
+            end_point,
+            end_handle,
+            ..
+        } = self.curves[first];
+        let start_handle = self.curves[second].start_handle;
+        let Some(point) = self
+            .points
+            .iter_mut()
+            .rev()
+            .find(|point| point.id == end_point)
+        else {
+            return;
+        };
+
+        // The end handle is already carried along by its terminal point
+        point
+            .connections
+            .retain(|connection| connection.point != end_handle);
+        point.connections.extend([
+            ConnectionData {
+                point: end_handle,
+                constraint: ConstraintData::Mirror(start_handle),
+            },
+            ConnectionData {
+                point: start_handle,
+                constraint: ConstraintData::Mirror(end_handle),
+            },
+        ]);
+    }
+}
+
+impl Default for Document {
+    fn default() -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            camera: CameraData::default(),
+            points: Vec::new(),
+            curves: Vec::new(),
+            paths: Vec::new(),
+        }
+    }
+}
+
+/// The entities that were spawned for a [Document]
+pub struct Spawned {
+    /// The entity given to each point
+    #[allow(dead_code)]
+    pub points: HashMap<PointId, Entity>,
+    /// The entity of each curve in the same order as [Document::curves]
+    pub curves: Vec<Entity>,
+}
+
+fn connection_data(
+    connected: &Connected,
+    point_ids: &HashMap<Entity, PointId>,
+) -> Option<ConnectionData> {
+    let constraint = match connected.constraint {
+        Constraint::Rigid => ConstraintData::Rigid,
+        Constraint::Mirror(other) => ConstraintData::Mirror(*point_ids.get(&other)?),
+    };
+
+    Some(ConnectionData {
+        point: *point_ids.get(&connected.entity)?,
+        constraint,
+    })
+}
+
+fn connected(
+    connection: &ConnectionData,
+    entities: &HashMap<PointId, Entity>,
+) -> Option<Connected> {
+    let constraint = match connection.constraint {
+        ConstraintData::Rigid => Constraint::Rigid,
+        ConstraintData::Mirror(other) => Constraint::Mirror(*entities.get(&other)?),
+    };
+
+    Some(Connected {
+        entity: *entities.get(&connection.point)?,
+        constraint,
+    })
+}
+
+/// Despawns every curve, path and path follower
+pub fn clear_scene(world: &mut World) {
+    let curves: Vec<_> = world
+        .query::<(Entity, &BezierCurve)>()
+        .iter(world)
+        .map(|(entity, _)| entity)
+        .collect();
+    for curve in curves {
+        despawn_curve(world, curve);
+    }
+
+    let others: Vec<_> = world
+        .query_filtered::<Entity, Or<(With<BezierPath>, With<FollowPath>)>>()
+        .iter(world)
+        .collect();
+    for entity in others {
+        world.despawn(entity);
+    }
+
+    // Anything that refers to the old entities is now meaningless
+    if let Some(mut history) = world.get_resource_mut::<History>() {
+        history.clear();
+    }
+    if let Some(mut selection_set) = world.get_resource_mut::<SelectionSet>() {
+        selection_set.clear();
+    }
+}
+
+pub fn save_document(world: &mut World, path: impl AsRef<Path>) -> Result<(), DocumentError> {
+    let text = Document::capture(world).to_ron()?;
+    std::fs::write(path, text)?;
+    Ok(())
+}
+
+/// Replaces the scene with the document at path. The
+/// scene is left alone if the document can't be loaded
+pub fn load_document(world: &mut World, path: impl AsRef<Path>) -> Result<(), DocumentError> {
+    let text = std::fs::read_to_string(path)?;
+    let document = Document::from_ron(&text)?;
+    clear_scene(world);
+    document.spawn(world);
+    document.apply_camera(world);
+    Ok(())
+}
+
+/// The file that [Action::Save] and [Action::Open] use
+#[derive(Resource, Clone, Debug)]
+pub struct DocumentPath(pub PathBuf);
+
+impl Default for DocumentPath {
+    fn default() -> Self {
+        Self(PathBuf::from("scene.ron"))
+    }
+}
+
+pub fn save_load_system(world: &mut World, input_state: &mut SystemState<ActionInput>) {
+    let input = input_state.get(world);
+    // Loading in the middle of a drag would leave it dragging nothing
+    if input.any_button_pressed() {
+        return;
+    }
+
+    let save = input.just_pressed(Action::Save);
+    let open = input.just_pressed(Action::Open);
+    let path = world.resource::<DocumentPath>().0.clone();
+    let status = if save {
+        match save_document(world, &path) {
+            Ok(()) => {
+                super::autosave::document_saved(world, &path);
+                format!("Saved to {}", path.display())
+            }
+            Err(error) => {
+                eprintln!("Couldn't save to {}: {error}", path.display());
+                format!("Couldn't save to {}", path.display())
+            }
+        }
+    } else if open {
+        match load_document(world, &path) {
+            Ok(()) => {
+                super::autosave::document_saved(world, &path);
+                format!("Loaded {}", path.display())
+            }
+            Err(error) => {
+                eprintln!("Couldn't load {}: {error}", path.display());
+                format!("Couldn't load {}", path.display())
+            }
+        }
+    } else {
+        return;
+    };
+    world
+        .resource_mut::<TitleMessages>()
+        .set(FILE_MESSAGE, status);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    fn spawn_scene(world: &mut World) -> [Entity; 2] {
+        world.insert_resource(CameraPosition(Position::new(-10.0, 20.0)));
+        world.insert_resource(CameraZoom(2.0));
+        let curves = world.run_system_once(|mut commands: Commands| {
+            [0.0, 40.0].map(|x| {
+                super::super::create_bezier_curve(
+                    &mut commands,
+                    Position::new(x, 0.0),
+                    Position::new(x, 10.0),
+                    Position::new(x + 30.0, 10.0),
+                    Position::new(x + 30.0, 0.0),
+                )
+            })
+        });
+        let path = world.spawn(BezierPath(curves.to_vec())).id();
+        world.spawn(FollowPath::new(path, 50.0, FollowMode::PingPong, 0.25));
+
+        // Mirror the end handle of the first curve with the
+        // start handle of the second around the first end point
+        let [first, second] = curves.map(|curve| world.get::<BezierCurve>(curve).unwrap().clone());
+        world.entity_mut(first.end_point).insert(Connection(vec![
+            Connected {
+                entity: first.end_handle,
+                constraint: Constraint::Rigid,
+            },
+            Connected {
+                entity: second.start_handle,
+                constraint: Constraint::Mirror(first.end_handle),
+            },
+        ]));
+        curves
+    }
+
+    #[test]
+    fn save_and_load() {
+        let mut world = World::new();
+        spawn_scene(&mut world);
+        let document = Document::capture(&mut world);
+        assert_eq!(document.points.len(), 8);
+        assert_eq!(document.paths[0].curves, vec![0, 1]);
+        assert_eq!(
+            document.points[3].connections[1],
+            ConnectionData {
+                point: 5,
+                constraint: ConstraintData::Mirror(2),
+            }
+        );
+
+        let text = document.to_ron().unwrap();
+        let loaded = Document::from_ron(&text).unwrap();
+        assert_eq!(loaded, document);
+
+        // Loading gives everything new entities that
+        // are connected to each other like the old ones
+        let mut other_world = World::new();
+        other_world.init_resource::<CameraPosition>();
+        other_world.init_resource::<CameraZoom>();
+        other_world.spawn_empty();
+        let entities = loaded.spawn(&mut other_world).points;
+        loaded.apply_camera(&mut other_world);
+        let connection = other_world.get::<Connection>(entities[&3]).unwrap();
+        assert_eq!(
+            connection.0[1],
+            Connected {
+                entity: entities[&5],
+                constraint: Constraint::Mirror(entities[&2]),
+            }
+        );
+        assert_eq!(Document::capture(&mut other_world), document);
+
+        clear_scene(&mut other_world);
+        assert_eq!(other_world.entities().len(), 1);
+    }
+
+    #[test]
+    fn rejects_invalid_documents() {
+        let newer = format!("(version: {})", FORMAT_VERSION + 1);
+        assert!(matches!(
+            Document::from_ron(&newer),
+            Err(DocumentError::UnsupportedVersion(_))
+        ));
+
+        let missing_point = "(
+            version: 1,
+            points: [(id: 0, position: (0.0, 0.0))],
+            curves: [(start_point: 0, start_handle: 0, end_handle: 0, end_point: 1)],
+        )";
+        assert!(matches!(
+            Document::from_ron(missing_point),
+            Err(DocumentError::MissingPoint(1))
+        ));
+
+        let shared_point = "(
+            version: 1,
+            points: [
+                (id: 0, position: (0.0, 0.0)),
+                (id: 1, position: (0.0, 0.0)),
+                (id: 2, position: (0.0, 0.0)),
+                (id: 3, position: (0.0, 0.0)),
+                (id: 4, position: (0.0, 0.0)),
+                (id: 5, position: (0.0, 0.0)),
+                (id: 6, position: (0.0, 0.0)),
+            ],
+            curves: [
+                (start_point: 0, start_handle: 1, end_handle: 2, end_point: 3),
+                (start_point: 3, start_handle: 4, end_handle: 5, end_point: 6),
+            ],
+        )";
+        assert!(matches!(
+            Document::from_ron(shared_point),
+            Err(DocumentError::SharedPoint(3))
+        ));
+
+        let duplicate_point = "(
+            version: 1,
+            points: [(id: 0, position: (0.0, 0.0)), (id: 0, position: (1.0, 0.0))],
+        )";
+        assert!(matches!(
+            Document::from_ron(duplicate_point),
+            Err(DocumentError::DuplicatePoint(0))
+        ));
+
+        for zoom in ["0.0", "-1.0", "inf", "NaN"] {
+            let zoom = format!("(version: 1, camera: (position: (0.0, 0.0), zoom: {zoom}))");
+            assert!(matches!(
+                Document::from_ron(&zoom),
+                Err(DocumentError::InvalidZoom(_))
+            ));
+        }
+
+        let empty = Document::from_ron("(version: 1)").unwrap();
+        assert!(empty.curves.is_empty());
+    }
+}
@@ -0,0 +1,737 @@
+use bevy::ecs::{
+    entity::Entity,
+    event::{Event, EventReader},
+    system::{CommandQueue, Commands, Query, Res, ResMut, SystemState},
+    world::World,
+};
+
+use crate::{
+    gestures::Gesture,
+    history::{Edit, EntityRemap, History},
+    input_map::{Action, ActionInput},
+    position::Position,
+    rendering::{primitives::StrokeStyle, Camera, Size},
+    selection::{Connected, Connection, Constraint, SelectionSet, Tool},
+};
+
+use super::components::{BezierCurve, BezierPath};
+
+/// Everything needed to spawn a curve again after it has been despawned
+#[derive(Clone)]
+pub struct CurveSnapshot {
+    curve: Entity,
+    control_points: [Entity; 4],
+    positions: [Position; 4],
+    style: StrokeStyle,
+    /// Each path the curve is part of along with where in the path it is
+    paths: Vec<(Entity, usize)>,
+    /// Connections between the curve's control points and entities outside
+    /// of the curve, like the join between two curves drawn with the pen.
+    /// Each is the host of the connection and what it is connected to
+    joins: Vec<(Entity, Connected)>,
+}
+
+impl CurveSnapshot {
+    pub fn take(world: &mut World, curve: Entity) -> Option<Self> {
+        let control_points = world.get::<BezierCurve>(curve)?.control_points();
+        let mut positions = [Position::default(); 4];
+        for (position, entity) in positions.iter_mut().zip(control_points) {
+            *position = *world.get::<Position>(entity)?;
+        }
+
+        let paths = world
+            .query::<(Entity, &BezierPath)>()
+            .iter(world)
+            .filter_map(|(path, BezierPath(curves))| {
+                let index = curves.iter().position(|other| *other == curve)?;
+                Some((path, index))
+            })
+            .collect();
+
+        // Connections inside of the curve are made again when it is spawned
+        let joins = world
+            .query::<(Entity, &Connection)>()
+            .iter(world)
+            .flat_map(|(host, connection)| connection.0.iter().map(move |other| (host, *other)))
+            .filter(|(host, other)| {
+                control_points.contains(host) != control_points.contains(&other.entity)
+            })
+            .collect();
+
+        Some(Self {
+            curve,
+            control_points,
+            positions,
+            style: world.get::<StrokeStyle>(curve).copied().unwrap_or_default(),
+            paths,
+            joins,
+        })
+    }
+
+    /// Forgets every join that isn't between two of points
+    pub fn retain_joins_between(&mut self, points: &[Entity]) {
+        self.joins.retain(|(host, connected)| {
+            points.contains(host) && points.contains(&connected.entity)
+        });
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.paths.iter().map(|(path, _)| *path)
+    }
+
+    pub fn curve(&self) -> Entity {
+        self.curve
+    }
+
+    pub fn positions(&self) -> [Position; 4] {
+        self.positions
+    }
+
+    pub fn translate(&mut self, offset: Position) {
+        for position in self.positions.iter_mut() {
+            *position = *position + offset;
+        }
+    }
+
+    pub fn control_points(&self) -> [Entity; 4] {
+        self.control_points
+    }
+
+    /// The start and end points of the curve
+    pub fn terminal_points(&self) -> [Entity; 2] {
+        [self.control_points[0], self.control_points[3]]
+    }
+
+    /// Spawns the curve again, which gives it new entities
+    pub fn spawn(&mut self, world: &mut World, remap: &mut EntityRemap) {
+        self.spawn_curve(world, remap);
+        self.join(world, remap);
+    }
+
+    /// Spawns every curve before joining any of them, so that
+    /// curves joined to each other are joined to the new entities
+    pub fn spawn_all(curves: &mut [Self], world: &mut World, remap: &mut EntityRemap) {
+        for curve in curves.iter_mut() {
+            curve.spawn_curve(world, remap);
+        }
+        for curve in curves.iter_mut() {
+            curve.join(world, remap);
+        }
+    }
+
+    fn spawn_curve(&mut self, world: &mut World, remap: &mut EntityRemap) {
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, world);
+        let [start_point, start_handle, end_handle, end_point] = self.positions;
+        let curve = super::create_bezier_curve(
+            &mut commands,
+            start_point,
+            start_handle,
+            end_handle,
+            end_point,
+        );
+        commands.entity(curve).insert(self.style);
+        queue.apply(world);
+
+        for (path, index) in self.paths.iter() {
+            if let Some(mut path) = world.get_mut::<BezierPath>(*path) {
+                let index = (*index).min(path.0.len());
+                path.0.insert(index, curve);
+            }
+        }
+
+        let control_points = world.get::<BezierCurve>(curve).unwrap().control_points();
+        remap.insert(self.curve, curve);
+        remap.extend(self.control_points.into_iter().zip(control_points));
+        self.curve = curve;
+        self.control_points = control_points;
+    }
+
+    fn join(&mut self, world: &mut World, remap: &EntityRemap) {
+        for (host, connected) in self.joins.iter_mut() {
+            remap_join(host, connected, remap);
+            // Whatever the curve was joined to could have been deleted since
+            if world.get_entity(connected.entity).is_none() {
+                continue;
+            }
+            let Some(mut host) = world.get_entity_mut(*host) else {
+                continue;
+            };
+            match host.get_mut::<Connection>() {
+                // Both curves of a join know about it so it can be made twice
+                Some(connection) if connection.0.contains(&*connected) => {}
+                Some(mut connection) => connection.0.push(*connected),
+                None => {
+                    host.insert(Connection(vec![*connected]));
+                }
+            }
+        }
+    }
+
+    pub fn remap_entities(&mut self, remap: &EntityRemap) {
+        let swap = |entity: &mut Entity| {
+            if let Some(new_entity) = remap.get(entity) {
+                *entity = *new_entity;
+            }
+        };
+
+        swap(&mut self.curve);
+        self.control_points.iter_mut().for_each(swap);
+        self.paths.iter_mut().for_each(|(path, _)| swap(path));
+        for (host, connected) in self.joins.iter_mut() {
+            remap_join(host, connected, remap);
+        }
+    }
+}
+
+fn remap_join(host: &mut Entity, connected: &mut Connected, remap: &EntityRemap) {
+    let swap = |entity: &mut Entity| {
+        if let Some(new_entity) = remap.get(entity) {
+            *entity = *new_entity;
+        }
+    };
+
+    swap(host);
+    swap(&mut connected.entity);
+    if let Constraint::Mirror(mirrored) = &mut connected.constraint {
+        swap(mirrored);
+    }
+}
+
+/// Despawns a curve along with all of its parts straight away rather
+/// than leaving them to be cleaned up, so that a curve spawned in its
+/// place can take its place in any paths
+pub fn despawn_curve(world: &mut World, curve: Entity) {
+    let Some(bezier_curve) = world.get::<BezierCurve>(curve).cloned() else {
+        return;
+    };
+
+    for entity in bezier_curve
+        .control_points()
+        .into_iter()
+        .chain([bezier_curve.curve_primitives, curve])
+    {
+        world.despawn(entity);
+    }
+
+    for mut path in world.query::<&mut BezierPath>().iter_mut(world) {
+        path.0.retain(|other| *other != curve);
+    }
+
+    // Anything joined to the curve is left connected to nothing
+    let control_points = bezier_curve.control_points();
+    for mut connection in world.query::<&mut Connection>().iter_mut(world) {
+        if connection
+            .entities()
+            .any(|other| control_points.contains(&other))
+        {
+            connection
+                .0
+                .retain(|connected| !control_points.contains(&connected.entity));
+        }
+    }
+}
+
+/// A curve was added, it is only snapshotted once
+/// it is undone since it can be edited until then
+pub struct CurveCreated {
+    curve: Entity,
+    snapshot: Option<CurveSnapshot>,
+}
+
+impl CurveCreated {
+    pub fn new(curve: Entity) -> Self {
+        Self {
+            curve,
+            snapshot: None,
+        }
+    }
+}
+
+impl Edit for CurveCreated {
+    fn undo(&mut self, world: &mut World, _remap: &mut EntityRemap) {
+        self.snapshot = CurveSnapshot::take(world, self.curve);
+        despawn_curve(world, self.curve);
+    }
+
+    fn redo(&mut self, world: &mut World, remap: &mut EntityRemap) {
+        if let Some(snapshot) = self.snapshot.as_mut() {
+            snapshot.spawn(world, remap);
+            self.curve = snapshot.curve;
+        }
+    }
+
+    fn remap_entities(&mut self, remap: &EntityRemap) {
+        if let Some(new_curve) = remap.get(&self.curve) {
+            self.curve = *new_curve;
+        }
+        if let Some(snapshot) = self.snapshot.as_mut() {
+            snapshot.remap_entities(remap);
+        }
+    }
+}
+
+/// Curves were deleted, ordered so that spawning them again in
+/// order puts them back in the same place in their paths
+pub struct CurvesDeleted(pub Vec<CurveSnapshot>);
+
+impl CurvesDeleted {
+    /// Snapshots every curve without despawning them, for
+    /// recording curves that were added as an [Inverse](crate::history::Inverse)
+    pub fn snapshot(world: &mut World, curves: &[Entity]) -> Self {
+        let mut snapshots: Vec<_> = curves
+            .iter()
+            .filter_map(|curve| CurveSnapshot::take(world, *curve))
+            .collect();
+        // Curves earlier in a path have to be put back first
+        snapshots.sort_by_key(|snapshot| snapshot.paths.first().map(|(_, index)| *index));
+
+        Self(snapshots)
+    }
+
+    /// Snapshots and then despawns every curve
+    pub fn apply(world: &mut World, curves: &[Entity]) -> Self {
+        let deleted = Self::snapshot(world, curves);
+        for curve in curves {
+            despawn_curve(world, *curve);
+        }
+
+        deleted
+    }
+}
+
+impl Edit for CurvesDeleted {
+    fn undo(&mut self, world: &mut World, remap: &mut EntityRemap) {
+        CurveSnapshot::spawn_all(&mut self.0, world, remap);
+    }
+
+    fn redo(&mut self, world: &mut World, _remap: &mut EntityRemap) {
+        for snapshot in self.0.iter() {
+            despawn_curve(world, snapshot.curve);
+        }
+    }
+
+    fn remap_entities(&mut self, remap: &EntityRemap) {
+        for snapshot in self.0.iter_mut() {
+            snapshot.remap_entities(remap);
+        }
+    }
+}
+
+/// A curve was split in two at some point along it
+pub struct CurveSplit {
+    original: CurveSnapshot,
+    parts: [CurveSnapshot; 2],
+}
+
+impl CurveSplit {
+    /// Replaces curve with two curves that meet at t along it
+    pub fn apply(world: &mut World, curve: Entity, t: f64) -> Option<Self> {
+        let original = CurveSnapshot::take(world, curve)?;
+        despawn_curve(world, curve);
+
+        let (first, second) = super::split_bezier(&original.positions, t);
+        let part = |positions, offset| {
+            let mut part = original.clone();
+            part.positions = positions;
+            for (_, index) in part.paths.iter_mut() {
+                *index += offset;
+            }
+            part
+        };
+        let mut parts = [part(first, 0), part(second, 1)];
+
+        // Each part keeps the joins of the end of the original it is made from
+        let [start_point, start_handle, end_handle, end_point] = original.control_points;
+        for (part, ends) in parts
+            .iter_mut()
+            .zip([[start_point, start_handle], [end_handle, end_point]])
+        {
+            part.joins.retain(|(host, connected)| {
+                ends.contains(host) || ends.contains(&connected.entity)
+            });
+        }
+
+        // These parts have never been spawned so there
+        // is nothing for the new entities to replace
+        let mut remap = EntityRemap::default();
+        Self::spawn_parts(&mut parts, world, &mut remap);
+
+        Some(Self { original, parts })
+    }
+
+    /// Spawns both parts joined where they meet, so the
+    /// split point moves the ends of both of them
+    fn spawn_parts(parts: &mut [CurveSnapshot; 2], world: &mut World, remap: &mut EntityRemap) {
+        for part in parts.iter_mut() {
+            part.spawn(world, remap);
+        }
+        super::join_curves(world, parts[0].curve, parts[1].curve);
+    }
+}
+
+impl Edit for CurveSplit {
+    fn undo(&mut self, world: &mut World, remap: &mut EntityRemap) {
+        for part in self.parts.iter() {
+            despawn_curve(world, part.curve);
+        }
+        self.original.spawn(world, remap);
+    }
+
+    fn redo(&mut self, world: &mut World, remap: &mut EntityRemap) {
+        despawn_curve(world, self.original.curve);
+        Self::spawn_parts(&mut self.parts, world, remap);
+    }
+
+    fn remap_entities(&mut self, remap: &EntityRemap) {
+        self.original.remap_entities(remap);
+        for part in self.parts.iter_mut() {
+            part.remap_entities(remap);
+        }
+    }
+}
+
+/// Changes how a curve is drawn
+#[derive(Event, Clone, Copy, Debug)]
+#[allow(dead_code)]
+pub struct SetStrokeStyle {
+    pub curve: Entity,
+    pub style: StrokeStyle,
+}
+
+pub struct StyleChanged {
+    curve: Entity,
+    before: StrokeStyle,
+    after: StrokeStyle,
+}
+
+impl Edit for StyleChanged {
+    fn undo(&mut self, world: &mut World, _remap: &mut EntityRemap) {
+        if let Some(mut entity) = world.get_entity_mut(self.curve) {
+            entity.insert(self.before);
+        }
+    }
+
+    fn redo(&mut self, world: &mut World, _remap: &mut EntityRemap) {
+        if let Some(mut entity) = world.get_entity_mut(self.curve) {
+            entity.insert(self.after);
+        }
+    }
+
+    fn remap_entities(&mut self, remap: &EntityRemap) {
+        if let Some(new_curve) = remap.get(&self.curve) {
+            self.curve = *new_curve;
+        }
+    }
+}
+
+pub fn set_stroke_style_system(
+    mut events: EventReader<SetStrokeStyle>,
+    mut styles_query: Query<&mut StrokeStyle>,
+    mut history: ResMut<History>,
+) {
+    for SetStrokeStyle { curve, style } in events.read() {
+        let Ok(mut current) = styles_query.get_mut(*curve) else {
+            continue;
+        };
+
+        history.record(StyleChanged {
+            curve: *curve,
+            before: *current,
+            after: *style,
+        });
+        *current = *style;
+    }
+}
+
+/// Every curve that has a selected control point
+pub fn selected_curves(
+    selection_set: &SelectionSet,
+    curves_query: &Query<(Entity, &BezierCurve)>,
+) -> Vec<Entity> {
+    curves_query
+        .iter()
+        .filter(|(_, curve)| {
+            curve
+                .control_points()
+                .into_iter()
+                .any(|point| selection_set.contains(point))
+        })
+        .map(|(entity, _)| entity)
+        .collect()
+}
+
+/// Deleting a selected control point deletes its whole curve
+#[allow(clippy::type_complexity)]
+pub fn delete_selected_curves_system(
+    world: &mut World,
+    state: &mut SystemState<(
+        ActionInput,
+        Res<SelectionSet>,
+        Query<(Entity, &BezierCurve)>,
+    )>,
+) {
+    let (input, selection_set, curves_query) = state.get(world);
+    if !input.just_pressed(Action::Delete) {
+        return;
+    }
+
+    let curves = selected_curves(&selection_set, &curves_query);
+    if curves.is_empty() {
+        return;
+    }
+
+    let deleted = CurvesDeleted::apply(world, &curves);
+    world.resource_mut::<History>().record(deleted);
+}
+
+/// How many points along each curve are checked when
+/// looking for the point on a curve nearest the mouse
+const SPLIT_SAMPLES: usize = 100;
+
+/// Double clicking on a curve with a selection tool splits it in two there
+#[allow(clippy::type_complexity)]
+pub fn split_curve_system(
+    world: &mut World,
+    state: &mut SystemState<(
+        Res<Tool>,
+        ActionInput,
+        EventReader<Gesture>,
+        Camera,
+        Query<(Entity, &BezierCurve)>,
+        Query<&Position>,
+    )>,
+) {
+    let (tool, input, mut gestures, camera, curves_query, positions_query) = state.get_mut(world);
+    let clicked = gestures
+        .read()
+        .filter_map(|gesture| match *gesture {
+            Gesture::DoubleClick { button, position }
+                if input.is_button(Action::Select, button) =>
+            {
+                Some(position)
+            }
+            _ => None,
+        })
+        .last();
+    let Some(clicked) = clicked.filter(|_| tool.selects()) else {
+        return;
+    };
+
+    let max_distance = camera.to_world(Size::Screen(8.0));
+    let nearest = curves_query
+        .iter()
+        .filter_map(|(entity, curve)| {
+            let points = positions_query.get_many(curve.control_points()).ok()?;
+            let points = points.map(|point| *point);
+
+            // The ends are left out so that double clicking
+            // a terminal point doesn't split next to it
+            (1..SPLIT_SAMPLES)
+                .map(|i| i as f64 / SPLIT_SAMPLES as f64)
+                .map(|t| {
+                    let point = super::bezier(points[0], points[1], points[2], points[3], t);
+                    (entity, t, point.distance(&clicked))
+                })
+                .min_by(|(_, _, a), (_, _, b)| a.total_cmp(b))
+        })
+        .filter(|(_, _, distance)| *distance <= max_distance)
+        .min_by(|(_, _, a), (_, _, b)| a.total_cmp(b));
+
+    let Some((curve, t, _)) = nearest else {
+        return;
+    };
+    if let Some(split) = CurveSplit::apply(world, curve, t) {
+        world.resource_mut::<History>().record(split);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        history::{MovePoints, MoveTracker},
+        selection::resolve_constraints_system,
+    };
+    use bevy::ecs::{schedule::Schedule, system::RunSystemOnce, world::Mut};
+
+    fn spawn_path(world: &mut World) -> (Entity, Entity) {
+        let curve = world.run_system_once(|mut commands: Commands| {
+            super::super::create_bezier_curve(
+                &mut commands,
+                Position::new(0.0, 0.0),
+                Position::new(0.0, 10.0),
+                Position::new(30.0, 10.0),
+                Position::new(30.0, 0.0),
+            )
+        });
+        let path = world.spawn(BezierPath(vec![curve])).id();
+        (curve, path)
+    }
+
+    fn path_positions(world: &mut World, path: Entity) -> Vec<[Position; 4]> {
+        let curves = world.get::<BezierPath>(path).unwrap().0.clone();
+        curves
+            .into_iter()
+            .map(|curve| CurveSnapshot::take(world, curve).unwrap().positions)
+            .collect()
+    }
+
+    fn undo(world: &mut World) {
+        world.resource_scope(|world, mut history: Mut<History>| history.undo(world));
+    }
+
+    fn redo(world: &mut World) {
+        world.resource_scope(|world, mut history: Mut<History>| history.redo(world));
+    }
+
+    #[test]
+    fn split_undo_redo() {
+        let mut world = World::new();
+        world.init_resource::<History>();
+        let (curve, path) = spawn_path(&mut world);
+        let original = path_positions(&mut world, path);
+
+        let split = CurveSplit::apply(&mut world, curve, 0.5).unwrap();
+        world.resource_mut::<History>().record(split);
+        let split_positions = path_positions(&mut world, path);
+        assert_eq!(split_positions.len(), 2);
+        assert_eq!(split_positions[0][3], Position::new(15.0, 7.5));
+        assert_eq!(split_positions[0][3], split_positions[1][0]);
+
+        undo(&mut world);
+        assert_eq!(path_positions(&mut world, path), original);
+        // Curve, primitives and four control points
+        assert_eq!(world.entities().len(), 7);
+
+        redo(&mut world);
+        assert_eq!(path_positions(&mut world, path), split_positions);
+        assert_eq!(world.entities().len(), 13);
+    }
+
+    #[test]
+    fn split_halves_stay_joined() {
+        let mut world = World::new();
+        world.init_resource::<History>();
+        let (curve, path) = spawn_path(&mut world);
+        let next = world.run_system_once(|mut commands: Commands| {
+            super::super::create_bezier_curve(
+                &mut commands,
+                Position::new(30.0, 0.0),
+                Position::new(30.0, -10.0),
+                Position::new(60.0, -10.0),
+                Position::new(60.0, 0.0),
+            )
+        });
+        super::super::join_curves(&mut world, curve, next);
+        let next_start = world.get::<BezierCurve>(next).unwrap().start_point;
+
+        let split = CurveSplit::apply(&mut world, curve, 0.5).unwrap();
+        world.resource_mut::<History>().record(split);
+
+        let mut schedule = Schedule::default();
+        schedule.add_systems(resolve_constraints_system);
+        let mut move_split_point = |world: &mut World, to: Position| {
+            let halves = world.get::<BezierPath>(path).unwrap().0.clone();
+            let [first, second] =
+                [halves[0], halves[1]].map(|half| world.get::<BezierCurve>(half).unwrap().clone());
+            // The end of the original stays joined to the curve after it
+            let joined = world.get::<Connection>(second.end_point).unwrap();
+            assert!(joined.entities().any(|entity| entity == next_start));
+
+            schedule.run(world);
+            *world.get_mut::<Position>(first.end_point).unwrap() = to;
+            schedule.run(world);
+            assert_eq!(*world.get::<Position>(second.start_point).unwrap(), to);
+        };
+
+        move_split_point(&mut world, Position::new(15.0, 12.0));
+        undo(&mut world);
+        redo(&mut world);
+        move_split_point(&mut world, Position::new(15.0, 2.0));
+    }
+
+    #[test]
+    fn joins_survive_undo_redo() {
+        let mut world = World::new();
+        world.init_resource::<History>();
+        let (first, _) = spawn_path(&mut world);
+        let second = world.run_system_once(|mut commands: Commands| {
+            super::super::create_bezier_curve(
+                &mut commands,
+                Position::new(30.0, 0.0),
+                Position::new(40.0, 0.0),
+                Position::new(50.0, 0.0),
+                Position::new(60.0, 0.0),
+            )
+        });
+        super::super::join_curves(&mut world, first, second);
+        world
+            .resource_mut::<History>()
+            .record(CurveCreated::new(second));
+
+        let first_end = world.get::<BezierCurve>(first).unwrap().end_point;
+        let connected = |world: &World, entity: Entity| -> Vec<Entity> {
+            world
+                .get::<Connection>(entity)
+                .unwrap()
+                .entities()
+                .collect()
+        };
+        let first_end_handle = world.get::<BezierCurve>(first).unwrap().end_handle;
+
+        undo(&mut world);
+        assert_eq!(connected(&world, first_end), vec![first_end_handle]);
+
+        redo(&mut world);
+        let second = world
+            .query::<(Entity, &BezierCurve)>()
+            .iter(&world)
+            .map(|(entity, _)| entity)
+            .find(|entity| *entity != first)
+            .unwrap();
+        let second_start = world.get::<BezierCurve>(second).unwrap().start_point;
+        assert_eq!(
+            connected(&world, first_end),
+            vec![first_end_handle, second_start]
+        );
+        assert!(connected(&world, second_start).contains(&first_end));
+    }
+
+    #[test]
+    fn edits_follow_respawned_curves() {
+        let mut world = World::new();
+        world.init_resource::<History>();
+        world.init_resource::<MoveTracker>();
+        let (curve, path) = spawn_path(&mut world);
+        let start_point = world.get::<BezierCurve>(curve).unwrap().start_point;
+
+        *world.get_mut::<Position>(start_point).unwrap() = Position::new(5.0, 5.0);
+        world.resource_mut::<History>().record(MovePoints(vec![(
+            start_point,
+            Position::new(0.0, 0.0),
+            Position::new(5.0, 5.0),
+        )]));
+
+        let snapshot = CurveSnapshot::take(&mut world, curve).unwrap();
+        despawn_curve(&mut world, curve);
+        world
+            .resource_mut::<History>()
+            .record(CurvesDeleted(vec![snapshot]));
+        assert!(world.get::<BezierPath>(path).unwrap().0.is_empty());
+
+        // The curve comes back with new entities that
+        // the earlier move has to be applied to
+        undo(&mut world);
+        undo(&mut world);
+        assert_eq!(
+            path_positions(&mut world, path)[0][0],
+            Position::new(0.0, 0.0)
+        );
+
+        redo(&mut world);
+        redo(&mut world);
+        assert!(world.get::<BezierPath>(path).unwrap().0.is_empty());
+        assert_eq!(world.entities().len(), 1);
+    }
+}
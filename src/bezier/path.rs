@@ -0,0 +1,289 @@
+use crate::position::Position;
+
+use super::{bezier, bezier_tangent};
+
+/// Number of linear pieces used to approximate each segment
+/// when measuring its length
+const ARC_LENGTH_SUBDIVISIONS: usize = 60;
+
+/// Cumulative arc lengths sampled at evenly spaced t values
+/// along a single cubic segment, used to turn a distance along
+/// the segment back into a t value
+struct ArcLengthTable {
+    lengths: Vec<f32>,
+}
+
+impl ArcLengthTable {
+    fn new(control_points: &[Position; 4]) -> Self {
+        let mut lengths = Vec::with_capacity(ARC_LENGTH_SUBDIVISIONS + 1);
+        let mut previous = control_points[0];
+        let mut total = 0.0;
+        lengths.push(total);
+
+        for i in 1..=ARC_LENGTH_SUBDIVISIONS {
+            let t = i as f64 / ARC_LENGTH_SUBDIVISIONS as f64;
+            let point = bezier(
+                control_points[0],
+                control_points[1],
+                control_points[2],
+                control_points[3],
+                t,
+            );
+            total += point.distance(&previous);
+            lengths.push(total);
+            previous = point;
+        }
+
+        Self { lengths }
+    }
+
+    fn length(&self) -> f32 {
+        *self.lengths.last().unwrap()
+    }
+
+    /// Maps a distance along the segment to the t value
+    /// at that distance, clamping to the ends of the segment
+    fn t_at_distance(&self, distance: f32) -> f64 {
+        if distance <= 0.0 {
+            return 0.0;
+        }
+        if distance >= self.length() {
+            return 1.0;
+        }
+
+        // Index of the first sample that is at least distance along the curve,
+        // never 0 because of the check above
+        let index = self.lengths.partition_point(|&length| length < distance);
+        let before = self.lengths[index - 1];
+        let after = self.lengths[index];
+        let fraction = if after > before {
+            (distance - before) / (after - before)
+        } else {
+            0.0
+        };
+
+        (index as f64 - 1.0 + fraction as f64) / ARC_LENGTH_SUBDIVISIONS as f64
+    }
+}
+
+/// A chain of cubic segments that can be sampled by distance
+/// so that points move at a constant speed regardless of how
+/// the control points are laid out
+pub struct PathSampler {
+    segments: Vec<[Position; 4]>,
+    tables: Vec<ArcLengthTable>,
+}
+
+impl PathSampler {
+    pub fn new(segments: Vec<[Position; 4]>) -> Self {
+        let tables = segments.iter().map(ArcLengthTable::new).collect();
+        Self { segments, tables }
+    }
+
+    pub fn length(&self) -> f32 {
+        self.tables.iter().map(ArcLengthTable::length).sum()
+    }
+
+    /// Returns the point that is distance along the path,
+    /// clamping to the ends of the path
+    ///
+    /// Returns None if the path has no segments
+    pub fn position_at_distance(&self, distance: f32) -> Option<Position> {
+        let (segment, t) = self.segment_at_distance(distance)?;
+        Some(bezier(segment[0], segment[1], segment[2], segment[3], t))
+    }
+
+    /// Returns the direction the path is heading in at distance along
+    /// it as a unit vector, clamping to the ends of the path
+    ///
+    /// Returns None if the path has no segments
+    pub fn tangent_at_distance(&self, distance: f32) -> Option<Position> {
+        let (segment, t) = self.segment_at_distance(distance)?;
+        let tangent = bezier_tangent(segment, t);
+        let length = tangent.distance(&Position::default());
+        Some(if length > 0.0 {
+            tangent * (1.0 / length)
+        } else {
+            // Every control point is in the same place
+            Position::new(1.0, 0.0)
+        })
+    }
+
+    /// Returns the distance along the path of every join between segments
+    /// where the direction changes by more than min_angle radians, with
+    /// the direction the path leaves the join in as a unit vector.
+    /// Segments with every control point in the same place are skipped
+    /// over so they can't hide a corner
+    pub fn corners(&self, min_angle: f32) -> Vec<(f32, Position)> {
+        let mut corners = Vec::new();
+        let mut distance = 0.0;
+        let mut incoming: Option<Position> = None;
+        for (segment, table) in self.segments.iter().zip(&self.tables) {
+            let Some((start, end)) = end_directions(segment) else {
+                continue;
+            };
+            if let Some(incoming) = incoming {
+                let [x, y]: [f32; 2] = incoming.into();
+                let [out_x, out_y]: [f32; 2] = start.into();
+                let angle = (x * out_y - y * out_x).atan2(x * out_x + y * out_y);
+                if angle.abs() > min_angle {
+                    corners.push((distance, start));
+                }
+            }
+            incoming = Some(end);
+            distance += table.length();
+        }
+        corners
+    }
+
+    /// The segment that is distance along the path and the t value on it
+    fn segment_at_distance(&self, distance: f32) -> Option<(&[Position; 4], f64)> {
+        let mut remaining = distance;
+        for (segment, table) in self.segments.iter().zip(&self.tables) {
+            if remaining <= table.length() {
+                return Some((segment, table.t_at_distance(remaining)));
+            }
+            remaining -= table.length();
+        }
+
+        self.segments.last().map(|segment| (segment, 1.0))
+    }
+}
+
+/// The unit directions a segment leaves its start and arrives at its end
+/// in. The derivative is zero at an end whose handle is on top of it, so
+/// the direction is towards the closest control point that isn't
+fn end_directions(segment: &[Position; 4]) -> Option<(Position, Position)> {
+    let direction = |from: Position, to: Position| (to - from) * (1.0 / from.distance(&to));
+    let start = segment[1..].iter().find(|point| **point != segment[0])?;
+    let end = segment[..3]
+        .iter()
+        .rev()
+        .find(|point| **point != segment[3])?;
+    Some((direction(segment[0], *start), direction(*end, segment[3])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn straight_line() -> [Position; 4] {
+        [
+            Position::new(0.0, 0.0),
+            Position::new(10.0, 0.0),
+            Position::new(20.0, 0.0),
+            Position::new(30.0, 0.0),
+        ]
+    }
+
+    #[test]
+    fn straight_line_length() {
+        let sampler = PathSampler::new(vec![straight_line()]);
+        assert!((sampler.length() - 30.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn straight_line_is_uniform() {
+        let sampler = PathSampler::new(vec![straight_line()]);
+
+        for i in 0..=30 {
+            let position = sampler.position_at_distance(i as f32).unwrap();
+            assert_eq!(position, Position::new(i as f32, 0.0));
+        }
+    }
+
+    #[test]
+    fn uneven_handles_are_uniform() {
+        // Handles bunched up at the start make t advance
+        // much faster at the end of the curve
+        let sampler = PathSampler::new(vec![[
+            Position::new(0.0, 0.0),
+            Position::new(1.0, 0.0),
+            Position::new(2.0, 0.0),
+            Position::new(30.0, 0.0),
+        ]]);
+
+        let first = sampler.position_at_distance(10.0).unwrap();
+        let second = sampler.position_at_distance(20.0).unwrap();
+        assert!((first.x() - 10.0).abs() < 0.5);
+        assert!((second.x() - 20.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn multiple_segments() {
+        let offset = Position::new(30.0, 0.0);
+        let [a, b, c, d] = straight_line();
+        let sampler = PathSampler::new(vec![
+            straight_line(),
+            [a + offset, b + offset, c + offset, d + offset],
+        ]);
+
+        assert!((sampler.length() - 60.0).abs() < 0.001);
+        assert_eq!(
+            sampler.position_at_distance(45.0).unwrap(),
+            Position::new(45.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn distance_is_clamped() {
+        let sampler = PathSampler::new(vec![straight_line()]);
+
+        assert_eq!(
+            sampler.position_at_distance(-5.0).unwrap(),
+            Position::new(0.0, 0.0)
+        );
+        assert_eq!(
+            sampler.position_at_distance(100.0).unwrap(),
+            Position::new(30.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn tangents() {
+        // The handles are on top of the terminal points
+        let sampler = PathSampler::new(vec![[
+            Position::new(0.0, 0.0),
+            Position::new(0.0, 0.0),
+            Position::new(0.0, 30.0),
+            Position::new(0.0, 30.0),
+        ]]);
+
+        for distance in [0.0, 15.0, 30.0] {
+            assert_eq!(
+                sampler.tangent_at_distance(distance).unwrap(),
+                Position::new(0.0, 1.0)
+            );
+        }
+    }
+
+    #[test]
+    fn corners() {
+        let line = |from: (f32, f32), to: (f32, f32)| {
+            let from = Position::new(from.0, from.1);
+            let to = Position::new(to.0, to.1);
+            [from, from, to, to]
+        };
+        let sampler = PathSampler::new(vec![
+            line((0.0, 0.0), (10.0, 0.0)),
+            // Carries straight on so isn't a corner
+            line((10.0, 0.0), (20.0, 0.0)),
+            // Has no length so the turn is found at the next segment
+            line((20.0, 0.0), (20.0, 0.0)),
+            line((20.0, 0.0), (20.0, 10.0)),
+        ]);
+
+        let corners = sampler.corners(0.01);
+        assert_eq!(corners.len(), 1);
+        assert!((corners[0].0 - 20.0).abs() < 0.001, "{}", corners[0].0);
+        assert_eq!(corners[0].1, Position::new(0.0, 1.0));
+    }
+
+    #[test]
+    fn empty_path() {
+        let sampler = PathSampler::new(Vec::new());
+        assert_eq!(sampler.length(), 0.0);
+        assert!(sampler.position_at_distance(0.0).is_none());
+        assert!(sampler.tangent_at_distance(0.0).is_none());
+    }
+}
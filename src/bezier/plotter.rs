@@ -0,0 +1,358 @@
+use std::fmt::Write;
+
+use crate::position::Position;
+
+use super::document::Document;
+
+/// How many times a curve is split in half at most while flattening,
+/// which stops curves with nonsense positions from recursing forever
+const MAX_FLATTEN_DEPTH: u32 = 16;
+
+/// HPGL plotter units in a millimetre
+const HPGL_UNITS_PER_MM: f32 = 40.0;
+const MM_PER_INCH: f32 = 25.4;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Units {
+    Millimeters,
+    Inches,
+}
+
+/// Which point of the drawing ends up at the machine origin. The world
+/// has y pointing down while plotters have it pointing up, so y is
+/// always flipped and the drawing comes out the way it looks on screen
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Origin {
+    BottomLeft,
+    TopLeft,
+    Center,
+    /// The world origin, without moving the drawing
+    World,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct PlotterSettings {
+    /// How far in world units the flattened lines can stray from the curves
+    pub tolerance: f32,
+    /// Output units for every world unit
+    pub scale: f32,
+    pub units: Units,
+    pub origin: Origin,
+    /// Drawing speed in units per minute, used for G-code
+    pub feed_rate: f32,
+    /// G-code that lifts the pen off the paper
+    pub pen_up: String,
+    /// G-code that puts the pen on the paper
+    pub pen_down: String,
+}
+
+impl Default for PlotterSettings {
+    fn default() -> Self {
+        Self {
+            tolerance: 0.1,
+            scale: 1.0,
+            units: Units::Millimeters,
+            origin: Origin::BottomLeft,
+            feed_rate: 1000.0,
+            pen_up: "G0 Z5".to_owned(),
+            pen_down: "G1 Z0".to_owned(),
+        }
+    }
+}
+
+/// Turns a curve into a line that is never further than tolerance
+/// from it, splitting the curve until its handles are that close
+/// to the line between its terminal points
+pub fn flatten_curve(control_points: &[Position; 4], tolerance: f32) -> Vec<Position> {
+    fn flatten(
+        control_points: &[Position; 4],
+        tolerance: f32,
+        depth: u32,
+        line: &mut Vec<Position>,
+    ) {
+        let [start, start_handle, end_handle, end] = *control_points;
+        // Measured to the segment rather than the line through it, so
+        // handles that stick out past the ends still count as curved
+        let flatness = start_handle
+            .distance_squared_to_segment(start, end)
+            .max(end_handle.distance_squared_to_segment(start, end))
+            .sqrt();
+        if flatness <= tolerance || depth >= MAX_FLATTEN_DEPTH {
+            line.push(end);
+            return;
+        }
+
+        let (first, second) = super::split_bezier(control_points, 0.5);
+        flatten(&first, tolerance, depth + 1, line);
+        flatten(&second, tolerance, depth + 1, line);
+    }
+
+    let mut line = vec![control_points[0]];
+    flatten(control_points, tolerance, 0, &mut line);
+    line
+}
+
+/// Flattens every curve in the document and orders the lines so that the
+/// pen travels as little as possible between them. Each line starts at
+/// the end of the line closest to where the last one finished, so lines
+/// can be drawn backwards, and lines that carry on from each other are
+/// joined so that the pen doesn't lift between them. The pen starts at
+/// the world position that ends up at the machine origin
+pub fn plot_lines(document: &Document, tolerance: f32, origin: Origin) -> Vec<Vec<Position>> {
+    let mut remaining: Vec<Vec<Position>> = document
+        .curve_positions()
+        .iter()
+        .map(|curve| flatten_curve(curve, tolerance))
+        .collect();
+
+    let mut lines: Vec<Vec<Position>> = Vec::new();
+    let mut pen = world_origin(&remaining, origin);
+    while !remaining.is_empty() {
+        let (index, reversed, distance) = remaining
+            .iter()
+            .enumerate()
+            .flat_map(|(index, line)| {
+                [
+                    (index, false, pen.distance(&line[0])),
+                    (index, true, pen.distance(line.last().unwrap())),
+                ]
+            })
+            .min_by(|a, b| a.2.total_cmp(&b.2))
+            .unwrap();
+
+        let mut line = remaining.swap_remove(index);
+        if reversed {
+            line.reverse();
+        }
+        pen = *line.last().unwrap();
+
+        match lines.last_mut() {
+            Some(last) if distance <= tolerance => last.extend(line.into_iter().skip(1)),
+            _ => lines.push(line),
+        }
+    }
+    lines
+}
+
+/// Where origin is in world space for the bounds of lines
+fn world_origin(lines: &[Vec<Position>], origin: Origin) -> Position {
+    let mut points = lines.iter().flatten();
+    let Some(first) = points.next() else {
+        return Position::default();
+    };
+    let (min, max) = points.fold((*first, *first), |(min, max), point| {
+        (
+            Position::new(min.x().min(point.x()), min.y().min(point.y())),
+            Position::new(max.x().max(point.x()), max.y().max(point.y())),
+        )
+    });
+    match origin {
+        Origin::BottomLeft => Position::new(min.x(), max.y()),
+        Origin::TopLeft => min,
+        Origin::Center => Position::lerp(min, max, 0.5),
+        Origin::World => Position::default(),
+    }
+}
+
+/// Moves lines from world space into output units around the origin
+fn to_output(lines: &mut [Vec<Position>], settings: &PlotterSettings) {
+    let origin = world_origin(lines, settings.origin);
+    for point in lines.iter_mut().flatten() {
+        let relative = (*point - origin) * settings.scale;
+        // Subtracting rather than negating so that 0 isn't written as -0
+        *point = Position::new(relative.x(), 0.0 - relative.y());
+    }
+}
+
+pub fn export_gcode(document: &Document, settings: &PlotterSettings) -> String {
+    let mut lines = plot_lines(document, settings.tolerance, settings.origin);
+    to_output(&mut lines, settings);
+
+    let mut gcode = String::new();
+    let units = match settings.units {
+        Units::Millimeters => "G21",
+        Units::Inches => "G20",
+    };
+    // The feed rate is set before any pen command, which may be a G1
+    writeln!(
+        gcode,
+        "{units}\nG90\nF{}\n{}",
+        settings.feed_rate, settings.pen_up
+    )
+    .unwrap();
+    for line in lines {
+        let [start, rest @ ..] = line.as_slice() else {
+            continue;
+        };
+        writeln!(gcode, "G0 X{:.3} Y{:.3}", start.x(), start.y()).unwrap();
+        writeln!(gcode, "{}", settings.pen_down).unwrap();
+        for (i, point) in rest.iter().enumerate() {
+            write!(gcode, "G1 X{:.3} Y{:.3}", point.x(), point.y()).unwrap();
+            // Feed rate is modal but the pen commands can change it
+            if i == 0 {
+                write!(gcode, " F{}", settings.feed_rate).unwrap();
+            }
+            gcode.push('\n');
+        }
+        writeln!(gcode, "{}", settings.pen_up).unwrap();
+    }
+    gcode.push_str("G0 X0 Y0\nM2\n");
+    gcode
+}
+
+pub fn export_hpgl(document: &Document, settings: &PlotterSettings) -> String {
+    let mut lines = plot_lines(document, settings.tolerance, settings.origin);
+    to_output(&mut lines, settings);
+
+    let plotter_units = match settings.units {
+        Units::Millimeters => HPGL_UNITS_PER_MM,
+        Units::Inches => HPGL_UNITS_PER_MM * MM_PER_INCH,
+    };
+    let coordinates = |point: &Position| {
+        format!(
+            "{},{}",
+            (point.x() * plotter_units).round() as i32,
+            (point.y() * plotter_units).round() as i32
+        )
+    };
+
+    let mut hpgl = String::from("IN;SP1;PA;\n");
+    for line in lines {
+        let [start, rest @ ..] = line.as_slice() else {
+            continue;
+        };
+        let rest: Vec<_> = rest.iter().map(coordinates).collect();
+        writeln!(hpgl, "PU{};PD{};", coordinates(start), rest.join(",")).unwrap();
+    }
+    hpgl.push_str("PU;SP0;\n");
+    hpgl
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rendering::primitives::StrokeStyle;
+
+    fn line(from: (f32, f32), to: (f32, f32)) -> [Position; 4] {
+        let from = Position::new(from.0, from.1);
+        let to = Position::new(to.0, to.1);
+        [from, from, to, to]
+    }
+
+    #[test]
+    fn flattens_to_tolerance() {
+        let curve = [
+            Position::new(0.0, 0.0),
+            Position::new(0.0, 100.0),
+            Position::new(100.0, 100.0),
+            Position::new(100.0, 0.0),
+        ];
+        assert_eq!(flatten_curve(&line((0.0, 0.0), (10.0, 0.0)), 0.1).len(), 2);
+
+        let coarse = flatten_curve(&curve, 1.0);
+        let fine = flatten_curve(&curve, 0.01);
+        assert!(fine.len() > coarse.len());
+        assert_eq!(fine[0], curve[0]);
+        assert_eq!(*fine.last().unwrap(), curve[3]);
+
+        // Every point on the curve is close to the flattened line
+        for point in super::super::generate_bezier_points(&curve) {
+            let distance = fine
+                .windows(2)
+                .map(|segment| {
+                    let [start, end] = [segment[0], segment[1]];
+                    let length = start.distance_squared(&end);
+                    let relative = point - start;
+                    let along = end - start;
+                    let t = ((relative.x() * along.x() + relative.y() * along.y()) / length)
+                        .clamp(0.0, 1.0);
+                    point.distance(&Position::lerp(start, end, t as f64))
+                })
+                .fold(f32::INFINITY, f32::min);
+            assert!(distance <= 0.01, "{distance}");
+        }
+    }
+
+    #[test]
+    fn orders_lines_to_minimise_travel() {
+        let mut document = Document::default();
+        for curve in [
+            line((100.0, 0.0), (200.0, 0.0)),
+            line((10.0, 0.0), (90.0, 0.0)),
+            line((0.0, 0.0), (10.0, 0.0)),
+        ] {
+            document.push_curve(curve, StrokeStyle::default());
+        }
+
+        let lines = plot_lines(&document, 0.1, Origin::World);
+        // The first two lines meet so they are drawn without lifting the pen
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            [(0.0, 0.0), (10.0, 0.0), (90.0, 0.0)].map(|(x, y)| Position::new(x, y))
+        );
+        assert_eq!(lines[1][0], Position::new(100.0, 0.0));
+    }
+
+    #[test]
+    fn keeps_handles_past_the_ends() {
+        // The handles are in line with the ends but stick out past them
+        let curve = [(0.0, 0.0), (-50.0, 0.0), (150.0, 0.0), (100.0, 0.0)]
+            .map(|(x, y)| Position::new(x, y));
+        let line = flatten_curve(&curve, 0.1);
+        let min = line.iter().map(Position::x).fold(f32::INFINITY, f32::min);
+        let max = line
+            .iter()
+            .map(Position::x)
+            .fold(f32::NEG_INFINITY, f32::max);
+        assert!(min < -5.0, "{min}");
+        assert!(max > 105.0, "{max}");
+    }
+
+    #[test]
+    fn starts_at_the_origin() {
+        let mut document = Document::default();
+        for curve in [
+            line((0.0, 0.0), (10.0, 0.0)),
+            line((0.0, 100.0), (10.0, 100.0)),
+        ] {
+            document.push_curve(curve, StrokeStyle::default());
+        }
+
+        // The bottom left is the bottom of the screen, where y is largest
+        let lines = plot_lines(&document, 0.1, Origin::BottomLeft);
+        assert_eq!(lines[0][0], Position::new(0.0, 100.0));
+        let lines = plot_lines(&document, 0.1, Origin::TopLeft);
+        assert_eq!(lines[0][0], Position::new(0.0, 0.0));
+    }
+
+    #[test]
+    fn writes_gcode_and_hpgl() {
+        let mut document = Document::default();
+        document.push_curve(line((10.0, 10.0), (20.0, 30.0)), StrokeStyle::default());
+        document.push_curve(line((40.0, 30.0), (40.0, 10.0)), StrokeStyle::default());
+
+        let settings = PlotterSettings {
+            feed_rate: 500.0,
+            ..Default::default()
+        };
+        let gcode = export_gcode(&document, &settings);
+        let expected = "G21\nG90\nF500\nG0 Z5\n\
+            G0 X10.000 Y0.000\nG1 Z0\nG1 X0.000 Y20.000 F500\nG0 Z5\n\
+            G0 X30.000 Y20.000\nG1 Z0\nG1 X30.000 Y0.000 F500\nG0 Z5\n\
+            G0 X0 Y0\nM2\n";
+        assert_eq!(gcode, expected);
+
+        let settings = PlotterSettings {
+            units: Units::Inches,
+            origin: Origin::World,
+            scale: 0.1,
+            ..Default::default()
+        };
+        let hpgl = export_hpgl(&document, &settings);
+        assert_eq!(
+            hpgl,
+            "IN;SP1;PA;\nPU1016,-1016;PD2032,-3048;\nPU4064,-3048;PD4064,-1016;\nPU;SP0;\n"
+        );
+    }
+}
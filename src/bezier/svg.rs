@@ -0,0 +1,307 @@
+use std::{collections::VecDeque, fmt::Write};
+
+use bevy::ecs::{system::SystemState, world::World};
+
+use crate::{
+    history::{History, Inverse},
+    input_map::{Action, ActionInput},
+    position::Position,
+    rendering::primitives::{LineCap, LineJoin, StrokeStyle},
+    title::TitleMessages,
+};
+
+use super::{
+    document::{Document, DocumentError, DocumentPath, FILE_MESSAGE},
+    history::CurvesDeleted,
+};
+
+mod import;
+mod path_data;
+
+pub use import::import_svg;
+
+/// How close the end of one curve and the start of the next
+/// have to be for them to be drawn as one continuous line
+const JOIN_TOLERANCE: f32 = 1e-3;
+
+/// Writes every curve in the document as SVG `<path>` elements in world
+/// space. Curves that follow on from each other with the same style are
+/// merged into a single subpath so that they are joined rather than capped
+pub fn export_svg(document: &Document) -> String {
+    let curves = document.curve_positions();
+
+    // Curves in paths go first so that chains follow the path order
+    let mut order: Vec<usize> = document
+        .paths
+        .iter()
+        .flat_map(|path| path.curves.iter().copied())
+        .collect();
+    order.extend(0..curves.len());
+
+    let mut used = vec![false; curves.len()];
+    let mut chains: Vec<Vec<usize>> = Vec::new();
+    for first in order.iter().copied() {
+        if used[first] {
+            continue;
+        }
+        used[first] = true;
+
+        let style = document.curves[first].style;
+        // An unused curve with the same style whose control point at is on point
+        let unused_joining = |used: &[bool], at: usize, point: Position| {
+            order.iter().copied().find(|index| {
+                !used[*index]
+                    && document.curves[*index].style == style
+                    && curves[*index][at].distance(&point) <= JOIN_TOLERANCE
+            })
+        };
+
+        let mut chain = VecDeque::from([first]);
+        loop {
+            let end = curves[*chain.back().unwrap()][3];
+            let Some(next) = unused_joining(&used, 0, end) else {
+                break;
+            };
+            used[next] = true;
+            chain.push_back(next);
+        }
+        // Curves before the first one in the chain can come
+        // after it in the order when they aren't in a path
+        loop {
+            let start = curves[*chain.front().unwrap()][0];
+            let Some(previous) = unused_joining(&used, 3, start) else {
+                break;
+            };
+            used[previous] = true;
+            chain.push_front(previous);
+        }
+        chains.push(chain.into());
+    }
+
+    let (min, max) = bounds(document, &curves);
+    let size = max - min;
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}" width="{}" height="{}">"#,
+        min.x(),
+        min.y(),
+        size.x(),
+        size.y(),
+        size.x(),
+        size.y(),
+    )
+    .unwrap();
+
+    for chain in chains {
+        let start = curves[chain[0]][0];
+        let mut d = format!("M {} {}", start.x(), start.y());
+        for index in chain.iter() {
+            let [_, start_handle, end_handle, end_point] = curves[*index];
+            write!(
+                d,
+                " C {} {} {} {} {} {}",
+                start_handle.x(),
+                start_handle.y(),
+                end_handle.x(),
+                end_handle.y(),
+                end_point.x(),
+                end_point.y(),
+            )
+            .unwrap();
+        }
+        let end = curves[*chain.last().unwrap()][3];
+        if end.distance(&start) <= JOIN_TOLERANCE {
+            d.push_str(" Z");
+        }
+
+        writeln!(
+            svg,
+            r#"  <path d="{d}" fill="none" {}/>"#,
+            stroke_attributes(&document.curves[chain[0]].style)
+        )
+        .unwrap();
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// The box around every control point, which always contains the
+/// curves, grown so that the width of the strokes isn't cut off
+fn bounds(document: &Document, curves: &[[Position; 4]]) -> (Position, Position) {
+    let mut points = curves.iter().flatten();
+    let Some(first) = points.next() else {
+        return (Position::default(), Position::default());
+    };
+    let (min, max) = points.fold((*first, *first), |(min, max), point| {
+        (
+            Position::new(min.x().min(point.x()), min.y().min(point.y())),
+            Position::new(max.x().max(point.x()), max.y().max(point.y())),
+        )
+    });
+
+    let margin = document
+        .curves
+        .iter()
+        .map(|curve| curve.style.width / 2.0)
+        .fold(0.0, f32::max);
+    let margin = Position::new(margin, margin);
+    (min - margin, max + margin)
+}
+
+fn stroke_attributes(style: &StrokeStyle) -> String {
+    let [r, g, b, a] = style.color.rgba();
+    let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    let join = match style.join {
+        LineJoin::Miter => "miter",
+        LineJoin::Bevel => "bevel",
+        LineJoin::Round => "round",
+    };
+    let cap = match style.cap {
+        LineCap::Butt => "butt",
+        LineCap::Square => "square",
+        LineCap::Round => "round",
+    };
+
+    let mut attributes = format!(
+        r#"stroke="rgb({},{},{})" stroke-width="{}" stroke-linejoin="{join}" stroke-linecap="{cap}""#,
+        channel(r),
+        channel(g),
+        channel(b),
+        style.width,
+    );
+    if a < 1.0 {
+        write!(attributes, r#" stroke-opacity="{a}""#).unwrap();
+    }
+    attributes
+}
+
+pub fn export_svg_system(world: &mut World, input_state: &mut SystemState<ActionInput>) {
+    if !input_state.get(world).just_pressed(Action::ExportSvg) {
+        return;
+    }
+
+    let path = world.resource::<DocumentPath>().0.with_extension("svg");
+    let svg = export_svg(&Document::capture(world));
+    let status = match std::fs::write(&path, svg) {
+        Ok(()) => format!("Exported to {}", path.display()),
+        Err(error) => {
+            eprintln!("Couldn't export to {}: {error}", path.display());
+            format!("Couldn't export to {}", path.display())
+        }
+    };
+    world
+        .resource_mut::<TitleMessages>()
+        .set(FILE_MESSAGE, status);
+}
+
+/// Adds the curves in the SVG next to the document file to the scene
+pub fn import_svg_system(world: &mut World, input_state: &mut SystemState<ActionInput>) {
+    if !input_state.get(world).just_pressed(Action::ImportSvg) {
+        return;
+    }
+
+    let path = world.resource::<DocumentPath>().0.with_extension("svg");
+    let document = match std::fs::read_to_string(&path)
+        .map_err(DocumentError::from)
+        .and_then(|text| import_svg(&text))
+    {
+        Ok(document) => document,
+        Err(error) => {
+            eprintln!("Couldn't import {}: {error}", path.display());
+            world
+                .resource_mut::<TitleMessages>()
+                .set(FILE_MESSAGE, format!("Couldn't import {}", path.display()));
+            return;
+        }
+    };
+
+    let spawned = document.spawn(world);
+    let imported = CurvesDeleted::snapshot(world, &spawned.curves);
+    world.resource_mut::<History>().record(Inverse(imported));
+    world.resource_mut::<TitleMessages>().set(
+        FILE_MESSAGE,
+        format!(
+            "Imported {} curves from {}",
+            spawned.curves.len(),
+            path.display()
+        ),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bezier::document::PathData, rendering::Color};
+
+    fn document(curves: &[[(f32, f32); 4]], style: StrokeStyle) -> Document {
+        let mut document = Document::default();
+        for curve in curves {
+            document.push_curve(curve.map(|(x, y)| Position::new(x, y)), style);
+        }
+        document
+    }
+
+    #[test]
+    fn merges_joined_curves() {
+        let style = StrokeStyle {
+            color: Color::new_with_alpha(1.0, 0.5, 0.0, 0.5),
+            width: 4.0,
+            join: LineJoin::Round,
+            cap: LineCap::Round,
+        };
+        let mut document = document(
+            &[
+                [(30.0, 0.0), (40.0, 0.0), (50.0, 10.0), (60.0, 0.0)],
+                [(0.0, 0.0), (0.0, 10.0), (30.0, 10.0), (30.0, 0.0)],
+                [(100.0, 0.0), (100.0, 20.0), (110.0, 20.0), (110.0, 0.0)],
+            ],
+            style,
+        );
+        document.paths.push(PathData {
+            curves: vec![1, 0],
+            followers: Vec::new(),
+        });
+
+        let svg = export_svg(&document);
+        let paths: Vec<_> = svg.lines().filter(|line| line.contains("<path")).collect();
+        assert_eq!(paths.len(), 2);
+        assert!(paths[0].contains(r#"d="M 0 0 C 0 10 30 10 30 0 C 40 0 50 10 60 0""#));
+        assert!(paths[0].contains(r#"stroke="rgb(255,128,0)" stroke-width="4""#));
+        assert!(paths[0].contains(r#"stroke-opacity="0.5""#));
+        assert!(paths[1].contains(r#"d="M 100 0 C 100 20 110 20 110 0""#));
+        assert!(svg.contains(r#"viewBox="-2 -2 114 24""#));
+    }
+
+    #[test]
+    fn merges_curves_out_of_order() {
+        // Like a chain drawn with the pen after entities have been reused
+        let document = document(
+            &[
+                [(20.0, 0.0), (20.0, 5.0), (30.0, 5.0), (30.0, 0.0)],
+                [(10.0, 0.0), (10.0, 5.0), (20.0, 5.0), (20.0, 0.0)],
+                [(0.0, 0.0), (0.0, 5.0), (10.0, 5.0), (10.0, 0.0)],
+            ],
+            StrokeStyle::default(),
+        );
+
+        let svg = export_svg(&document);
+        let paths: Vec<_> = svg.lines().filter(|line| line.contains("<path")).collect();
+        assert_eq!(paths.len(), 1);
+        assert!(paths[0].contains(r#"d="M 0 0 C 0 5 10 5 10 0 C 10 5 20 5 20 0 C 20 5 30 5 30 0""#));
+    }
+
+    #[test]
+    fn closes_loops() {
+        let document = document(
+            &[
+                [(0.0, 0.0), (0.0, 10.0), (10.0, 10.0), (10.0, 0.0)],
+                [(10.0, 0.0), (10.0, -10.0), (0.0, -10.0), (0.0, 0.0)],
+            ],
+            StrokeStyle::default(),
+        );
+        let svg = export_svg(&document);
+        assert!(svg.contains("C 10 -10 0 -10 0 0 Z\""));
+    }
+}
@@ -0,0 +1,581 @@
+use roxmltree::Node;
+
+use crate::{
+    bezier::document::{Document, DocumentError, PathData},
+    position::Position,
+    rendering::{
+        primitives::{LineCap, LineJoin, StrokeStyle},
+        Color,
+    },
+};
+
+use super::path_data::{parse_numbers, parse_path_data};
+
+/// An affine transform stored as the six values of an SVG `matrix()`
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Transform([f32; 6]);
+
+impl Transform {
+    const IDENTITY: Transform = Transform([1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+
+    fn translate(x: f32, y: f32) -> Self {
+        Transform([1.0, 0.0, 0.0, 1.0, x, y])
+    }
+
+    /// Applies other first and then self
+    fn multiply(self, other: Transform) -> Self {
+        let [a1, b1, c1, d1, e1, f1] = self.0;
+        let [a2, b2, c2, d2, e2, f2] = other.0;
+        Transform([
+            a1 * a2 + c1 * b2,
+            b1 * a2 + d1 * b2,
+            a1 * c2 + c1 * d2,
+            b1 * c2 + d1 * d2,
+            a1 * e2 + c1 * f2 + e1,
+            b1 * e2 + d1 * f2 + f1,
+        ])
+    }
+
+    fn apply(&self, position: Position) -> Position {
+        let [a, b, c, d, e, f] = self.0;
+        let (x, y) = (position.x(), position.y());
+        Position::new(a * x + c * y + e, b * x + d * y + f)
+    }
+
+    /// How much lengths are scaled by on average, used to scale stroke widths
+    fn scale(&self) -> f32 {
+        let [a, b, c, d, ..] = self.0;
+        (a * d - b * c).abs().sqrt()
+    }
+
+    /// Parses a `transform` attribute, which is invalid as a whole if
+    /// any part of it is
+    fn parse(text: &str) -> Option<Self> {
+        let mut transform = Transform::IDENTITY;
+        let mut rest = text.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        while !rest.is_empty() {
+            let (name, after_name) = rest.split_once('(')?;
+            let (arguments, after_arguments) = after_name.split_once(')')?;
+            let arguments = parse_numbers(arguments);
+
+            let next = match (name.trim(), arguments.as_slice()) {
+                ("matrix", [a, b, c, d, e, f]) => Transform([*a, *b, *c, *d, *e, *f]),
+                ("translate", [x]) => Transform::translate(*x, 0.0),
+                ("translate", [x, y]) => Transform::translate(*x, *y),
+                ("scale", [scale]) => Transform([*scale, 0.0, 0.0, *scale, 0.0, 0.0]),
+                ("scale", [x, y]) => Transform([*x, 0.0, 0.0, *y, 0.0, 0.0]),
+                ("rotate", [angle]) => rotate(*angle),
+                ("rotate", [angle, x, y]) => Transform::translate(*x, *y)
+                    .multiply(rotate(*angle))
+                    .multiply(Transform::translate(-x, -y)),
+                ("skewX", [angle]) => {
+                    Transform([1.0, 0.0, angle.to_radians().tan(), 1.0, 0.0, 0.0])
+                }
+                ("skewY", [angle]) => {
+                    Transform([1.0, angle.to_radians().tan(), 0.0, 1.0, 0.0, 0.0])
+                }
+                _ => return None,
+            };
+            transform = transform.multiply(next);
+            rest = after_arguments.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        }
+
+        Some(transform)
+    }
+}
+
+fn rotate(degrees: f32) -> Transform {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    Transform([cos, sin, -sin, cos, 0.0, 0.0])
+}
+
+/// The stroke properties that are inherited from parent elements
+#[derive(Clone, Copy, Debug)]
+struct Style {
+    color: Color,
+    width: f32,
+    join: LineJoin,
+    cap: LineCap,
+    stroke_opacity: f32,
+    /// Group opacity, which isn't inherited in SVG but
+    /// fades everything in the group so it is multiplied down
+    opacity: f32,
+    hidden: bool,
+}
+
+impl Default for Style {
+    /// The initial values SVG uses, except that shapes without a
+    /// stroke are still drawn with one so that they can be edited
+    fn default() -> Self {
+        Self {
+            color: Color::BLACK,
+            width: 1.0,
+            join: LineJoin::Miter,
+            cap: LineCap::Butt,
+            stroke_opacity: 1.0,
+            opacity: 1.0,
+            hidden: false,
+        }
+    }
+}
+
+impl Style {
+    /// Applies the presentation attributes of node, and then the
+    /// declarations in its style attribute which take priority over them
+    fn apply(mut self, node: &Node) -> Self {
+        let attributes = node
+            .attributes()
+            .map(|attribute| (attribute.name(), attribute.value()));
+        let declarations = node
+            .attribute("style")
+            .into_iter()
+            .flat_map(|style| style.split(';'))
+            .filter_map(|declaration| declaration.split_once(':'))
+            .map(|(name, value)| (name.trim(), value.trim()));
+
+        for (name, value) in attributes.chain(declarations) {
+            match name {
+                "stroke" => {
+                    if let Some(color) = parse_color(value) {
+                        self.color = color;
+                    }
+                }
+                "stroke-width" => {
+                    if let Some(width) = parse_length(value) {
+                        self.width = width;
+                    }
+                }
+                "stroke-opacity" => {
+                    if let Some(opacity) = parse_length(value) {
+                        self.stroke_opacity = opacity.clamp(0.0, 1.0);
+                    }
+                }
+                "opacity" => {
+                    if let Some(opacity) = parse_length(value) {
+                        self.opacity *= opacity.clamp(0.0, 1.0);
+                    }
+                }
+                "stroke-linejoin" => match value {
+                    "miter" => self.join = LineJoin::Miter,
+                    "bevel" => self.join = LineJoin::Bevel,
+                    "round" => self.join = LineJoin::Round,
+                    _ => {}
+                },
+                "stroke-linecap" => match value {
+                    "butt" => self.cap = LineCap::Butt,
+                    "square" => self.cap = LineCap::Square,
+                    "round" => self.cap = LineCap::Round,
+                    _ => {}
+                },
+                "display" => self.hidden = value == "none",
+                _ => {}
+            }
+        }
+
+        self
+    }
+
+    fn stroke_style(&self, transform: &Transform) -> StrokeStyle {
+        let [r, g, b, a] = self.color.rgba();
+        StrokeStyle {
+            color: Color::new_with_alpha(r, g, b, a * self.stroke_opacity * self.opacity),
+            width: self.width * transform.scale(),
+            join: self.join,
+            cap: self.cap,
+        }
+    }
+}
+
+/// Parses hex colors, `rgb()` and the most common color keywords.
+/// Anything else, including `none`, isn't a color that can be drawn
+fn parse_color(value: &str) -> Option<Color> {
+    let value = value.trim();
+    if let Some(hex) = value.strip_prefix('#') {
+        let digits: Vec<_> = hex
+            .chars()
+            .map(|digit| digit.to_digit(16))
+            .collect::<Option<_>>()?;
+        let channels = match digits.as_slice() {
+            [r, g, b] => [r * 17, g * 17, b * 17],
+            [r1, r2, g1, g2, b1, b2] => [r1 * 16 + r2, g1 * 16 + g2, b1 * 16 + b2],
+            _ => return None,
+        };
+        let [r, g, b] = channels.map(|channel| channel as f32 / 255.0);
+        return Some(Color::new(r, g, b));
+    }
+
+    if let Some(arguments) = value
+        .strip_prefix("rgb(")
+        .and_then(|value| value.strip_suffix(')'))
+    {
+        let scale = if arguments.contains('%') {
+            100.0
+        } else {
+            255.0
+        };
+        let [r, g, b] = parse_numbers(arguments).try_into().ok()?;
+        let channel = |value: f32| (value / scale).clamp(0.0, 1.0);
+        return Some(Color::new(channel(r), channel(g), channel(b)));
+    }
+
+    let [r, g, b] = match value {
+        "black" => [0, 0, 0],
+        "white" => [255, 255, 255],
+        "red" => [255, 0, 0],
+        "green" => [0, 128, 0],
+        "lime" => [0, 255, 0],
+        "blue" => [0, 0, 255],
+        "yellow" => [255, 255, 0],
+        "orange" => [255, 165, 0],
+        "purple" => [128, 0, 128],
+        "gray" | "grey" => [128, 128, 128],
+        _ => return None,
+    };
+    Some(Color::new(
+        r as f32 / 255.0,
+        g as f32 / 255.0,
+        b as f32 / 255.0,
+    ))
+}
+
+/// Lengths are read as user units whatever their unit is
+fn parse_length(value: &str) -> Option<f32> {
+    parse_numbers(value).first().copied()
+}
+
+/// The path data for one of the basic shapes, which
+/// are all equivalent to a path with the same outline
+fn shape_path_data(node: &Node) -> Option<String> {
+    let length = |name| node.attribute(name).and_then(parse_length);
+    let length_or_zero = |name| length(name).unwrap_or_default();
+
+    let d = match node.tag_name().name() {
+        "path" => node.attribute("d")?.to_owned(),
+        "line" => format!(
+            "M{} {} L{} {}",
+            length_or_zero("x1"),
+            length_or_zero("y1"),
+            length_or_zero("x2"),
+            length_or_zero("y2"),
+        ),
+        "rect" => {
+            let (x, y) = (length_or_zero("x"), length_or_zero("y"));
+            let (width, height) = (length("width")?, length("height")?);
+            if width <= 0.0 || height <= 0.0 {
+                return None;
+            }
+            // A missing radius is the same as the other one
+            let (rx, ry) = match (length("rx"), length("ry")) {
+                (Some(rx), Some(ry)) => (rx, ry),
+                (Some(radius), None) | (None, Some(radius)) => (radius, radius),
+                (None, None) => (0.0, 0.0),
+            };
+            let (rx, ry) = (rx.clamp(0.0, width / 2.0), ry.clamp(0.0, height / 2.0));
+            let (right, bottom) = (x + width, y + height);
+
+            if rx > 0.0 && ry > 0.0 {
+                let corner = format!("A{rx} {ry} 0 0 1");
+                format!(
+                    "M{} {y} H{} {corner} {right} {} V{} {corner} {} {bottom} H{} {corner} {x} {} V{} {corner} {} {y} Z",
+                    x + rx,
+                    right - rx,
+                    y + ry,
+                    bottom - ry,
+                    right - rx,
+                    x + rx,
+                    bottom - ry,
+                    y + ry,
+                    x + rx,
+                )
+            } else {
+                format!("M{x} {y} H{right} V{bottom} H{x} Z")
+            }
+        }
+        "circle" | "ellipse" => {
+            let (cx, cy) = (length_or_zero("cx"), length_or_zero("cy"));
+            let (rx, ry) = if node.tag_name().name() == "circle" {
+                let radius = length("r")?;
+                (radius, radius)
+            } else {
+                (length("rx")?, length("ry")?)
+            };
+            if rx <= 0.0 || ry <= 0.0 {
+                return None;
+            }
+            let quarter = format!("A{rx} {ry} 0 0 1");
+            format!(
+                "M{} {cy} {quarter} {cx} {} {quarter} {} {cy} {quarter} {cx} {} {quarter} {} {cy} Z",
+                cx + rx,
+                cy + ry,
+                cx - rx,
+                cy - ry,
+                cx + rx,
+            )
+        }
+        name @ ("polyline" | "polygon") => {
+            let numbers = parse_numbers(node.attribute("points")?);
+            let mut points = numbers.chunks_exact(2);
+            let [x, y] = points.next()?.try_into().ok()?;
+            let mut d = format!("M{x} {y}");
+            for point in points {
+                d.push_str(&format!(" L{} {}", point[0], point[1]));
+            }
+            if name == "polygon" {
+                d.push_str(" Z");
+            }
+            d
+        }
+        _ => return None,
+    };
+
+    Some(d)
+}
+
+/// Elements whose children are never drawn directly
+const NOT_RENDERED: [&str; 8] = [
+    "defs",
+    "symbol",
+    "clipPath",
+    "mask",
+    "pattern",
+    "marker",
+    "linearGradient",
+    "radialGradient",
+];
+
+/// Whether the handles either side of a point are the same length in
+/// opposite directions, like the handles of an `S` command, which
+/// keeps the join smooth as long as they stay mirrored
+fn mirrored(in_handle: Position, point: Position, out_handle: Position) -> bool {
+    in_handle != point && in_handle + out_handle == point * 2.0
+}
+
+fn import_node(
+    node: Node,
+    parent_transform: Transform,
+    parent_style: Style,
+    document: &mut Document,
+) {
+    if !node.is_element() || NOT_RENDERED.contains(&node.tag_name().name()) {
+        return;
+    }
+
+    let style = parent_style.apply(&node);
+    if style.hidden {
+        return;
+    }
+    let transform = node
+        .attribute("transform")
+        .map(|transform| Transform::parse(transform).unwrap_or(Transform::IDENTITY))
+        .map_or(parent_transform, |transform| {
+            parent_transform.multiply(transform)
+        });
+
+    if let Some(d) = shape_path_data(&node) {
+        let stroke_style = style.stroke_style(&transform);
+        for subpath in parse_path_data(&d) {
+            let positions: Vec<_> = subpath
+                .curves
+                .iter()
+                .map(|curve| curve.map(|point| transform.apply(point)))
+                .collect();
+            let curves: Vec<_> = positions
+                .iter()
+                .map(|positions| document.push_curve(*positions, stroke_style))
+                .collect();
+
+            // Each segment starts where the last one ended, so they are
+            // joined to move together like the shape's vertices
+            let mut joins: Vec<_> = (1..curves.len()).map(|i| (i - 1, i)).collect();
+            if subpath.closed && !curves.is_empty() {
+                joins.push((curves.len() - 1, 0));
+            }
+            for (first, second) in joins {
+                document.join_curves(curves[first], curves[second]);
+                let [.., in_handle, point] = positions[first];
+                if mirrored(in_handle, point, positions[second][1]) {
+                    document.mirror_handles(curves[first], curves[second]);
+                }
+            }
+
+            document.paths.push(PathData {
+                curves,
+                followers: Vec::new(),
+            });
+        }
+        return;
+    }
+
+    for child in node.children() {
+        import_node(child, transform, style, document);
+    }
+}
+
+/// Converts the paths and basic shapes in an SVG into a document where
+/// each subpath is a path of curves. Coordinates are kept in the user
+/// space of the root element, so a `viewBox` doesn't scale the curves
+pub fn import_svg(text: &str) -> Result<Document, DocumentError> {
+    let svg = roxmltree::Document::parse(text)?;
+    let mut document = Document::default();
+    import_node(
+        svg.root_element(),
+        Transform::IDENTITY,
+        Style::default(),
+        &mut document,
+    );
+    Ok(document)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bezier::document::{ConnectionData, ConstraintData};
+
+    fn ends(document: &Document, path: usize) -> Vec<Position> {
+        document.paths[path]
+            .curves
+            .iter()
+            .map(|curve| {
+                let id = document.curves[*curve].end_point;
+                document
+                    .points
+                    .iter()
+                    .find(|point| point.id == id)
+                    .unwrap()
+                    .position
+            })
+            .collect()
+    }
+
+    /// Whether the end of first is joined to the start of second both ways
+    fn joined(document: &Document, first: usize, second: usize) -> bool {
+        let end_point = document.curves[first].end_point;
+        let start_point = document.curves[second].start_point;
+        let connected = |host, other| {
+            let point = document.points.iter().find(|point| point.id == host);
+            point.unwrap().connections.iter().any(|connection| {
+                connection.point == other && connection.constraint == ConstraintData::Rigid
+            })
+        };
+        connected(end_point, start_point) && connected(start_point, end_point)
+    }
+
+    fn assert_near(a: Position, b: Position) {
+        assert!(a.distance(&b) < 1e-3, "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn transforms() {
+        let transform = Transform::parse("translate(10) rotate(90, 5 5), scale(2 1)").unwrap();
+        // Scaled to (2, 0), rotated about (5, 5) to (10, 2) and moved by 10
+        assert_near(
+            transform.apply(Position::new(1.0, 0.0)),
+            Position::new(20.0, 2.0),
+        );
+        assert!(Transform::parse("translate(1) wobble(2)").is_none());
+    }
+
+    #[test]
+    fn imports_shapes_with_styles() {
+        let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 100 100">
+            <defs><path d="M0 0 L1 1"/></defs>
+            <g transform="translate(10 20)" stroke="#f00" stroke-width="2">
+                <path d="M0 0 h10 m0 10 h10" style="stroke: rgb(0, 0, 255); opacity: 0.5"/>
+                <g transform="scale(2)">
+                    <rect x="1" y="1" width="4" height="2" stroke-linejoin="round"/>
+                </g>
+            </g>
+            <circle cx="50" cy="50" r="10"/>
+            <polyline points="0,0 10,0 10,10 5"/>
+            <line x1="0" y1="0" x2="1" y2="1" display="none"/>
+        </svg>"##;
+        let document = import_svg(svg).unwrap();
+        // Two subpaths, a rectangle, a circle and a polyline
+        assert_eq!(document.paths.len(), 5);
+
+        assert_eq!(ends(&document, 0), [Position::new(20.0, 20.0)]);
+        assert_eq!(ends(&document, 1), [Position::new(30.0, 30.0)]);
+        let style = document.curves[0].style;
+        assert_eq!(style.color, Color::new_with_alpha(0.0, 0.0, 1.0, 0.5));
+        assert_eq!(style.width, 2.0);
+
+        assert_eq!(
+            ends(&document, 2),
+            [
+                Position::new(20.0, 22.0),
+                Position::new(20.0, 26.0),
+                Position::new(12.0, 26.0),
+                Position::new(12.0, 22.0),
+            ]
+        );
+        // Every side of the rectangle is joined to the next, all the way round
+        let sides = &document.paths[2].curves;
+        for (i, side) in sides.iter().enumerate() {
+            assert!(joined(&document, *side, sides[(i + 1) % sides.len()]));
+        }
+        let rect = document.curves[document.paths[2].curves[0]].style;
+        assert_eq!(rect.color, Color::new(1.0, 0.0, 0.0));
+        assert_eq!(rect.width, 4.0);
+        assert_eq!(rect.join, LineJoin::Round);
+
+        let circle = ends(&document, 3);
+        assert_eq!(circle.len(), 4);
+        assert_near(circle[0], Position::new(50.0, 60.0));
+        assert_eq!(
+            document.curves[document.paths[3].curves[0]].style.color,
+            Color::BLACK
+        );
+
+        // The odd number at the end of the points is ignored
+        assert_eq!(ends(&document, 4).len(), 2);
+        // An open polyline doesn't join its last segment back to the first
+        let [first, last] = [0, 1].map(|i| document.paths[4].curves[i]);
+        assert!(joined(&document, first, last));
+        assert!(!joined(&document, last, first));
+        // Separate subpaths aren't joined
+        assert!(!joined(
+            &document,
+            document.paths[0].curves[0],
+            document.paths[1].curves[0]
+        ));
+    }
+
+    #[test]
+    fn mirrors_smooth_joins() {
+        let svg = r#"<svg><path d="M0 0 C0 10 10 10 10 0 S20 -10 20 0 C20 5 30 10 30 0"/></svg>"#;
+        let document = import_svg(svg).unwrap();
+        let [first, second, third] = [0, 1, 2].map(|i| document.curves[i].clone());
+        assert!(joined(&document, 0, 1) && joined(&document, 1, 2));
+
+        let connections = |id| {
+            let point = document.points.iter().find(|point| point.id == id);
+            point.unwrap().connections.clone()
+        };
+        // The reflected handle of the S command is mirrored
+        let mirrors = connections(first.end_point);
+        assert!(mirrors.contains(&ConnectionData {
+            point: first.end_handle,
+            constraint: ConstraintData::Mirror(second.start_handle),
+        }));
+        assert!(mirrors.contains(&ConnectionData {
+            point: second.start_handle,
+            constraint: ConstraintData::Mirror(first.end_handle),
+        }));
+        // A corner between curves isn't
+        let corner = connections(second.end_point);
+        assert!(!corner.iter().any(|connection| {
+            matches!(connection.constraint, ConstraintData::Mirror(_))
+                || connection.point == third.start_handle
+        }));
+    }
+
+    #[test]
+    fn rounded_rects() {
+        let document = import_svg(r#"<svg><rect width="10" height="10" rx="2"/></svg>"#).unwrap();
+        let ends = ends(&document, 0);
+        assert_eq!(ends.len(), 8);
+        assert_eq!(ends[0], Position::new(8.0, 0.0));
+        assert_near(ends[1], Position::new(10.0, 2.0));
+        assert_near(*ends.last().unwrap(), Position::new(2.0, 0.0));
+    }
+}
@@ -0,0 +1,468 @@
+use std::f64::consts::{FRAC_PI_2, TAU};
+
+use crate::position::Position;
+
+/// Start point, start handle, end handle and end point of a cubic curve
+pub type Cubic = [Position; 4];
+
+/// Curves that follow on from each other, from a move command to the next
+/// move or close command
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Subpath {
+    pub curves: Vec<Cubic>,
+    pub closed: bool,
+}
+
+fn line(from: Position, to: Position) -> Cubic {
+    [
+        from,
+        Position::lerp(from, to, 1.0 / 3.0),
+        Position::lerp(from, to, 2.0 / 3.0),
+        to,
+    ]
+}
+
+/// A quadratic curve has exactly the same shape as a
+/// cubic curve with handles 2/3 of the way to its control point
+fn quadratic(from: Position, control: Position, to: Position) -> Cubic {
+    [
+        from,
+        Position::lerp(from, control, 2.0 / 3.0),
+        Position::lerp(to, control, 2.0 / 3.0),
+        to,
+    ]
+}
+
+/// Approximates an SVG elliptical arc with one cubic curve for every
+/// quarter turn or less, following the SVG implementation notes for
+/// converting from endpoint to center parameterization
+fn arc(
+    from: Position,
+    radii: (f32, f32),
+    x_axis_rotation: f32,
+    large_arc: bool,
+    sweep: bool,
+    to: Position,
+) -> Vec<Cubic> {
+    if from == to {
+        return Vec::new();
+    }
+    let (mut rx, mut ry) = (radii.0.abs() as f64, radii.1.abs() as f64);
+    if rx == 0.0 || ry == 0.0 {
+        return vec![line(from, to)];
+    }
+
+    let (sin, cos) = (x_axis_rotation as f64).to_radians().sin_cos();
+    let rotate = |x: f64, y: f64| (cos * x - sin * y, sin * x + cos * y);
+    let (x1, y1, x2, y2) = (
+        from.x() as f64,
+        from.y() as f64,
+        to.x() as f64,
+        to.y() as f64,
+    );
+
+    // The start point in a space where the ellipse isn't rotated
+    // and the midpoint between the start and end is the origin
+    let (dx, dy) = ((x1 - x2) / 2.0, (y1 - y2) / 2.0);
+    let (x1p, y1p) = (cos * dx + sin * dy, -sin * dx + cos * dy);
+
+    // Radii that are too small to reach the end are scaled up until they just do
+    let lambda = (x1p / rx).powi(2) + (y1p / ry).powi(2);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+
+    let numerator = (rx * ry).powi(2) - (rx * y1p).powi(2) - (ry * x1p).powi(2);
+    let denominator = (rx * y1p).powi(2) + (ry * x1p).powi(2);
+    let sign = if large_arc == sweep { -1.0 } else { 1.0 };
+    let coefficient = sign * (numerator / denominator).max(0.0).sqrt();
+    let (cxp, cyp) = (coefficient * rx * y1p / ry, -coefficient * ry * x1p / rx);
+    let (cx, cy) = rotate(cxp, cyp);
+    let (cx, cy) = (cx + (x1 + x2) / 2.0, cy + (y1 + y2) / 2.0);
+
+    let angle = |ux: f64, uy: f64, vx: f64, vy: f64| (ux * vy - uy * vx).atan2(ux * vx + uy * vy);
+    let start_angle = angle(1.0, 0.0, (x1p - cxp) / rx, (y1p - cyp) / ry);
+    let mut sweep_angle = angle(
+        (x1p - cxp) / rx,
+        (y1p - cyp) / ry,
+        (-x1p - cxp) / rx,
+        (-y1p - cyp) / ry,
+    );
+    if !sweep && sweep_angle > 0.0 {
+        sweep_angle -= TAU;
+    } else if sweep && sweep_angle < 0.0 {
+        sweep_angle += TAU;
+    }
+
+    let count = (sweep_angle.abs() / FRAC_PI_2).ceil().max(1.0) as usize;
+    let step = sweep_angle / count as f64;
+    // How far along the tangent the handles are for a curve
+    // that follows a circle for step radians
+    let handle_length = 4.0 / 3.0 * (step / 4.0).tan();
+
+    let point = |angle: f64| {
+        let (x, y) = rotate(rx * angle.cos(), ry * angle.sin());
+        Position::new((cx + x) as f32, (cy + y) as f32)
+    };
+    let tangent = |angle: f64| {
+        let (x, y) = rotate(-rx * angle.sin(), ry * angle.cos());
+        Position::new((x * handle_length) as f32, (y * handle_length) as f32)
+    };
+
+    (0..count)
+        .map(|i| {
+            let start = start_angle + step * i as f64;
+            let end = start + step;
+            // The ends are set exactly so that rounding errors
+            // don't leave gaps between the arc and what is next to it
+            let start_point = if i == 0 { from } else { point(start) };
+            let end_point = if i == count - 1 { to } else { point(end) };
+            [
+                start_point,
+                start_point + tangent(start),
+                end_point - tangent(end),
+                end_point,
+            ]
+        })
+        .collect()
+}
+
+/// Reads the numbers and commands of path data and lists of numbers
+struct Parser<'a> {
+    text: &'a [u8],
+    index: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            text: text.as_bytes(),
+            index: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.index).copied()
+    }
+
+    fn skip_separators(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r' | b',')) {
+            self.index += 1;
+        }
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.skip_separators();
+        self.peek().is_none()
+    }
+
+    fn command(&mut self) -> Option<u8> {
+        self.skip_separators();
+        let command = self.peek().filter(u8::is_ascii_alphabetic)?;
+        self.index += 1;
+        Some(command)
+    }
+
+    fn number(&mut self) -> Option<f32> {
+        self.skip_separators();
+        let start = self.index;
+        if matches!(self.peek(), Some(b'+' | b'-')) {
+            self.index += 1;
+        }
+        let mut digits = false;
+        let mut point = false;
+        while let Some(byte) = self.peek() {
+            match byte {
+                b'0'..=b'9' => digits = true,
+                // A second point starts the next number, like in "1.5.5"
+                b'.' if !point => point = true,
+                _ => break,
+            }
+            self.index += 1;
+        }
+        if digits && matches!(self.peek(), Some(b'e' | b'E')) {
+            let mantissa_end = self.index;
+            self.index += 1;
+            if matches!(self.peek(), Some(b'+' | b'-')) {
+                self.index += 1;
+            }
+            let exponent_start = self.index;
+            while matches!(self.peek(), Some(b'0'..=b'9')) {
+                self.index += 1;
+            }
+            if self.index == exponent_start {
+                self.index = mantissa_end;
+            }
+        }
+
+        let number = std::str::from_utf8(&self.text[start..self.index])
+            .ok()
+            .filter(|_| digits)
+            .and_then(|number| number.parse().ok());
+        if number.is_none() {
+            self.index = start;
+        }
+        number
+    }
+
+    /// Arc flags are a single digit that doesn't need to be
+    /// separated from what comes after it, like in "a1 1 0 011 1"
+    fn flag(&mut self) -> Option<bool> {
+        self.skip_separators();
+        let flag = match self.peek()? {
+            b'0' => false,
+            b'1' => true,
+            _ => return None,
+        };
+        self.index += 1;
+        Some(flag)
+    }
+
+    fn point(&mut self) -> Option<Position> {
+        Some(Position::new(self.number()?, self.number()?))
+    }
+}
+
+/// Every number in text, such as the points of a polygon
+pub fn parse_numbers(text: &str) -> Vec<f32> {
+    let mut parser = Parser::new(text);
+    std::iter::from_fn(|| parser.number()).collect()
+}
+
+/// Converts the `d` attribute of an SVG path into cubic curves. Like
+/// browsers do, everything up to the first error in the data is kept
+pub fn parse_path_data(d: &str) -> Vec<Subpath> {
+    let mut parser = Parser::new(d);
+    let mut subpaths = Vec::new();
+    let mut subpath = Subpath::default();
+    let mut current = Position::default();
+    let mut start = Position::default();
+    // The last handle of the previous curve, which S and T reflect
+    let mut last_cubic_handle = None;
+    let mut last_quadratic_control = None;
+    let mut command = None;
+    let mut moved = false;
+
+    while !parser.at_end() {
+        match parser.command() {
+            Some(next) => command = Some(next),
+            // Numbers without a command repeat the last one, except
+            // that the points after a move are lines
+            None => match command {
+                Some(b'M') => command = Some(b'L'),
+                Some(b'm') => command = Some(b'l'),
+                Some(b'Z' | b'z') | None => break,
+                _ => {}
+            },
+        }
+        let Some(command) = command else {
+            break;
+        };
+        // Path data has to start with a move
+        if !moved && !command.eq_ignore_ascii_case(&b'M') {
+            break;
+        }
+        let relative = command.is_ascii_lowercase();
+        let base = if relative {
+            current
+        } else {
+            Position::default()
+        };
+        let offset = move |point: Position| point + base;
+
+        let mut cubic_handle = None;
+        let mut quadratic_control = None;
+        let curves = match command.to_ascii_uppercase() {
+            b'M' => {
+                let Some(point) = parser.point() else {
+                    break;
+                };
+                if !subpath.curves.is_empty() {
+                    subpaths.push(std::mem::take(&mut subpath));
+                }
+                current = offset(point);
+                start = current;
+                moved = true;
+                Vec::new()
+            }
+            b'L' => {
+                let Some(point) = parser.point() else {
+                    break;
+                };
+                vec![line(current, offset(point))]
+            }
+            b'H' => {
+                let Some(x) = parser.number() else {
+                    break;
+                };
+                let x = if relative { current.x() + x } else { x };
+                vec![line(current, Position::new(x, current.y()))]
+            }
+            b'V' => {
+                let Some(y) = parser.number() else {
+                    break;
+                };
+                let y = if relative { current.y() + y } else { y };
+                vec![line(current, Position::new(current.x(), y))]
+            }
+            b'C' => {
+                let (Some(start_handle), Some(end_handle), Some(point)) =
+                    (parser.point(), parser.point(), parser.point())
+                else {
+                    break;
+                };
+                cubic_handle = Some(offset(end_handle));
+                vec![[
+                    current,
+                    offset(start_handle),
+                    offset(end_handle),
+                    offset(point),
+                ]]
+            }
+            b'S' => {
+                let (Some(end_handle), Some(point)) = (parser.point(), parser.point()) else {
+                    break;
+                };
+                let start_handle =
+                    last_cubic_handle.map_or(current, |handle| current * 2.0 - handle);
+                cubic_handle = Some(offset(end_handle));
+                vec![[current, start_handle, offset(end_handle), offset(point)]]
+            }
+            b'Q' => {
+                let (Some(control), Some(point)) = (parser.point(), parser.point()) else {
+                    break;
+                };
+                quadratic_control = Some(offset(control));
+                vec![quadratic(current, offset(control), offset(point))]
+            }
+            b'T' => {
+                let Some(point) = parser.point() else {
+                    break;
+                };
+                let control =
+                    last_quadratic_control.map_or(current, |control| current * 2.0 - control);
+                quadratic_control = Some(control);
+                vec![quadratic(current, control, offset(point))]
+            }
+            b'A' => {
+                let (Some(rx), Some(ry), Some(rotation), Some(large_arc), Some(sweep), Some(point)) = (
+                    parser.number(),
+                    parser.number(),
+                    parser.number(),
+                    parser.flag(),
+                    parser.flag(),
+                    parser.point(),
+                ) else {
+                    break;
+                };
+                arc(current, (rx, ry), rotation, large_arc, sweep, offset(point))
+            }
+            b'Z' => {
+                let closing = (current != start).then(|| line(current, start));
+                subpath.curves.extend(closing);
+                if !subpath.curves.is_empty() {
+                    subpath.closed = true;
+                    subpaths.push(std::mem::take(&mut subpath));
+                }
+                current = start;
+                Vec::new()
+            }
+            _ => break,
+        };
+
+        if let Some(curve) = curves.last() {
+            current = curve[3];
+        }
+        subpath.curves.extend(curves);
+        last_cubic_handle = cubic_handle;
+        last_quadratic_control = quadratic_control;
+    }
+
+    if !subpath.curves.is_empty() {
+        subpaths.push(subpath);
+    }
+    subpaths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ends(subpath: &Subpath) -> Vec<(Position, Position)> {
+        subpath
+            .curves
+            .iter()
+            .map(|curve| (curve[0], curve[3]))
+            .collect()
+    }
+
+    fn assert_near(a: Position, b: Position) {
+        assert!(a.distance(&b) < 1e-3, "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn lines_and_moves() {
+        let subpaths = parse_path_data("M10,10 h20 v-10.5-.5 L 0 0 z m5 5 20 0");
+        assert_eq!(subpaths.len(), 2);
+        assert!(subpaths[0].closed);
+        assert_eq!(
+            ends(&subpaths[0])
+                .into_iter()
+                .map(|(_, end)| end)
+                .collect::<Vec<_>>(),
+            [
+                Position::new(30.0, 10.0),
+                Position::new(30.0, -0.5),
+                Position::new(30.0, -1.0),
+                Position::new(0.0, 0.0),
+                Position::new(10.0, 10.0),
+            ]
+        );
+        // Moves after a close are relative to the start of the closed subpath
+        assert_eq!(
+            ends(&subpaths[1]),
+            [(Position::new(15.0, 15.0), Position::new(35.0, 15.0))]
+        );
+        assert!(!subpaths[1].closed);
+    }
+
+    #[test]
+    fn smooth_curves_reflect_handles() {
+        let subpaths = parse_path_data("M0 0 C0 10 10 10 10 0 s10-10 10 0 Q25 5 30 0 T40 0");
+        let curves = &subpaths[0].curves;
+        assert_eq!(curves[1][1], Position::new(10.0, -10.0));
+        assert_eq!(curves[1][2], Position::new(20.0, -10.0));
+        // A quadratic's handles are 2/3 of the way to its control point
+        assert_near(
+            curves[2][1],
+            Position::new(20.0 + 5.0 * 2.0 / 3.0, 10.0 / 3.0),
+        );
+        // T reflects the last control point to (35, -5)
+        assert_near(curves[3][2], Position::new(40.0 - 10.0 / 3.0, -10.0 / 3.0));
+    }
+
+    #[test]
+    fn arcs() {
+        // A half circle of radius 10 with compact flags
+        let subpaths = parse_path_data("M0 0a10 10 0 0020 0");
+        let curves = &subpaths[0].curves;
+        assert_eq!(curves.len(), 2);
+        assert_eq!(curves[1][3], Position::new(20.0, 0.0));
+        // Without the sweep flag the arc goes anticlockwise on screen,
+        // which is down through the bottom of the circle since y is down
+        assert_near(curves[0][3], Position::new(10.0, 10.0));
+
+        // Radii that are too small are scaled up to fit
+        let subpaths = parse_path_data("M0 0 A1 1 0 0 1 20 0");
+        assert_near(subpaths[0].curves[0][3], Position::new(10.0, -10.0));
+    }
+
+    #[test]
+    fn stops_at_errors() {
+        let subpaths = parse_path_data("M0 0 L10 0 L20 X 30 0");
+        assert_eq!(subpaths[0].curves.len(), 1);
+        assert!(parse_path_data("L10 0").is_empty());
+        assert_eq!(parse_numbers("1,2 3e1-4.5.5"), [1.0, 2.0, 30.0, -4.5, 0.5]);
+    }
+}
@@ -1,48 +1,62 @@
-use bevy::ecs::{
-    change_detection::DetectChanges,
-    component::Component,
-    entity::Entity,
-    query::{Added, With},
-    removal_detection::RemovedComponents,
-    system::{Commands, Local, Query, Res},
-    world::Ref,
+use bevy::{
+    ecs::{
+        change_detection::DetectChanges,
+        component::Component,
+        entity::Entity,
+        event::EventReader,
+        query::{Or, With},
+        removal_detection::RemovedComponents,
+        system::{Commands, Local, ParamSet, Query, Res},
+        world::{Mut, Ref},
+    },
+    utils::{HashMap, HashSet},
 };
 
 use crate::{
     hidden::Hidden,
     position::Position,
     rendering::{
-        point::Points,
-        primitives::{self, Lines},
+        primitives::{self, Lines, StrokeStyle},
         Color, Stroke,
     },
-    selection::Selected,
+    selection::{Connection, EntitiesDeselected, EntitiesSelected, HoverShape, Hovered, Selected},
 };
 
-use super::{components::BezierCurve, generate_bezier_points, generate_bezier_points_with_offset};
+use super::{
+    components::{
+        BezierCurve, BezierCurvePrimitives, BezierEndPoint, BezierHandle, BezierPath,
+        BezierStartPoint, FollowPath,
+    },
+    create_handle, generate_bezier_points,
+    path::PathSampler,
+};
 
+/// How much wider a hovered curve is drawn than its stroke
+const HOVER_STROKE_WIDENING: f32 = 2.0;
+
+#[allow(clippy::too_many_arguments)]
 fn update_bezier_curve(
     commands: &mut Commands,
     bezier_curve: &BezierCurve,
-    points: &mut Points,
+    stroke_style: Option<Ref<StrokeStyle>>,
+    mut hover_shape: Option<Mut<HoverShape>>,
+    hovered: bool,
     lines: &mut Lines,
     positions_query: &Query<(Ref<Position>, Option<&Selected>)>,
     primitives_query: &mut Query<&mut primitives::Primatives>,
-    system: &Res<crate::my_time::Time>,
     control_points: &mut Local<[Position; 4]>,
 ) {
-    let control_points_query = positions_query.iter_many([
-        bezier_curve.start_point,
-        bezier_curve.start_handle,
-        bezier_curve.end_handle,
-        bezier_curve.end_point,
-    ]);
+    let Ok(control_points_query) = positions_query.get_many(bezier_curve.control_points()) else {
+        // A control point was removed this frame and the curve
+        // will be repaired or removed by [repair_bezier_curve_system]
+        return;
+    };
 
     let mut control_points_changed = false;
     let mut start_selected = false;
     let mut end_selected = false;
 
-    for (i, (point, selected)) in control_points_query.enumerate() {
+    for (i, (point, selected)) in control_points_query.into_iter().enumerate() {
         if point.is_changed() {
             control_points_changed = true;
         }
@@ -52,7 +66,7 @@ fn update_bezier_curve(
         // TODO: Figure out a way to use either refs _or_ objects
         // below. May be able to make improvements in a lot of places
         // since right now we are doing a lot of unnecesary cloning/copying
-        control_points[i] = point.as_ref().clone();
+        control_points[i] = *point.as_ref();
 
         if i == 0 && selected.is_some() {
             start_selected = true;
@@ -63,15 +77,6 @@ fn update_bezier_curve(
         }
     }
 
-    // TODO: Modify this to always have a constant speed and distance between points instead of
-    // what it currently does
-    let offset = system.elapsed / 4.0;
-    let point_iterator =
-        generate_bezier_points_with_offset(&control_points, Some(10), Some(offset), false);
-    for point in point_iterator {
-        points.draw_point(point, 10.0, Color::RED);
-    }
-
     let [start_point_position, start_handle_position, end_handle_position, end_point_position] =
         control_points[..]
     else {
@@ -94,59 +99,269 @@ fn update_bezier_curve(
         end_handle.insert(Hidden);
     }
 
-    if control_points_changed {
-        let mut curve = primitives_query
-            .get_mut(bezier_curve.curve_primitives)
-            .unwrap();
+    let mut curve = primitives_query
+        .get_mut(bezier_curve.curve_primitives)
+        .unwrap();
+
+    // A hovered curve is drawn in a highlight stroke so that it stands out
+    let mut style = stroke_style.as_deref().copied().unwrap_or_default();
+    if hovered {
+        style = StrokeStyle {
+            color: Color::GREEN,
+            width: style.width + HOVER_STROKE_WIDENING,
+            ..style
+        };
+    }
+    if curve.style() != style {
+        curve.set_style(style);
+    }
 
-        let curve_points = generate_bezier_points(&control_points);
-        curve.set_positions(curve_points);
+    if control_points_changed {
+        let curve_points: Vec<_> = generate_bezier_points(control_points).collect();
+        curve.set_positions(curve_points.iter().copied());
+        if let Some(hover_shape) = hover_shape.as_mut() {
+            hover_shape.0 = curve_points;
+        }
     }
 }
 
+#[allow(clippy::type_complexity)]
 pub fn update_bezier_curve_system(
     mut commands: Commands,
-    bezier_curve_query: Query<&BezierCurve>,
-    mut points: Points,
+    mut bezier_curve_query: Query<(
+        &BezierCurve,
+        Option<Ref<StrokeStyle>>,
+        Option<&mut HoverShape>,
+        Option<&Hovered>,
+    )>,
     mut lines: Lines,
     positions_query: Query<(Ref<Position>, Option<&Selected>)>,
     mut primitives_query: Query<&mut primitives::Primatives>,
-    system: Res<crate::my_time::Time>,
     mut control_points: Local<[Position; 4]>,
 ) {
-    for bezier_curve in bezier_curve_query.iter() {
+    for (bezier_curve, stroke_style, hover_shape, hovered) in bezier_curve_query.iter_mut() {
         update_bezier_curve(
             &mut commands,
             bezier_curve,
-            &mut points,
+            stroke_style,
+            hover_shape,
+            hovered.is_some(),
             &mut lines,
             &positions_query,
             &mut primitives_query,
-            &system,
             &mut control_points,
         );
     }
 }
 
+/// Spawns a new handle on top of terminal_point to take the place of
+/// old_handle and returns it
+fn replace_handle(
+    commands: &mut Commands,
+    curve: Entity,
+    old_handle: Entity,
+    terminal_point: Entity,
+    positions_query: &Query<&Position>,
+    connections_query: &mut Query<&mut Connection>,
+) -> Entity {
+    // Only the position may have been removed rather
+    // than the whole entity
+    if let Some(mut old_handle_commands) = commands.get_entity(old_handle) {
+        old_handle_commands.despawn();
+    }
+
+    let position = positions_query
+        .get(terminal_point)
+        .copied()
+        .unwrap_or_default();
+    let new_handle = create_handle(commands, position, curve).id();
+
+    if let Ok(mut connection) = connections_query.get_mut(terminal_point) {
+        connection.replace(old_handle, new_handle);
+    }
+
+    new_handle
+}
+
+/// Fixes up curves that lost one of their control points.
+/// A lost handle is replaced with a new handle sitting on its
+/// terminal point, while losing a terminal point removes the curve
+pub fn repair_bezier_curve_system(
+    mut commands: Commands,
+    mut removed_positions: RemovedComponents<Position>,
+    mut bezier_curve_query: Query<(Entity, &mut BezierCurve)>,
+    positions_query: Query<&Position>,
+    mut connections_query: Query<&mut Connection>,
+    mut removed: Local<HashSet<Entity>>,
+) {
+    removed.clear();
+    removed.extend(removed_positions.read());
+    if removed.is_empty() {
+        return;
+    }
+
+    for (curve_entity, mut curve) in bezier_curve_query.iter_mut() {
+        if removed.contains(&curve.start_point) || removed.contains(&curve.end_point) {
+            commands.entity(curve_entity).despawn();
+            continue;
+        }
+
+        if removed.contains(&curve.start_handle) {
+            curve.start_handle = replace_handle(
+                &mut commands,
+                curve_entity,
+                curve.start_handle,
+                curve.start_point,
+                &positions_query,
+                &mut connections_query,
+            );
+        }
+
+        if removed.contains(&curve.end_handle) {
+            curve.end_handle = replace_handle(
+                &mut commands,
+                curve_entity,
+                curve.end_handle,
+                curve.end_point,
+                &positions_query,
+                &mut connections_query,
+            );
+        }
+    }
+}
+
+/// Removes every entity that belonged to a curve once the curve is gone
+#[allow(clippy::type_complexity)]
+pub fn despawn_orphaned_curve_parts_system(
+    mut commands: Commands,
+    mut removed_curves: RemovedComponents<BezierCurve>,
+    bezier_curve_query: Query<(), With<BezierCurve>>,
+    parts_query: Query<
+        (
+            Entity,
+            Option<&BezierHandle>,
+            Option<&BezierStartPoint>,
+            Option<&BezierEndPoint>,
+            Option<&BezierCurvePrimitives>,
+        ),
+        Or<(
+            With<BezierHandle>,
+            With<BezierStartPoint>,
+            With<BezierEndPoint>,
+            With<BezierCurvePrimitives>,
+        )>,
+    >,
+    mut paths_query: Query<&mut BezierPath>,
+    mut removed: Local<HashSet<Entity>>,
+) {
+    removed.clear();
+    removed.extend(removed_curves.read());
+    if removed.is_empty() {
+        return;
+    }
+
+    for (entity, handle, start_point, end_point, curve_primitives) in parts_query.iter() {
+        let owners = [
+            handle.map(|BezierHandle(curve)| *curve),
+            start_point.map(|BezierStartPoint(curve)| *curve),
+            end_point.map(|BezierEndPoint(curve)| *curve),
+            curve_primitives.map(|BezierCurvePrimitives(curve)| *curve),
+        ];
+        let mut owners = owners.into_iter().flatten();
+
+        // A mid point of a spline belongs to two curves so it
+        // is only removed once neither of them exist
+        let orphaned = owners.clone().any(|owner| removed.contains(&owner))
+            && owners.all(|owner| !bezier_curve_query.contains(owner));
+        if orphaned {
+            commands.entity(entity).despawn();
+        }
+    }
+
+    for mut path in paths_query.iter_mut() {
+        if path.0.iter().any(|curve| removed.contains(curve)) {
+            path.0.retain(|curve| !removed.contains(curve));
+        }
+    }
+}
+
+/// Builds a sampler for every segment of path, skipping
+/// any curves whose control points no longer exist
+fn path_sampler(
+    path: &BezierPath,
+    bezier_curve_query: &Query<&BezierCurve>,
+    positions_query: &Query<&Position>,
+) -> PathSampler {
+    let segments = bezier_curve_query
+        .iter_many(&path.0)
+        .filter_map(|curve| positions_query.get_many(curve.control_points()).ok())
+        .map(|points| points.map(|point| *point))
+        .collect();
+
+    PathSampler::new(segments)
+}
+
+#[allow(clippy::type_complexity)]
+pub fn follow_path_system(
+    time: Res<crate::my_time::Time>,
+    paths_query: Query<&BezierPath>,
+    bezier_curve_query: Query<&BezierCurve>,
+    mut followers_query: Query<(Entity, &mut FollowPath)>,
+    mut positions_queries: ParamSet<(Query<&Position>, Query<&mut Position, With<FollowPath>>)>,
+    mut samplers: Local<HashMap<Entity, PathSampler>>,
+    mut new_positions: Local<Vec<(Entity, Position)>>,
+) {
+    // Paths can be edited at any time so samplers
+    // are only shared between followers for one frame
+    samplers.clear();
+
+    let positions_query = positions_queries.p0();
+    for (entity, mut follow_path) in followers_query.iter_mut() {
+        if !samplers.contains_key(&follow_path.path) {
+            let Ok(path) = paths_query.get(follow_path.path) else {
+                continue;
+            };
+            let sampler = path_sampler(path, &bezier_curve_query, &positions_query);
+            samplers.insert(follow_path.path, sampler);
+        }
+        let sampler = &samplers[&follow_path.path];
+
+        let length = sampler.length();
+        let distance = follow_path.speed * time.delta as f32;
+        follow_path.advance(distance, length);
+
+        if let Some(position) = sampler.position_at_distance(follow_path.progress * length) {
+            new_positions.push((entity, position));
+        }
+    }
+
+    let mut positions_query = positions_queries.p1();
+    for (entity, new_position) in new_positions.drain(..) {
+        if let Ok(mut position) = positions_query.get_mut(entity) {
+            *position = new_position;
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct SolidWhenSelected;
 
 pub fn solid_when_selected_system(
     mut stroke_query: Query<&mut Stroke, With<SolidWhenSelected>>,
-    selected_added: Query<Entity, Added<Selected>>,
-    mut selected_removed: RemovedComponents<Selected>,
+    mut selected: EventReader<EntitiesSelected>,
+    mut deselected: EventReader<EntitiesDeselected>,
 ) {
-    for entity in selected_added.iter() {
-        let Ok(mut stroke) = stroke_query.get_mut(entity) else {
-            continue;
-        };
-        *stroke = Stroke::Solid;
+    for EntitiesSelected(entities) in selected.read() {
+        let mut iter = stroke_query.iter_many_mut(entities);
+        while let Some(mut stroke) = iter.fetch_next() {
+            *stroke = Stroke::Solid;
+        }
     }
 
-    for entity in selected_removed.read() {
-        let Ok(mut stroke) = stroke_query.get_mut(entity) else {
-            continue;
-        };
-        *stroke = Stroke::Outline;
+    for EntitiesDeselected(entities) in deselected.read() {
+        let mut iter = stroke_query.iter_many_mut(entities);
+        while let Some(mut stroke) = iter.fetch_next() {
+            *stroke = Stroke::Outline;
+        }
     }
 }
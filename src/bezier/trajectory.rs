@@ -0,0 +1,515 @@
+use std::{f32::consts::PI, fmt::Write};
+
+use serde::Serialize;
+
+use crate::position::Position;
+
+use super::path::PathSampler;
+
+/// How many time steps the S-curve smoothing window is split into
+const SMOOTHING_STEPS: f32 = 16.0;
+/// The most time steps used for smoothing a whole trajectory,
+/// which keeps very high jerk limits from using up all the memory
+const MAX_SMOOTHING_STEPS: f32 = 100_000.0;
+/// Radians that the path has to turn by where segments join
+/// for the join to be a corner rather than a smooth bend
+const CORNER_ANGLE: f32 = PI / 180.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum VelocityProfile {
+    /// Constant acceleration up to the speed limit and constant braking back down
+    Trapezoidal,
+    /// Acceleration that ramps up and down without going over the jerk limit
+    SCurve,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct TrajectorySettings {
+    /// World units between samples along the path
+    pub spacing: f32,
+    /// World units per second
+    pub max_speed: f32,
+    /// World units per second squared, for speeding up and slowing down
+    pub max_acceleration: f32,
+    /// World units per second cubed, only used by [VelocityProfile::SCurve]
+    pub max_jerk: f32,
+    /// World units per second squared towards the inside of a bend,
+    /// which is what slows things down around tight bends
+    pub max_lateral_acceleration: f32,
+    /// World units per second at corners, where the path changes
+    /// direction without bending so can't be taken at any speed
+    pub corner_speed: f32,
+    pub profile: VelocityProfile,
+}
+
+impl Default for TrajectorySettings {
+    fn default() -> Self {
+        Self {
+            spacing: 1.0,
+            max_speed: 100.0,
+            max_acceleration: 200.0,
+            max_jerk: 2000.0,
+            max_lateral_acceleration: 200.0,
+            corner_speed: 0.0,
+            profile: VelocityProfile::Trapezoidal,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize)]
+pub struct TrajectorySample {
+    /// Seconds from the start of the path
+    pub time: f32,
+    /// World units along the path
+    pub distance: f32,
+    pub x: f32,
+    pub y: f32,
+    /// Radians from the x axis towards the y axis
+    pub heading: f32,
+    /// One over the radius of the bend, positive when turning towards the y axis
+    pub curvature: f32,
+    /// World units per second
+    pub speed: f32,
+}
+
+/// Wraps angle into -PI..PI
+fn wrap_angle(angle: f32) -> f32 {
+    (angle + PI).rem_euclid(2.0 * PI) - PI
+}
+
+/// Linearly interpolates the y value at x, where xs never decreases
+fn interpolate(xs: &[f32], ys: &[f32], x: f32) -> f32 {
+    if xs.len() < 2 {
+        return ys.first().copied().unwrap_or_default();
+    }
+    let index = xs
+        .partition_point(|other| *other < x)
+        .clamp(1, xs.len() - 1);
+    let (x0, x1) = (xs[index - 1], xs[index]);
+    if x1 <= x0 {
+        return ys[index];
+    }
+    let fraction = ((x - x0) / (x1 - x0)).clamp(0.0, 1.0);
+    ys[index - 1] + (ys[index] - ys[index - 1]) * fraction
+}
+
+/// The fastest speeds that start and end at rest, stay under the limit
+/// at every sample and never speed up or slow down faster than
+/// max_acceleration. Speed squared changes linearly with distance under
+/// constant acceleration so the limits can be spread along the samples
+fn trapezoidal(distances: &[f32], limits: &[f32], max_acceleration: f32) -> Vec<f32> {
+    let mut speeds = limits.to_vec();
+    let count = speeds.len();
+    speeds[0] = 0.0;
+    speeds[count - 1] = 0.0;
+
+    for i in 1..count {
+        let reachable =
+            speeds[i - 1].powi(2) + 2.0 * max_acceleration * (distances[i] - distances[i - 1]);
+        speeds[i] = speeds[i].min(reachable.sqrt());
+    }
+    for i in (0..count - 1).rev() {
+        let reachable =
+            speeds[i + 1].powi(2) + 2.0 * max_acceleration * (distances[i + 1] - distances[i]);
+        speeds[i] = speeds[i].min(reachable.sqrt());
+    }
+    speeds
+}
+
+/// When each sample is reached, with the speed changing
+/// at a constant rate between samples
+fn sample_times(distances: &[f32], speeds: &[f32]) -> Vec<f32> {
+    let mut times = Vec::with_capacity(distances.len());
+    let mut time = 0.0;
+    times.push(time);
+    for i in 1..distances.len() {
+        let distance = distances[i] - distances[i - 1];
+        if distance > 0.0 {
+            time += 2.0 * distance / (speeds[i - 1] + speeds[i]).max(f32::MIN_POSITIVE);
+        }
+        times.push(time);
+    }
+    times
+}
+
+/// Smooths a trapezoidal profile by averaging its speed over a sliding
+/// window of time. Averaging turns every jump in acceleration into a ramp
+/// that takes the length of the window, which is long enough that even
+/// going from full acceleration straight to full braking stays within the
+/// jerk limit. Averaging also mixes in speeds from nearby places on the
+/// path, so the limits are first lowered to the lowest limit within the
+/// distance that could be covered in the window. Returns the time and
+/// speed at each sample
+fn s_curve(
+    distances: &[f32],
+    limits: &[f32],
+    settings: &TrajectorySettings,
+) -> (Vec<f32>, Vec<f32>) {
+    let window_time = 2.0 * settings.max_acceleration / settings.max_jerk;
+    let reach = settings.max_speed * window_time;
+    let limits: Vec<f32> = distances
+        .iter()
+        .map(|distance| {
+            let start = distances.partition_point(|other| *other < distance - reach);
+            let end = distances.partition_point(|other| *other <= distance + reach);
+            limits[start..end]
+                .iter()
+                .copied()
+                .fold(f32::INFINITY, f32::min)
+        })
+        .collect();
+
+    let speeds = trapezoidal(distances, &limits, settings.max_acceleration);
+    let times = sample_times(distances, &speeds);
+    let end = *times.last().unwrap();
+
+    let step = (window_time / SMOOTHING_STEPS).max(end / MAX_SMOOTHING_STEPS);
+    // Averaging this many steps spans the whole window
+    let window = (window_time / step).round() as usize + 1;
+    let steps = (end / step).ceil() as usize;
+    let grid: Vec<f32> = (0..=steps)
+        .map(|i| interpolate(&times, &speeds, i as f32 * step))
+        .collect();
+
+    // Each smoothed speed is the average of the window before it,
+    // which is at rest from the start of the trajectory
+    let mut smoothed = Vec::with_capacity(grid.len() + window - 1);
+    let mut sum = 0.0;
+    for i in 0..grid.len() + window - 1 {
+        sum += grid.get(i).copied().unwrap_or_default() as f64;
+        if let Some(old) = i.checked_sub(window).and_then(|old| grid.get(old)) {
+            sum -= *old as f64;
+        }
+        smoothed.push((sum / window as f64).max(0.0) as f32);
+    }
+
+    let mut travelled = Vec::with_capacity(smoothed.len());
+    let mut distance = 0.0;
+    travelled.push(distance);
+    for pair in smoothed.windows(2) {
+        distance += (pair[0] + pair[1]) / 2.0 * step;
+        travelled.push(distance);
+    }
+    // Stepping through time loses a little distance, which
+    // shouldn't leave the end of the path unreachable
+    let length = *distances.last().unwrap();
+    if distance > 0.0 {
+        for travelled in travelled.iter_mut() {
+            *travelled *= length / distance;
+        }
+    }
+
+    let grid_times: Vec<f32> = (0..smoothed.len()).map(|i| i as f32 * step).collect();
+    distances
+        .iter()
+        .map(|distance| {
+            (
+                interpolate(&travelled, &grid_times, *distance),
+                interpolate(&travelled, &smoothed, *distance),
+            )
+        })
+        .unzip()
+}
+
+/// Samples a path at even distances along it and at every corner, with the
+/// heading and curvature of the path and when and how fast each sample is
+/// passed when moving along the path as quickly as the settings allow.
+/// The heading at a corner is the one the path arrives in
+pub fn plan_trajectory(
+    segments: Vec<[Position; 4]>,
+    settings: &TrajectorySettings,
+) -> Vec<TrajectorySample> {
+    if segments.is_empty() {
+        return Vec::new();
+    }
+    let sampler = PathSampler::new(segments);
+    let length = sampler.length();
+    let mut distances: Vec<f32> = if length > 0.0 {
+        let count = (length / settings.spacing).ceil() as usize;
+        (0..=count)
+            .map(|i| (i as f32 * settings.spacing).min(length))
+            .collect()
+    } else {
+        vec![0.0]
+    };
+
+    // Corners get a sample of their own so that the stop happens right at
+    // them, which also keeps the speed there from depending on the spacing
+    let corners = sampler.corners(CORNER_ANGLE);
+    distances.extend(corners.iter().map(|(distance, _)| *distance));
+    distances.sort_by(f32::total_cmp);
+    distances.dedup();
+    let leaving: Vec<Option<Position>> = distances
+        .iter()
+        .map(|distance| {
+            corners
+                .iter()
+                .find(|(corner, _)| corner == distance)
+                .map(|(_, leaving)| *leaving)
+        })
+        .collect();
+
+    let heading = |tangent: Position| tangent.y().atan2(tangent.x());
+    let headings: Vec<f32> = distances
+        .iter()
+        .map(|distance| heading(sampler.tangent_at_distance(*distance).unwrap()))
+        .collect();
+    // The heading each sample is left in, which only differs at corners
+    let departures: Vec<f32> = leaving
+        .iter()
+        .zip(&headings)
+        .map(|(leaving, arriving)| leaving.map_or(*arriving, heading))
+        .collect();
+    let curvatures: Vec<f32> = (0..distances.len())
+        .map(|i| {
+            let before = i.saturating_sub(1);
+            // The turn at a corner isn't a bend, so only look back from it
+            let after = if leaving[i].is_some() {
+                i
+            } else {
+                (i + 1).min(distances.len() - 1)
+            };
+            let distance = distances[after] - distances[before];
+            if distance > 0.0 {
+                wrap_angle(headings[after] - departures[before]) / distance
+            } else {
+                0.0
+            }
+        })
+        .collect();
+    let limits: Vec<f32> = curvatures
+        .iter()
+        .zip(&leaving)
+        .map(|(curvature, leaving)| {
+            let bend_limit = (settings.max_lateral_acceleration / curvature.abs()).sqrt();
+            let limit = settings.max_speed.min(bend_limit);
+            if leaving.is_some() {
+                limit.min(settings.corner_speed)
+            } else {
+                limit
+            }
+        })
+        .collect();
+
+    let (times, speeds) = match settings.profile {
+        VelocityProfile::Trapezoidal => {
+            let speeds = trapezoidal(&distances, &limits, settings.max_acceleration);
+            (sample_times(&distances, &speeds), speeds)
+        }
+        VelocityProfile::SCurve => s_curve(&distances, &limits, settings),
+    };
+
+    (0..distances.len())
+        .map(|i| {
+            let position = sampler.position_at_distance(distances[i]).unwrap();
+            TrajectorySample {
+                time: times[i],
+                distance: distances[i],
+                x: position.x(),
+                y: position.y(),
+                heading: headings[i],
+                curvature: curvatures[i],
+                speed: speeds[i],
+            }
+        })
+        .collect()
+}
+
+pub fn trajectory_csv(samples: &[TrajectorySample]) -> String {
+    let mut csv = String::from("time,distance,x,y,heading,curvature,speed\n");
+    for sample in samples {
+        writeln!(
+            csv,
+            "{},{},{},{},{},{},{}",
+            sample.time,
+            sample.distance,
+            sample.x,
+            sample.y,
+            sample.heading,
+            sample.curvature,
+            sample.speed
+        )
+        .unwrap();
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn straight_line(length: f32) -> Vec<[Position; 4]> {
+        vec![[0.0, 0.0, length, length].map(|y| Position::new(0.0, y))]
+    }
+
+    /// The acceleration between each sample and the next
+    fn accelerations(samples: &[TrajectorySample]) -> Vec<f32> {
+        samples
+            .windows(2)
+            .map(|pair| (pair[1].speed - pair[0].speed) / (pair[1].time - pair[0].time))
+            .collect()
+    }
+
+    #[test]
+    fn trapezoidal_profile() {
+        let settings = TrajectorySettings {
+            max_speed: 10.0,
+            max_acceleration: 10.0,
+            ..Default::default()
+        };
+        let samples = plan_trajectory(straight_line(100.0), &settings);
+
+        assert_eq!(samples.len(), 101);
+        let last = samples.last().unwrap();
+        assert_eq!(last.distance, 100.0);
+        assert_eq!(Position::new(last.x, last.y), Position::new(0.0, 100.0));
+        assert_eq!(samples[0].speed, 0.0);
+        assert_eq!(last.speed, 0.0);
+        // One second to speed up, nine at full speed and one to slow down
+        assert!((last.time - 11.0).abs() < 0.001, "{}", last.time);
+        assert!((samples[50].speed - 10.0).abs() < 0.001);
+
+        for sample in samples.iter() {
+            assert!((sample.heading - PI / 2.0).abs() < 0.001);
+            assert_eq!(sample.curvature, 0.0);
+        }
+        for acceleration in accelerations(&samples) {
+            assert!(acceleration.abs() <= 10.001, "{acceleration}");
+        }
+    }
+
+    #[test]
+    fn slows_down_for_bends() {
+        // A quarter circle with a radius of 50 around the origin
+        let handle = 50.0 * 0.5523;
+        let arc = vec![[
+            Position::new(50.0, 0.0),
+            Position::new(50.0, handle),
+            Position::new(handle, 50.0),
+            Position::new(0.0, 50.0),
+        ]];
+        let settings = TrajectorySettings {
+            max_speed: 1000.0,
+            max_acceleration: 1000.0,
+            max_lateral_acceleration: 50.0,
+            ..Default::default()
+        };
+        let samples = plan_trajectory(arc, &settings);
+
+        let middle = samples[samples.len() / 2];
+        assert!(
+            (middle.curvature - 1.0 / 50.0).abs() < 0.001,
+            "{}",
+            middle.curvature
+        );
+        // Going around a radius of 50 at 50 per second accelerates inwards at 50
+        assert!((middle.speed - 50.0).abs() < 1.0, "{}", middle.speed);
+        let heading = PI / 2.0 + middle.distance / 50.0;
+        assert!(
+            (middle.heading - heading).abs() < 0.001,
+            "{}",
+            middle.heading
+        );
+    }
+
+    #[test]
+    fn slows_down_for_corners() {
+        let corner = || {
+            vec![
+                [0.0, 0.0, 50.0, 50.0].map(|x| Position::new(x, 0.0)),
+                [0.0, 0.0, 50.0, 50.0].map(|y| Position::new(50.0, y)),
+            ]
+        };
+        let at_corner = |settings: &TrajectorySettings| {
+            let samples = plan_trajectory(corner(), settings);
+            let corner = samples.iter().find(|sample| sample.distance == 50.0);
+            *corner.expect("a sample at the corner")
+        };
+
+        // The stop doesn't depend on the samples landing near the corner
+        for spacing in [0.3, 1.0, 7.0] {
+            let settings = TrajectorySettings {
+                spacing,
+                ..Default::default()
+            };
+            let sample = at_corner(&settings);
+            assert_eq!(sample.speed, 0.0, "{spacing}");
+            assert_eq!(Position::new(sample.x, sample.y), Position::new(50.0, 0.0));
+            assert!(sample.heading.abs() < 0.001, "{}", sample.heading);
+            for sample in plan_trajectory(corner(), &settings) {
+                assert!(sample.curvature.abs() < 0.001, "{}", sample.curvature);
+            }
+        }
+
+        let settings = TrajectorySettings {
+            corner_speed: 5.0,
+            ..Default::default()
+        };
+        assert!((at_corner(&settings).speed - 5.0).abs() < 0.001);
+
+        // Straight on isn't a corner, so there's no extra sample
+        let straight = vec![
+            [0.0, 0.0, 50.0, 50.0].map(|x| Position::new(x, 0.0)),
+            [50.0, 50.0, 100.0, 100.0].map(|x| Position::new(x, 0.0)),
+        ];
+        let samples = plan_trajectory(straight, &TrajectorySettings::default());
+        assert_eq!(samples.len(), 101);
+        assert!((samples[50].speed - 100.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn s_curve_profile() {
+        let trapezoidal_settings = TrajectorySettings {
+            max_speed: 10.0,
+            max_acceleration: 10.0,
+            max_jerk: 20.0,
+            ..Default::default()
+        };
+        let settings = TrajectorySettings {
+            profile: VelocityProfile::SCurve,
+            ..trapezoidal_settings.clone()
+        };
+        let trapezoidal = plan_trajectory(straight_line(100.0), &trapezoidal_settings);
+        let samples = plan_trajectory(straight_line(100.0), &settings);
+
+        assert_eq!(samples.len(), trapezoidal.len());
+        let last = samples.last().unwrap();
+        assert_eq!(samples[0].speed, 0.0);
+        assert!(last.speed < 0.1, "{}", last.speed);
+        // Ramping acceleration up and down takes as long as the smoothing window
+        let window_time = 2.0 * 10.0 / 20.0;
+        let extra = last.time - trapezoidal.last().unwrap().time;
+        assert!((extra - window_time).abs() < 0.05, "{extra}");
+
+        let accelerations = accelerations(&samples);
+        for acceleration in accelerations.iter() {
+            assert!(acceleration.abs() <= 10.1, "{acceleration}");
+        }
+        // Acceleration builds up rather than starting at full
+        assert!(accelerations[0] < 5.0, "{}", accelerations[0]);
+        for sample in samples.iter() {
+            assert!(sample.speed <= 10.001);
+        }
+    }
+
+    #[test]
+    fn empty_and_zero_length_paths() {
+        let settings = TrajectorySettings::default();
+        assert!(plan_trajectory(Vec::new(), &settings).is_empty());
+
+        let samples = plan_trajectory(straight_line(0.0), &settings);
+        assert_eq!(samples.len(), 1);
+        assert_eq!(samples[0].time, 0.0);
+        assert_eq!(samples[0].speed, 0.0);
+    }
+
+    #[test]
+    fn writes_csv() {
+        let samples = plan_trajectory(straight_line(2.0), &TrajectorySettings::default());
+        let csv = trajectory_csv(&samples);
+        let rows: Vec<_> = csv.lines().collect();
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[0], "time,distance,x,y,heading,curvature,speed");
+        assert!(rows[1].starts_with("0,0,0,0,"));
+    }
+}
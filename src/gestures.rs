@@ -0,0 +1,388 @@
+use bevy::{
+    app::{Plugin, PreUpdate},
+    ecs::{
+        event::{Event, EventWriter},
+        query::With,
+        schedule::IntoSystemConfigs,
+        system::{Local, Query, Res, Resource},
+    },
+    input::{mouse::MouseButton, ButtonInput, InputSystem},
+    utils::HashMap,
+    window::{PrimaryWindow, Window},
+};
+
+use crate::{my_time::Time, position::Position, rendering::Camera};
+
+/// A high level mouse interaction, built from the raw mouse buttons
+/// and cursor so that systems don't each have to work out whether
+/// a press turned into a click or a drag. Positions are in world space
+#[derive(Event, Clone, Copy, Debug, PartialEq)]
+#[allow(dead_code)]
+pub enum Gesture {
+    /// The button was pressed and released without moving far
+    Click {
+        button: MouseButton,
+        position: Position,
+    },
+    /// Sent along with the second of two clicks close together in
+    /// time and space
+    DoubleClick {
+        button: MouseButton,
+        position: Position,
+    },
+    /// The mouse moved far enough with the button held to start a drag
+    DragStart {
+        button: MouseButton,
+        /// Where the button was pressed
+        start: Position,
+        position: Position,
+    },
+    /// The mouse moved during a drag
+    Drag {
+        button: MouseButton,
+        delta: Position,
+        position: Position,
+    },
+    /// The button was released during a drag
+    DragEnd {
+        button: MouseButton,
+        position: Position,
+    },
+    /// The button was held without moving for a while,
+    /// releasing after a long press doesn't send a click
+    LongPress {
+        button: MouseButton,
+        position: Position,
+    },
+}
+
+#[derive(Resource, Clone)]
+pub struct GestureSettings {
+    /// How far in screen pixels the mouse has to move
+    /// while pressed to start a drag
+    pub drag_threshold: f32,
+    /// Most seconds between two clicks for them to be a double click
+    pub double_click_time: f64,
+    /// Seconds the button has to be held to be a long press
+    pub long_press_time: f64,
+}
+
+impl Default for GestureSettings {
+    fn default() -> Self {
+        Self {
+            drag_threshold: 4.0,
+            double_click_time: 0.3,
+            long_press_time: 0.6,
+        }
+    }
+}
+
+/// Where the mouse is in both screen and world space
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Cursor {
+    pub screen: Position,
+    pub world: Position,
+}
+
+struct PressState {
+    pressed_at: f64,
+    start: Cursor,
+    last_world: Position,
+    dragging: bool,
+    long_pressed: bool,
+}
+
+/// Tracks a single mouse button and turns its
+/// presses and releases into gestures
+#[derive(Default)]
+pub struct GestureRecognizer {
+    press: Option<PressState>,
+    last_click: Option<(f64, Cursor)>,
+}
+
+impl GestureRecognizer {
+    /// Advances the recognizer by a frame and appends
+    /// any gestures that were recognized onto out
+    pub fn update(
+        &mut self,
+        button: MouseButton,
+        input: &ButtonInput<MouseButton>,
+        time: f64,
+        cursor: Cursor,
+        settings: &GestureSettings,
+        out: &mut Vec<Gesture>,
+    ) {
+        let position = cursor.world;
+
+        if input.just_pressed(button) {
+            self.press = Some(PressState {
+                pressed_at: time,
+                start: cursor,
+                last_world: position,
+                dragging: false,
+                long_pressed: false,
+            });
+        }
+
+        let Some(press) = self.press.as_mut() else {
+            return;
+        };
+
+        if !press.dragging && press.start.screen.distance(&cursor.screen) > settings.drag_threshold
+        {
+            press.dragging = true;
+            out.push(Gesture::DragStart {
+                button,
+                start: press.start.world,
+                position,
+            });
+        } else if press.dragging && press.last_world != position {
+            out.push(Gesture::Drag {
+                button,
+                delta: position - press.last_world,
+                position,
+            });
+        } else if !press.dragging
+            && !press.long_pressed
+            && time - press.pressed_at >= settings.long_press_time
+        {
+            press.long_pressed = true;
+            out.push(Gesture::LongPress { button, position });
+        }
+        press.last_world = position;
+
+        if input.just_released(button) || !input.pressed(button) {
+            let press = self.press.take().unwrap();
+
+            if press.dragging {
+                out.push(Gesture::DragEnd { button, position });
+            } else if !press.long_pressed {
+                out.push(Gesture::Click { button, position });
+
+                let double_click = self.last_click.take().is_some_and(|(clicked_at, last)| {
+                    time - clicked_at <= settings.double_click_time
+                        && last.screen.distance(&cursor.screen) <= settings.drag_threshold
+                });
+                if double_click {
+                    out.push(Gesture::DoubleClick { button, position });
+                } else {
+                    self.last_click = Some((time, cursor));
+                }
+            }
+        }
+    }
+}
+
+const BUTTONS: [MouseButton; 3] = [MouseButton::Left, MouseButton::Right, MouseButton::Middle];
+
+#[allow(clippy::too_many_arguments)]
+fn recognize_gestures_system(
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    time: Res<Time>,
+    settings: Res<GestureSettings>,
+    camera: Camera,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut recognizers: Local<HashMap<MouseButton, GestureRecognizer>>,
+    mut last_cursor: Local<Cursor>,
+    mut gestures: Local<Vec<Gesture>>,
+    mut gesture_events: EventWriter<Gesture>,
+) {
+    // Keep using the last known position while the cursor is outside of the window
+    if let Some(cursor_position) = window_query
+        .get_single()
+        .ok()
+        .and_then(Window::cursor_position)
+    {
+        last_cursor.screen = Position::from([cursor_position.x, cursor_position.y]);
+    }
+    // The camera can move without the cursor moving
+    last_cursor.world = camera.screen_to_world(last_cursor.screen);
+
+    for button in BUTTONS {
+        recognizers.entry(button).or_default().update(
+            button,
+            &mouse_buttons,
+            time.elapsed,
+            *last_cursor,
+            &settings,
+            &mut gestures,
+        );
+    }
+
+    gesture_events.send_batch(gestures.drain(..));
+}
+
+pub struct GesturePlugin;
+
+impl Plugin for GesturePlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<GestureSettings>();
+        app.add_event::<Gesture>();
+        app.add_systems(PreUpdate, recognize_gestures_system.after(InputSystem));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Harness {
+        recognizer: GestureRecognizer,
+        input: ButtonInput<MouseButton>,
+        settings: GestureSettings,
+        time: f64,
+        gestures: Vec<Gesture>,
+    }
+
+    impl Harness {
+        fn new() -> Self {
+            Self {
+                recognizer: GestureRecognizer::default(),
+                input: ButtonInput::default(),
+                settings: GestureSettings::default(),
+                time: 0.0,
+                gestures: Vec::new(),
+            }
+        }
+
+        /// Runs a frame that is delta seconds after the last one
+        /// with the mouse at x, y
+        fn frame(&mut self, delta: f64, x: f32, y: f32) -> Vec<Gesture> {
+            self.time += delta;
+            let position = Position::new(x, y);
+            let cursor = Cursor {
+                screen: position,
+                world: position,
+            };
+            self.recognizer.update(
+                MouseButton::Left,
+                &self.input,
+                self.time,
+                cursor,
+                &self.settings,
+                &mut self.gestures,
+            );
+            self.input.clear();
+            self.gestures.drain(..).collect()
+        }
+
+        fn press(&mut self) {
+            self.input.press(MouseButton::Left);
+        }
+
+        fn release(&mut self) {
+            self.input.release(MouseButton::Left);
+        }
+    }
+
+    #[test]
+    fn click() {
+        let mut harness = Harness::new();
+        harness.press();
+        assert!(harness.frame(0.0, 10.0, 10.0).is_empty());
+
+        // Small movements don't start a drag
+        assert!(harness.frame(0.05, 12.0, 11.0).is_empty());
+
+        harness.release();
+        let position = Position::new(12.0, 11.0);
+        assert_eq!(
+            harness.frame(0.05, 12.0, 11.0),
+            vec![Gesture::Click {
+                button: MouseButton::Left,
+                position
+            }]
+        );
+        assert!(harness.frame(0.05, 12.0, 11.0).is_empty());
+    }
+
+    #[test]
+    fn double_click() {
+        let mut harness = Harness::new();
+        harness.press();
+        harness.frame(0.0, 10.0, 10.0);
+        harness.release();
+        harness.frame(0.05, 10.0, 10.0);
+
+        harness.press();
+        harness.frame(0.1, 10.0, 10.0);
+        harness.release();
+        let gestures = harness.frame(0.05, 10.0, 10.0);
+        assert!(matches!(
+            gestures[..],
+            [Gesture::Click { .. }, Gesture::DoubleClick { .. }]
+        ));
+
+        // A third click starts over rather than being another double click
+        harness.press();
+        harness.frame(0.05, 10.0, 10.0);
+        harness.release();
+        let gestures = harness.frame(0.05, 10.0, 10.0);
+        assert!(matches!(gestures[..], [Gesture::Click { .. }]));
+    }
+
+    #[test]
+    fn slow_clicks_are_not_double_clicks() {
+        let mut harness = Harness::new();
+        harness.press();
+        harness.frame(0.0, 10.0, 10.0);
+        harness.release();
+        harness.frame(0.05, 10.0, 10.0);
+
+        harness.press();
+        harness.frame(1.0, 10.0, 10.0);
+        harness.release();
+        let gestures = harness.frame(0.05, 10.0, 10.0);
+        assert!(matches!(gestures[..], [Gesture::Click { .. }]));
+    }
+
+    #[test]
+    fn drag() {
+        let mut harness = Harness::new();
+        harness.press();
+        harness.frame(0.0, 10.0, 10.0);
+
+        let gestures = harness.frame(0.05, 20.0, 10.0);
+        assert_eq!(
+            gestures,
+            vec![Gesture::DragStart {
+                button: MouseButton::Left,
+                start: Position::new(10.0, 10.0),
+                position: Position::new(20.0, 10.0),
+            }]
+        );
+
+        let gestures = harness.frame(0.05, 25.0, 12.0);
+        assert_eq!(
+            gestures,
+            vec![Gesture::Drag {
+                button: MouseButton::Left,
+                delta: Position::new(5.0, 2.0),
+                position: Position::new(25.0, 12.0),
+            }]
+        );
+
+        // Holding still during a drag doesn't send anything
+        assert!(harness.frame(1.0, 25.0, 12.0).is_empty());
+
+        harness.release();
+        let gestures = harness.frame(0.05, 25.0, 12.0);
+        assert!(matches!(gestures[..], [Gesture::DragEnd { .. }]));
+    }
+
+    #[test]
+    fn long_press() {
+        let mut harness = Harness::new();
+        harness.press();
+        harness.frame(0.0, 10.0, 10.0);
+        assert!(harness.frame(0.3, 10.0, 10.0).is_empty());
+
+        let gestures = harness.frame(0.4, 10.0, 10.0);
+        assert!(matches!(gestures[..], [Gesture::LongPress { .. }]));
+        assert!(harness.frame(0.4, 10.0, 10.0).is_empty());
+
+        // Releasing a long press isn't a click
+        harness.release();
+        assert!(harness.frame(0.05, 10.0, 10.0).is_empty());
+    }
+}
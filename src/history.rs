@@ -0,0 +1,393 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    app::{Plugin, Update},
+    ecs::{
+        entity::Entity,
+        event::EventReader,
+        query::{Changed, With},
+        removal_detection::RemovedComponents,
+        schedule::IntoSystemConfigs,
+        system::{Query, ResMut, Resource, SystemState},
+        world::{Mut, World},
+    },
+    utils::{HashMap, HashSet},
+};
+
+use crate::{
+    input_map::{Action, ActionInput},
+    position::Position,
+    selection::{DragEnded, Draggable, SelectionSystems, TransformSelection},
+};
+
+/// Old entities mapped to the entities that replaced them
+pub type EntityRemap = HashMap<Entity, Entity>;
+
+/// A change that can be undone and redone. Every operation that should
+/// be undoable implements this and is recorded in the [History] once
+/// it has been applied.
+///
+/// Edits act on the world directly so that they can spawn and despawn
+/// entities. Entities that are spawned again get new ids, so an edit
+/// that does this adds the old and new ids to the remap, and every
+/// other edit in the history then has its ids swapped over
+pub trait Edit: Send + Sync + 'static {
+    fn undo(&mut self, world: &mut World, remap: &mut EntityRemap);
+    fn redo(&mut self, world: &mut World, remap: &mut EntityRemap);
+    /// Swaps any stored entities that are in remap for their replacements
+    fn remap_entities(&mut self, remap: &EntityRemap);
+}
+
+/// The opposite of another edit, so undoing it redoes the inner edit.
+/// Lets edits like pasting reuse the edit for deleting
+pub struct Inverse<E>(pub E);
+
+impl<E: Edit> Edit for Inverse<E> {
+    fn undo(&mut self, world: &mut World, remap: &mut EntityRemap) {
+        self.0.redo(world, remap);
+    }
+
+    fn redo(&mut self, world: &mut World, remap: &mut EntityRemap) {
+        self.0.undo(world, remap);
+    }
+
+    fn remap_entities(&mut self, remap: &EntityRemap) {
+        self.0.remap_entities(remap);
+    }
+}
+
+/// Edits that can be undone and redone, oldest first
+#[derive(Resource)]
+pub struct History {
+    undo: VecDeque<Box<dyn Edit>>,
+    redo: Vec<Box<dyn Edit>>,
+    /// The most edits that are kept, the oldest is dropped to make room
+    limit: usize,
+    /// Goes up every time an edit is made, undone or redone
+    revision: u64,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::with_limit(100)
+    }
+}
+
+impl History {
+    pub fn with_limit(limit: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            limit,
+            revision: 0,
+        }
+    }
+
+    /// Adds an edit that has already been applied, which
+    /// means anything that was undone can't be redone
+    pub fn record(&mut self, edit: impl Edit) {
+        self.revision += 1;
+        self.redo.clear();
+        self.undo.push_back(Box::new(edit));
+        while self.undo.len() > self.limit {
+            self.undo.pop_front();
+        }
+    }
+
+    /// Forgets every edit, for when the entities
+    /// they refer to are replaced wholesale
+    pub fn clear(&mut self) {
+        self.revision += 1;
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    /// Tells whether anything has changed since an earlier revision
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    #[allow(dead_code)]
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    #[allow(dead_code)]
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn undo(&mut self, world: &mut World) {
+        let Some(mut edit) = self.undo.pop_back() else {
+            return;
+        };
+
+        self.revision += 1;
+        let mut remap = EntityRemap::default();
+        edit.undo(world, &mut remap);
+        self.redo.push(edit);
+        self.remap_entities(&remap);
+    }
+
+    pub fn redo(&mut self, world: &mut World) {
+        let Some(mut edit) = self.redo.pop() else {
+            return;
+        };
+
+        self.revision += 1;
+        let mut remap = EntityRemap::default();
+        edit.redo(world, &mut remap);
+        self.undo.push_back(edit);
+        self.remap_entities(&remap);
+    }
+
+    fn remap_entities(&mut self, remap: &EntityRemap) {
+        if remap.is_empty() {
+            return;
+        }
+
+        for edit in self.undo.iter_mut().chain(self.redo.iter_mut()) {
+            edit.remap_entities(remap);
+        }
+    }
+}
+
+/// Entities that were moved along with where they were moved from and to
+pub struct MovePoints(pub Vec<(Entity, Position, Position)>);
+
+impl MovePoints {
+    fn apply(&self, world: &mut World, to_before: bool) {
+        for (entity, before, after) in self.0.iter() {
+            let position = if to_before { *before } else { *after };
+            if let Some(mut current) = world.get_mut::<Position>(*entity) {
+                *current = position;
+            }
+
+            // Moves made by the history shouldn't be recorded again
+            world
+                .resource_mut::<MoveTracker>()
+                .committed
+                .insert(*entity, position);
+        }
+    }
+}
+
+impl Edit for MovePoints {
+    fn undo(&mut self, world: &mut World, _remap: &mut EntityRemap) {
+        self.apply(world, true);
+    }
+
+    fn redo(&mut self, world: &mut World, _remap: &mut EntityRemap) {
+        self.apply(world, false);
+    }
+
+    fn remap_entities(&mut self, remap: &EntityRemap) {
+        for (entity, _, _) in self.0.iter_mut() {
+            if let Some(new_entity) = remap.get(entity) {
+                *entity = *new_entity;
+            }
+        }
+    }
+}
+
+/// Keeps track of where every [Draggable] entity was the last time
+/// moves were recorded so that everything that moved during a drag,
+/// including entities carried along by constraints, becomes one edit
+#[derive(Resource, Default)]
+pub struct MoveTracker {
+    committed: HashMap<Entity, Position>,
+    /// Entities that have moved since moves were last recorded
+    pending: HashSet<Entity>,
+}
+
+#[allow(clippy::type_complexity)]
+fn track_moves_system(
+    mut tracker: ResMut<MoveTracker>,
+    mut history: ResMut<History>,
+    moved_query: Query<(Entity, &Position), (With<Draggable>, Changed<Position>)>,
+    positions_query: Query<&Position>,
+    mut removed_positions: RemovedComponents<Position>,
+    mut drag_ended: EventReader<DragEnded>,
+    mut transformed: EventReader<TransformSelection>,
+) {
+    let tracker = &mut *tracker;
+
+    for entity in removed_positions.read() {
+        tracker.committed.remove(&entity);
+        tracker.pending.remove(&entity);
+    }
+
+    for (entity, position) in moved_query.iter() {
+        // New entities start out where they are spawned
+        if !tracker.committed.contains_key(&entity) {
+            tracker.committed.insert(entity, *position);
+        } else {
+            tracker.pending.insert(entity);
+        }
+    }
+
+    // Every drag and transform of the selection is a single edit
+    let commit = drag_ended.read().count() + transformed.read().count() > 0;
+    if !commit || tracker.pending.is_empty() {
+        return;
+    }
+
+    let mut moves = Vec::new();
+    for entity in tracker.pending.drain() {
+        let Ok(after) = positions_query.get(entity) else {
+            continue;
+        };
+        let before = tracker.committed.insert(entity, *after).unwrap_or(*after);
+        if before != *after {
+            moves.push((entity, before, *after));
+        }
+    }
+
+    if !moves.is_empty() {
+        history.record(MovePoints(moves));
+    }
+}
+
+fn undo_redo_system(world: &mut World, input_state: &mut SystemState<ActionInput>) {
+    let input = input_state.get(world);
+    // Undoing in the middle of a drag would fight with the drag
+    if input.any_button_pressed() {
+        return;
+    }
+
+    let redo = input.just_pressed(Action::Redo);
+    let undo = input.just_pressed(Action::Undo);
+    world.resource_scope(|world, mut history: Mut<History>| {
+        if redo {
+            history.redo(world);
+        } else if undo {
+            history.undo(world);
+        }
+    });
+}
+
+pub struct HistoryPlugin;
+
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<History>();
+        app.init_resource::<MoveTracker>();
+        app.add_systems(
+            Update,
+            (
+                undo_redo_system.before(SelectionSystems),
+                track_moves_system.after(SelectionSystems),
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::{event::Events, schedule::Schedule};
+
+    /// Adds amount to the counter resource
+    struct Add(i32);
+
+    #[derive(Resource, Default)]
+    struct Counter(i32);
+
+    impl Edit for Add {
+        fn undo(&mut self, world: &mut World, _remap: &mut EntityRemap) {
+            world.resource_mut::<Counter>().0 -= self.0;
+        }
+
+        fn redo(&mut self, world: &mut World, _remap: &mut EntityRemap) {
+            world.resource_mut::<Counter>().0 += self.0;
+        }
+
+        fn remap_entities(&mut self, _remap: &EntityRemap) {}
+    }
+
+    fn add(world: &mut World, history: &mut History, amount: i32) {
+        world.resource_mut::<Counter>().0 += amount;
+        history.record(Add(amount));
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut world = World::new();
+        world.init_resource::<Counter>();
+        let mut history = History::with_limit(2);
+
+        add(&mut world, &mut history, 1);
+        add(&mut world, &mut history, 10);
+        add(&mut world, &mut history, 100);
+        assert_eq!(world.resource::<Counter>().0, 111);
+
+        // Only the last two edits are kept
+        history.undo(&mut world);
+        history.undo(&mut world);
+        history.undo(&mut world);
+        assert_eq!(world.resource::<Counter>().0, 1);
+        assert!(!history.can_undo());
+
+        history.redo(&mut world);
+        assert_eq!(world.resource::<Counter>().0, 11);
+
+        // A new edit replaces anything that could be redone
+        add(&mut world, &mut history, 1000);
+        assert!(!history.can_redo());
+        history.redo(&mut world);
+        assert_eq!(world.resource::<Counter>().0, 1011);
+    }
+
+    #[test]
+    fn drags_are_one_edit() {
+        let mut world = World::new();
+        world.init_resource::<History>();
+        world.init_resource::<MoveTracker>();
+        world.init_resource::<Events<DragEnded>>();
+        world.init_resource::<Events<TransformSelection>>();
+
+        let mut schedule = Schedule::default();
+        schedule.add_systems(track_moves_system);
+
+        let point = world.spawn((Position::new(0.0, 0.0), Draggable)).id();
+        let handle = world.spawn((Position::new(5.0, 0.0), Draggable)).id();
+        schedule.run(&mut world);
+
+        // Several frames of a drag that carries the handle along
+        for x in 1..=3 {
+            let x = x as f32;
+            *world.get_mut::<Position>(point).unwrap() = Position::new(x, 0.0);
+            *world.get_mut::<Position>(handle).unwrap() = Position::new(x + 5.0, 0.0);
+            schedule.run(&mut world);
+        }
+        assert!(!world.resource::<History>().can_undo());
+
+        world.send_event(DragEnded {
+            entities: vec![point],
+            offset: Position::new(3.0, 0.0),
+        });
+        schedule.run(&mut world);
+
+        world.resource_scope(|world, mut history: Mut<History>| history.undo(world));
+        assert_eq!(
+            *world.get::<Position>(point).unwrap(),
+            Position::new(0.0, 0.0)
+        );
+        assert_eq!(
+            *world.get::<Position>(handle).unwrap(),
+            Position::new(5.0, 0.0)
+        );
+        assert!(!world.resource::<History>().can_undo());
+
+        // Undoing isn't recorded as another move
+        world.send_event(TransformSelection::Translate(Position::new(0.0, 0.0)));
+        schedule.run(&mut world);
+        assert!(!world.resource::<History>().can_undo());
+
+        world.resource_scope(|world, mut history: Mut<History>| history.redo(world));
+        assert_eq!(
+            *world.get::<Position>(handle).unwrap(),
+            Position::new(8.0, 0.0)
+        );
+    }
+}
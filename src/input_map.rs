@@ -0,0 +1,591 @@
+use std::{fmt, path::Path};
+
+use bevy::{
+    app::Plugin,
+    ecs::system::{Res, Resource, SystemParam},
+    input::{keyboard::KeyCode, mouse::MouseButton, ButtonInput},
+    utils::HashMap,
+};
+use serde::{Deserialize, Serialize};
+
+/// File in the working directory that the input map is loaded from
+const CONFIG_PATH: &str = "input.ron";
+
+/// Something the user can do that can be bound to an input
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum Action {
+    /// Clicking and dragging on the canvas
+    Select,
+    /// Dragging the camera around with any tool
+    Pan,
+    ZoomIn,
+    ZoomOut,
+    Delete,
+    Undo,
+    Redo,
+    /// Saves the document to `scene.ron` in the working directory
+    Save,
+    /// Replaces the document with the one saved in `scene.ron`
+    Open,
+    /// Writes the document as an SVG next to the file it is saved to
+    ExportSvg,
+    /// Adds the curves in the SVG next to the document file to the scene
+    ImportSvg,
+    /// Restores unsaved changes found when the app starts
+    RestoreRecovery,
+    Copy,
+    Cut,
+    Paste,
+    /// Pastes with the center of the copied curves under the mouse
+    PasteAtCursor,
+    /// Copies and pastes the selection in one step
+    /// without changing what is in the clipboard
+    Duplicate,
+    /// Backs out of whatever is in progress
+    Cancel,
+    /// Held while selecting to add to the selection,
+    /// or to remove from it along with [Action::ToggleSelection]
+    AddToSelection,
+    /// Held while selecting to flip whether each entity is selected
+    ToggleSelection,
+    /// Held while dragging to stop the drag from snapping
+    DisableSnapping,
+    /// Held while nudging to move the selection further
+    LargeNudge,
+    /// Held while clicking with the zoom tool to zoom out instead
+    ZoomToolOut,
+    ToggleLasso,
+    NudgeLeft,
+    NudgeRight,
+    NudgeUp,
+    NudgeDown,
+    SelectTool,
+    DirectSelectTool,
+    PenTool,
+    PanTool,
+    ZoomTool,
+    MeasureTool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum Input {
+    Key(KeyCode),
+    Mouse(MouseButton),
+}
+
+/// Modifier keys that have to be held for a [Binding] to be active,
+/// either the left or right key counts
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl Modifiers {
+    pub const NONE: Self = Self {
+        ctrl: false,
+        shift: false,
+        alt: false,
+    };
+
+    pub fn held(keys: &ButtonInput<KeyCode>) -> Self {
+        Self {
+            ctrl: keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]),
+            shift: keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]),
+            alt: keys.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]),
+        }
+    }
+
+    /// Whether every modifier in other is also in self
+    fn contains(self, other: Self) -> bool {
+        (self.ctrl || !other.ctrl) && (self.shift || !other.shift) && (self.alt || !other.alt)
+    }
+}
+
+/// An input along with the modifiers that have to be held with it
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct Binding {
+    pub input: Input,
+    #[serde(default)]
+    pub modifiers: Modifiers,
+}
+
+impl Binding {
+    pub const fn key(key: KeyCode) -> Self {
+        Self {
+            input: Input::Key(key),
+            modifiers: Modifiers::NONE,
+        }
+    }
+
+    pub const fn mouse(button: MouseButton) -> Self {
+        Self {
+            input: Input::Mouse(button),
+            modifiers: Modifiers::NONE,
+        }
+    }
+
+    pub const fn with_ctrl(mut self) -> Self {
+        self.modifiers.ctrl = true;
+        self
+    }
+
+    pub const fn with_shift(mut self) -> Self {
+        self.modifiers.shift = true;
+        self
+    }
+}
+
+/// Written the way shortcuts are shown to the user, like `Ctrl+Shift+Z`
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Modifiers { ctrl, shift, alt } = self.modifiers;
+        for (held, name) in [(ctrl, "Ctrl"), (shift, "Shift"), (alt, "Alt")] {
+            if held {
+                write!(f, "{name}+")?;
+            }
+        }
+
+        match self.input {
+            Input::Key(key) => {
+                let name = format!("{key:?}");
+                let name = name
+                    .strip_prefix("Key")
+                    .or_else(|| name.strip_prefix("Digit"))
+                    .unwrap_or(&name);
+                write!(f, "{name}")
+            }
+            Input::Mouse(button) => write!(f, "{button:?} Click"),
+        }
+    }
+}
+
+/// How the camera scrolls when the mouse is near the edge of the window
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EdgeScroll {
+    /// Distance from the edge in screen pixels that scrolling starts at
+    pub margin: f32,
+    /// Screen pixels per second
+    pub speed: f32,
+}
+
+impl Default for EdgeScroll {
+    fn default() -> Self {
+        Self {
+            margin: 50.0,
+            speed: 150.0,
+        }
+    }
+}
+
+/// Maps every [Action] to the inputs that trigger it. Loaded from
+/// `input.ron` in the working directory when it exists, any action
+/// left out of the file keeps its default bindings
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputMap {
+    pub bindings: HashMap<Action, Vec<Binding>>,
+    pub edge_scroll: EdgeScroll,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        use Action::*;
+
+        let either = |keys: [KeyCode; 2]| keys.map(Binding::key).to_vec();
+        let shift = either([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+        let ctrl = either([KeyCode::ControlLeft, KeyCode::ControlRight]);
+        let alt = either([KeyCode::AltLeft, KeyCode::AltRight]);
+
+        let bindings = [
+            (Select, vec![Binding::mouse(MouseButton::Left)]),
+            (Pan, vec![Binding::mouse(MouseButton::Middle)]),
+            (ZoomIn, vec![Binding::key(KeyCode::Equal).with_ctrl()]),
+            (ZoomOut, vec![Binding::key(KeyCode::Minus).with_ctrl()]),
+            (
+                Delete,
+                vec![
+                    Binding::key(KeyCode::Delete),
+                    Binding::key(KeyCode::Backspace),
+                ],
+            ),
+            (Undo, vec![Binding::key(KeyCode::KeyZ).with_ctrl()]),
+            (
+                Redo,
+                vec![
+                    Binding::key(KeyCode::KeyZ).with_ctrl().with_shift(),
+                    Binding::key(KeyCode::KeyY).with_ctrl(),
+                ],
+            ),
+            (Save, vec![Binding::key(KeyCode::KeyS).with_ctrl()]),
+            (Open, vec![Binding::key(KeyCode::KeyO).with_ctrl()]),
+            (ExportSvg, vec![Binding::key(KeyCode::KeyE).with_ctrl()]),
+            (ImportSvg, vec![Binding::key(KeyCode::KeyI).with_ctrl()]),
+            (
+                RestoreRecovery,
+                vec![Binding::key(KeyCode::KeyR).with_ctrl()],
+            ),
+            (Copy, vec![Binding::key(KeyCode::KeyC).with_ctrl()]),
+            (Cut, vec![Binding::key(KeyCode::KeyX).with_ctrl()]),
+            (Paste, vec![Binding::key(KeyCode::KeyV).with_ctrl()]),
+            (
+                PasteAtCursor,
+                vec![Binding::key(KeyCode::KeyV).with_ctrl().with_shift()],
+            ),
+            (Duplicate, vec![Binding::key(KeyCode::KeyD).with_ctrl()]),
+            (Cancel, vec![Binding::key(KeyCode::Escape)]),
+            (AddToSelection, shift.clone()),
+            (ToggleSelection, ctrl),
+            (DisableSnapping, alt.clone()),
+            (LargeNudge, shift),
+            (ZoomToolOut, alt),
+            (ToggleLasso, vec![Binding::key(KeyCode::KeyL)]),
+            (NudgeLeft, vec![Binding::key(KeyCode::ArrowLeft)]),
+            (NudgeRight, vec![Binding::key(KeyCode::ArrowRight)]),
+            (NudgeUp, vec![Binding::key(KeyCode::ArrowUp)]),
+            (NudgeDown, vec![Binding::key(KeyCode::ArrowDown)]),
+            (SelectTool, vec![Binding::key(KeyCode::KeyV)]),
+            (DirectSelectTool, vec![Binding::key(KeyCode::KeyA)]),
+            (PenTool, vec![Binding::key(KeyCode::KeyP)]),
+            (PanTool, vec![Binding::key(KeyCode::KeyH)]),
+            (ZoomTool, vec![Binding::key(KeyCode::KeyZ)]),
+            (MeasureTool, vec![Binding::key(KeyCode::KeyM)]),
+        ];
+
+        Self {
+            bindings: bindings.into_iter().collect(),
+            edge_scroll: EdgeScroll::default(),
+        }
+    }
+}
+
+impl InputMap {
+    pub fn from_ron(text: &str) -> Result<Self, ron::error::SpannedError> {
+        let mut input_map: InputMap = ron::from_str(text)?;
+
+        // Fill in anything that was left out of the file
+        for (action, bindings) in InputMap::default().bindings {
+            input_map.bindings.entry(action).or_insert(bindings);
+        }
+
+        Ok(input_map)
+    }
+
+    /// Loads the input map from path, falling back on the defaults
+    /// if the file doesn't exist or can't be read
+    pub fn load_or_default(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        let Ok(text) = std::fs::read_to_string(path) else {
+            return Self::default();
+        };
+
+        match Self::from_ron(&text) {
+            Ok(input_map) => input_map,
+            Err(error) => {
+                eprintln!(
+                    "Using default input map, {} is invalid: {error}",
+                    path.display()
+                );
+                Self::default()
+            }
+        }
+    }
+
+    /// Whether binding for action is active for the given state of an
+    /// input. A binding is shadowed by a binding for another action on
+    /// the same input that needs every one of its modifiers and more,
+    /// as long as they are all held, so ctrl + z doesn't also count as
+    /// z while modifiers that aren't part of any binding are ignored,
+    /// which lets shift + click still count as a click
+    fn binding_active(
+        &self,
+        action: Action,
+        binding: &Binding,
+        held: Modifiers,
+        input_active: bool,
+    ) -> bool {
+        if !input_active || !held.contains(binding.modifiers) {
+            return false;
+        }
+
+        !self
+            .bindings
+            .iter()
+            .filter(|(other_action, _)| **other_action != action)
+            .flat_map(|(_, bindings)| bindings)
+            .any(|other| {
+                other.input == binding.input
+                    && held.contains(other.modifiers)
+                    && other.modifiers.contains(binding.modifiers)
+                    && other.modifiers != binding.modifiers
+            })
+    }
+
+    fn check(
+        &self,
+        action: Action,
+        held: Modifiers,
+        mut input_active: impl FnMut(Input) -> bool,
+    ) -> bool {
+        self.bindings.get(&action).is_some_and(|bindings| {
+            bindings.iter().any(|binding| {
+                self.binding_active(action, binding, held, input_active(binding.input))
+            })
+        })
+    }
+
+    /// Every binding for action written out for the user,
+    /// or "nothing" when the action isn't bound
+    pub fn describe(&self, action: Action) -> String {
+        let bindings = self.bindings.get(&action).into_iter().flatten();
+        let names: Vec<_> = bindings.map(Binding::to_string).collect();
+        if names.is_empty() {
+            return "nothing".to_string();
+        }
+        names.join(" or ")
+    }
+
+    /// Whether button is bound to action, ignoring modifiers. Used to match
+    /// up gestures, which already know which button made them, to actions
+    pub fn is_button(&self, action: Action, button: MouseButton) -> bool {
+        self.bindings.get(&action).is_some_and(|bindings| {
+            bindings
+                .iter()
+                .any(|binding| binding.input == Input::Mouse(button))
+        })
+    }
+}
+
+/// Checks the state of [Action]s against the current [InputMap]
+#[derive(SystemParam)]
+pub struct ActionInput<'w> {
+    pub map: Res<'w, InputMap>,
+    pub keys: Res<'w, ButtonInput<KeyCode>>,
+    pub mouse_buttons: Res<'w, ButtonInput<MouseButton>>,
+}
+
+impl ActionInput<'_> {
+    fn check(
+        &self,
+        action: Action,
+        key: fn(&ButtonInput<KeyCode>, KeyCode) -> bool,
+        button: fn(&ButtonInput<MouseButton>, MouseButton) -> bool,
+    ) -> bool {
+        let held = Modifiers::held(&self.keys);
+        self.map.check(action, held, |input| match input {
+            Input::Key(code) => key(&self.keys, code),
+            Input::Mouse(code) => button(&self.mouse_buttons, code),
+        })
+    }
+
+    pub fn pressed(&self, action: Action) -> bool {
+        self.check(action, ButtonInput::pressed, ButtonInput::pressed)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.check(action, ButtonInput::just_pressed, ButtonInput::just_pressed)
+    }
+
+    pub fn just_released(&self, action: Action) -> bool {
+        // Modifiers can be let go of first so they aren't checked on release
+        let bindings = self.map.bindings.get(&action).into_iter().flatten();
+        bindings.into_iter().any(|binding| match binding.input {
+            Input::Key(code) => self.keys.just_released(code),
+            Input::Mouse(code) => self.mouse_buttons.just_released(code),
+        })
+    }
+
+    pub fn is_button(&self, action: Action, button: MouseButton) -> bool {
+        self.map.is_button(action, button)
+    }
+
+    /// Whether any mouse button is held
+    pub fn any_button_pressed(&self) -> bool {
+        self.mouse_buttons.get_pressed().next().is_some()
+    }
+}
+
+pub struct InputMapPlugin;
+
+impl Plugin for InputMapPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.insert_resource(InputMap::load_or_default(CONFIG_PATH));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(pressed: &[KeyCode]) -> ButtonInput<KeyCode> {
+        let mut keys = ButtonInput::default();
+        for key in pressed {
+            keys.press(*key);
+        }
+        keys
+    }
+
+    fn check(input_map: &InputMap, action: Action, pressed: &[KeyCode]) -> bool {
+        let keys = keys(pressed);
+        input_map.check(action, Modifiers::held(&keys), |input| match input {
+            Input::Key(code) => keys.pressed(code),
+            Input::Mouse(_) => false,
+        })
+    }
+
+    #[test]
+    fn chords_shadow_plain_keys() {
+        let input_map = InputMap::default();
+        let (ctrl, shift) = (KeyCode::ControlLeft, KeyCode::ShiftRight);
+
+        assert!(check(&input_map, Action::ZoomTool, &[KeyCode::KeyZ]));
+        assert!(!check(&input_map, Action::Undo, &[KeyCode::KeyZ]));
+
+        assert!(check(&input_map, Action::Undo, &[ctrl, KeyCode::KeyZ]));
+        assert!(!check(&input_map, Action::ZoomTool, &[ctrl, KeyCode::KeyZ]));
+
+        assert!(check(
+            &input_map,
+            Action::Redo,
+            &[ctrl, shift, KeyCode::KeyZ]
+        ));
+        assert!(!check(
+            &input_map,
+            Action::Undo,
+            &[ctrl, shift, KeyCode::KeyZ]
+        ));
+
+        // Nothing else is bound to shift + arrow keys
+        assert!(check(
+            &input_map,
+            Action::NudgeLeft,
+            &[shift, KeyCode::ArrowLeft]
+        ));
+    }
+
+    #[test]
+    fn modifier_actions() {
+        let input_map = InputMap::default();
+        let (ctrl, shift) = (KeyCode::ControlRight, KeyCode::ShiftLeft);
+
+        assert!(check(&input_map, Action::AddToSelection, &[shift]));
+        assert!(check(&input_map, Action::LargeNudge, &[shift]));
+        assert!(!check(&input_map, Action::ToggleSelection, &[shift]));
+        assert!(check(&input_map, Action::ToggleSelection, &[ctrl, shift]));
+        assert!(check(
+            &input_map,
+            Action::DisableSnapping,
+            &[KeyCode::AltLeft]
+        ));
+        assert!(check(&input_map, Action::ZoomToolOut, &[KeyCode::AltRight]));
+    }
+
+    #[test]
+    fn only_supersets_for_other_actions_shadow() {
+        let input_map = InputMap::from_ron(
+            "(
+                bindings: {
+                    Delete: [(input: Key(KeyQ), modifiers: (shift: true))],
+                    Cut: [(input: Key(KeyQ), modifiers: (ctrl: true, alt: true))],
+                    Copy: [(input: Key(KeyW)), (input: Key(KeyW), modifiers: (shift: true))],
+                    Paste: [(input: Key(KeyW), modifiers: (shift: true, alt: true))],
+                },
+            )",
+        )
+        .unwrap();
+        let (ctrl, shift, alt) = (KeyCode::ControlLeft, KeyCode::ShiftLeft, KeyCode::AltLeft);
+
+        // Needing more modifiers isn't enough when they aren't a superset
+        let all = [ctrl, shift, alt, KeyCode::KeyQ];
+        assert!(check(&input_map, Action::Delete, &all));
+        assert!(check(&input_map, Action::Cut, &all));
+
+        // Bindings for the same action never shadow each other
+        assert!(check(&input_map, Action::Copy, &[shift, KeyCode::KeyW]));
+
+        // A superset for another action only shadows when all of it is held
+        assert!(check(&input_map, Action::Copy, &[alt, KeyCode::KeyW]));
+        let held = [shift, alt, KeyCode::KeyW];
+        assert!(!check(&input_map, Action::Copy, &held));
+        assert!(check(&input_map, Action::Paste, &held));
+    }
+
+    #[test]
+    fn describes_bindings() {
+        let input_map = InputMap::default();
+        assert_eq!(input_map.describe(Action::RestoreRecovery), "Ctrl+R");
+        assert_eq!(input_map.describe(Action::Cancel), "Escape");
+        assert_eq!(input_map.describe(Action::Redo), "Ctrl+Shift+Z or Ctrl+Y");
+
+        let input_map = InputMap::from_ron(
+            "(bindings: { Pan: [(input: Mouse(Right), modifiers: (alt: true))], Cut: [] })",
+        )
+        .unwrap();
+        assert_eq!(input_map.describe(Action::Pan), "Alt+Right Click");
+        assert_eq!(input_map.describe(Action::Cut), "nothing");
+    }
+
+    #[test]
+    fn defaults_are_unique() {
+        let input_map = InputMap::default();
+        // Modifiers are only held along with other actions, so
+        // each one can change what a few different actions do
+        let modifiers = [
+            KeyCode::ShiftLeft,
+            KeyCode::ShiftRight,
+            KeyCode::ControlLeft,
+            KeyCode::ControlRight,
+            KeyCode::AltLeft,
+            KeyCode::AltRight,
+        ]
+        .map(Input::Key);
+        let mut bindings: Vec<_> = input_map
+            .bindings
+            .values()
+            .flatten()
+            .filter(|binding| !modifiers.contains(&binding.input))
+            .collect();
+        let count = bindings.len();
+
+        bindings.sort_by_key(|binding| format!("{binding:?}"));
+        bindings.dedup();
+        assert_eq!(bindings.len(), count);
+    }
+
+    #[test]
+    fn load_partial_config() {
+        let input_map = InputMap::from_ron(
+            "(
+                bindings: {
+                    Undo: [(input: Key(KeyU))],
+                    Select: [(input: Mouse(Right), modifiers: (alt: true))],
+                },
+                edge_scroll: (margin: 10.0),
+            )",
+        )
+        .unwrap();
+
+        assert_eq!(
+            input_map.bindings[&Action::Undo],
+            vec![Binding::key(KeyCode::KeyU)]
+        );
+        assert!(input_map.is_button(Action::Select, MouseButton::Right));
+        assert!(!input_map.is_button(Action::Select, MouseButton::Left));
+        assert_eq!(
+            input_map.bindings[&Action::Redo],
+            InputMap::default().bindings[&Action::Redo]
+        );
+        assert_eq!(input_map.edge_scroll.margin, 10.0);
+        assert_eq!(input_map.edge_scroll.speed, 150.0);
+
+        // Everything that can be saved can be loaded again
+        let text = ron::to_string(&InputMap::default()).unwrap();
+        assert_eq!(
+            InputMap::from_ron(&text).unwrap().bindings,
+            InputMap::default().bindings
+        );
+    }
+}
@@ -1,10 +1,14 @@
 mod bezier;
+mod gestures;
 mod hidden;
+mod history;
+mod input_map;
 mod matrix;
 mod my_time;
 mod position;
 mod rendering;
 mod selection;
+mod title;
 
 use bevy::{
     prelude::PluginGroup,
@@ -12,22 +16,41 @@ use bevy::{
     DefaultPlugins,
 };
 use bezier::BezierPlugin;
+use gestures::GesturePlugin;
+use history::HistoryPlugin;
+use input_map::InputMapPlugin;
 use my_time::TimePlugin;
 use rendering::RenderingPlugin;
 use selection::SelectionPlugin;
+use title::TitlePlugin;
 
 fn main() {
+    // Converting scenes doesn't need a window so it runs before the app is created
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("convert") {
+        std::process::exit(bezier::run_convert_command(&args[1..]));
+    }
+
     let mut app = bevy::prelude::App::new();
     app.add_plugins(DefaultPlugins.set(WindowPlugin {
         primary_window: Some(Window {
-            resolution: WindowResolution::new(800.0, 480.0).with_scale_factor_override(1.),
+            resolution: WindowResolution::new(800.0, 480.0),
             resizable: true,
             present_mode: bevy::window::PresentMode::AutoNoVsync,
             ..Default::default()
         }),
         ..Default::default()
     }));
-    app.add_plugins((TimePlugin, SelectionPlugin, RenderingPlugin, BezierPlugin));
+    app.add_plugins((
+        TimePlugin,
+        InputMapPlugin,
+        GesturePlugin,
+        SelectionPlugin,
+        HistoryPlugin,
+        RenderingPlugin,
+        BezierPlugin,
+        TitlePlugin,
+    ));
 
     app.run();
 }
@@ -19,6 +19,10 @@ impl Mat3 {
         Mat3([[1.0, 0.0, x_offset], [0.0, 1.0, y_offset], [0.0, 0.0, 1.0]])
     }
 
+    pub fn scale(scale: f32) -> Mat3 {
+        Mat3([[scale, 0.0, 0.0], [0.0, scale, 0.0], [0.0, 0.0, 1.0]])
+    }
+
     #[allow(dead_code)]
     pub fn multiply(&self, other: &Mat3) -> Mat3 {
         let mut result = [[0.0; 3]; 3];
@@ -8,6 +8,8 @@ use bevy::{
 #[derive(Resource)]
 pub struct Time {
     pub elapsed: f64,
+    /// Seconds between the start of the previous frame and this one
+    pub delta: f64,
     timer: SystemTime,
 }
 
@@ -15,6 +17,7 @@ impl Default for Time {
     fn default() -> Self {
         Self {
             elapsed: 0.0,
+            delta: 0.0,
             timer: SystemTime::now(),
         }
     }
@@ -26,7 +29,9 @@ impl Plugin for TimePlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
         app.init_resource::<Time>();
         app.add_systems(First, |mut system: ResMut<Time>| {
-            system.elapsed = system.timer.elapsed().map(|s| s.as_secs_f64()).unwrap();
+            let elapsed = system.timer.elapsed().map(|s| s.as_secs_f64()).unwrap();
+            system.delta = elapsed - system.elapsed;
+            system.elapsed = elapsed;
         });
     }
 }
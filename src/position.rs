@@ -1,14 +1,16 @@
-use core::ops::{Add, Sub};
+use core::ops::{Add, Neg, Sub};
 use std::ops::Mul;
 
 use bevy::ecs::component::Component;
 use glium::uniforms::AsUniformValue;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Component, Default, Debug)]
+#[derive(Clone, Copy, Component, Default, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct Position([f32; 2]);
 
 impl Position {
-    pub fn new(x: f32, y: f32) -> Self {
+    pub const fn new(x: f32, y: f32) -> Self {
         Self([x, y])
     }
 
@@ -26,6 +28,23 @@ impl Position {
         x.powi(2) + y.powi(2)
     }
 
+    pub fn distance(&self, other: &Position) -> f32 {
+        self.distance_squared(other).sqrt()
+    }
+
+    /// Squared distance to the closest point on the line segment between start and end
+    pub fn distance_squared_to_segment(&self, start: Position, end: Position) -> f32 {
+        let [line_x, line_y] = (end - start).0;
+        let length_squared = line_x.powi(2) + line_y.powi(2);
+        if length_squared <= f32::EPSILON {
+            return self.distance_squared(&start);
+        }
+
+        let [x, y] = (*self - start).0;
+        let t = ((x * line_x + y * line_y) / length_squared).clamp(0.0, 1.0);
+        self.distance_squared(&Position::lerp(start, end, t as f64))
+    }
+
     pub fn lerp(this: Self, other: Self, t: f64) -> Self {
         let x = this.x() as f64 * (1. - t) + other.x() as f64 * t;
         let y = this.y() as f64 * (1. - t) + other.y() as f64 * t;
@@ -66,6 +85,14 @@ impl Sub<Position> for Position {
     }
 }
 
+impl Neg for Position {
+    type Output = Position;
+
+    fn neg(self) -> Self::Output {
+        Position([-self.x(), -self.y()])
+    }
+}
+
 impl Mul<f64> for Position {
     type Output = Position;
 
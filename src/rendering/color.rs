@@ -1,5 +1,6 @@
 use bevy::ecs::component::Component;
 use glium::uniforms::AsUniformValue;
+use serde::{Deserialize, Serialize};
 
 #[derive(Component)]
 pub enum Stroke {
@@ -7,7 +8,8 @@ pub enum Stroke {
     Solid,
 }
 
-#[derive(Clone, Copy, Debug, Component)]
+#[derive(Clone, Copy, Debug, PartialEq, Component, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct Color([f32; 4]);
 
 #[allow(dead_code)]
@@ -25,6 +27,10 @@ impl Color {
     pub fn new_with_alpha(r: f32, g: f32, b: f32, a: f32) -> Self {
         Self([r, g, b, a])
     }
+
+    pub fn rgba(&self) -> [f32; 4] {
+        self.0
+    }
 }
 
 impl Default for Color {
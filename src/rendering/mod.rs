@@ -2,8 +2,13 @@ mod color;
 pub mod point;
 pub mod primitives;
 mod renderer;
+mod tessellation;
 
 pub use color::Color;
 pub use color::Stroke;
+pub use renderer::Camera;
+pub use renderer::CameraMut;
 pub use renderer::CameraPosition;
+pub use renderer::CameraZoom;
 pub use renderer::RenderingPlugin;
+pub use renderer::Size;
@@ -1,4 +1,4 @@
-use super::{renderer::RenderParams, Color, Stroke};
+use super::{renderer::RenderParams, CameraZoom, Color, Size, Stroke};
 use crate::{hidden::Hidden, position::Position, selection};
 use bevy::ecs::{
     component::Component,
@@ -28,7 +28,7 @@ pub struct RenderData {
 
 #[derive(Component)]
 pub struct Point {
-    pub size: f32,
+    pub size: Size,
 }
 
 #[derive(Resource, Default)]
@@ -40,11 +40,13 @@ pub(super) struct PointsData {
 // Mutable reference here especially if multiple systems
 // need to draw points
 #[derive(SystemParam)]
+#[allow(dead_code)]
 pub struct Points<'w> {
     data: ResMut<'w, PointsData>,
 }
 
 impl Points<'_> {
+    #[allow(dead_code)]
     pub fn draw_point(&mut self, position: Position, size: f32, color: Color) {
         self.data.buffer.push(RenderData {
             position,
@@ -189,6 +191,7 @@ impl<'draw> Renderer<'draw> {
 
     pub fn draw_from_world(&self, render_params: &mut RenderParams, world: &mut World) {
         // Draw all points from ecs
+        let zoom = *world.resource::<CameraZoom>();
         let mut query = world.query::<(
             &Position,
             &Point,
@@ -202,7 +205,7 @@ impl<'draw> Renderer<'draw> {
             .map(
                 |(position, Point { size }, hovered, stroke, _hidden)| RenderData {
                     position: *position,
-                    size: *size,
+                    size: size.to_world(&zoom),
                     hovered: hovered.is_some(),
                     color: Color::RED,
                     outline: matches!(stroke, Some(Stroke::Outline)),
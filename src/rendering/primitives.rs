@@ -1,15 +1,18 @@
 use std::sync::atomic::AtomicUsize;
 
-use super::renderer::RenderParams;
+use super::{renderer::RenderParams, tessellation, Color};
 use crate::position::Position;
 use bevy::ecs::{
     component::Component,
     system::{ResMut, Resource, SystemParam},
 };
 use glium::{
-    dynamic_uniform, glutin::surface::WindowSurface, implement_vertex, Display, DrawParameters,
-    Program, Surface, VertexBuffer,
+    draw_parameters::{Stencil, StencilOperation, StencilTest},
+    dynamic_uniform,
+    glutin::surface::WindowSurface,
+    implement_vertex, Display, DrawParameters, Program, Surface, VertexBuffer,
 };
+use serde::{Deserialize, Serialize};
 
 static DATA_ID_COUNTER: AtomicUsize = AtomicUsize::new(0);
 
@@ -24,8 +27,49 @@ impl From<Type> for glium::index::PrimitiveType {
     fn from(value: Type) -> Self {
         match value {
             Type::Point => Self::Points,
-            Type::Line => Self::LinesList,
-            Type::LineStrip => Self::LineStrip,
+            // Lines are tessellated into triangles so that
+            // they can have joins and caps
+            Type::Line | Type::LineStrip => Self::TrianglesList,
+        }
+    }
+}
+
+/// How two connected line segments are joined together
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LineJoin {
+    #[default]
+    Miter,
+    Bevel,
+    Round,
+}
+
+/// How the open ends of a line are drawn
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[allow(dead_code)]
+pub enum LineCap {
+    #[default]
+    Butt,
+    Square,
+    Round,
+}
+
+/// Describes how a line is drawn, opacity is
+/// controlled by the alpha of the color
+#[derive(Clone, Copy, Debug, PartialEq, Component, Serialize, Deserialize)]
+pub struct StrokeStyle {
+    pub color: Color,
+    pub width: f32,
+    pub join: LineJoin,
+    pub cap: LineCap,
+}
+
+impl Default for StrokeStyle {
+    fn default() -> Self {
+        Self {
+            color: Color::RED,
+            width: 2.0,
+            join: LineJoin::default(),
+            cap: LineCap::default(),
         }
     }
 }
@@ -44,13 +88,21 @@ impl From<Position> for Vertex {
     }
 }
 
-const MAX_LINES: usize = 100;
+/// Lines the immediate buffer has room for before it first has to grow
+const INITIAL_LINES: usize = 128;
 
 #[derive(Resource, Default)]
-pub(super) struct LinesData {
+pub struct LinesData {
     lines_data: Vec<Position>,
 }
 
+impl LinesData {
+    #[cfg(test)]
+    pub fn line_count(&self) -> usize {
+        self.lines_data.len() / 2
+    }
+}
+
 #[derive(SystemParam)]
 pub struct Lines<'w> {
     data: ResMut<'w, LinesData>,
@@ -68,6 +120,20 @@ pub(super) struct Renderer {
     // Maybe it could be a good idea to just have the array be a fixed size if this becomes a performance
     // Issue and set it with a constant if we would like to reduce indirection
     buffers: Vec<Option<VertexBuffer<Vertex>>>,
+    /// Reused for the lines drawn each frame, only replaced
+    /// with a larger one when there are more lines than fit
+    immediate: Option<VertexBuffer<Vertex>>,
+}
+
+/// How many vertices the immediate buffer needs room for to fit needed
+/// vertices, or None if one that fits capacity vertices is already enough.
+/// It grows in powers of two so that a slowly growing selection
+/// doesn't make a new buffer every frame
+fn immediate_capacity(capacity: Option<usize>, needed: usize) -> Option<usize> {
+    match capacity {
+        Some(capacity) if capacity >= needed => None,
+        _ => Some(needed.next_power_of_two().max(INITIAL_LINES * 2)),
+    }
 }
 
 impl Renderer {
@@ -87,8 +153,10 @@ impl Renderer {
 
             out vec4 color;
 
+            uniform vec4 line_color;
+
             void main() {
-                color = vec4(1.0, 0.0, 0.0, 1.0);
+                color = line_color;
             }
         "#;
 
@@ -105,19 +173,37 @@ impl Renderer {
         Self {
             // Have a sufficiently large base capacity so we don't have to resize the array as much
             buffers: Vec::with_capacity(64),
+            immediate: None,
             program,
         }
     }
 
     pub fn draw_immediate(&mut self, render_params: &mut RenderParams, data: &mut LinesData) {
-        assert!(data.lines_data.len() < MAX_LINES * 2);
+        if data.lines_data.is_empty() {
+            return;
+        }
 
         let uniforms = dynamic_uniform! {
             world_to_view: render_params.world_to_view,
+            line_color: &Color::RED,
         };
 
         let data: Vec<_> = data.lines_data.drain(..).map(Vertex::from).collect();
-        let buffer = glium::VertexBuffer::new(render_params.display, &data).unwrap();
+        let capacity = self.immediate.as_ref().map(|buffer| buffer.len());
+        if let Some(capacity) = immediate_capacity(capacity, data.len()) {
+            let buffer =
+                glium::VertexBuffer::empty_dynamic(render_params.display, capacity).unwrap();
+            self.immediate = Some(buffer);
+        }
+        // Only the start of the buffer is written to and drawn
+        // so lines left over from earlier frames are ignored
+        let buffer = self
+            .immediate
+            .as_ref()
+            .unwrap()
+            .slice(0..data.len())
+            .unwrap();
+        buffer.write(&data);
 
         let params = DrawParameters {
             line_width: Some(2.0),
@@ -127,7 +213,7 @@ impl Renderer {
         render_params
             .target
             .draw(
-                &buffer,
+                buffer,
                 &glium::index::NoIndices(glium::index::PrimitiveType::LinesList),
                 &self.program,
                 &uniforms,
@@ -139,6 +225,7 @@ impl Renderer {
     pub fn draw(&mut self, render_params: &mut RenderParams, data: &mut Primatives) {
         let uniforms = dynamic_uniform! {
             world_to_view: render_params.world_to_view,
+            line_color: &data.style.color,
         };
 
         let buffer = {
@@ -154,7 +241,14 @@ impl Renderer {
                     self.buffers.resize_with(largest, || None);
                 }
 
-                if let Some(ref mut buffer) = self.buffers[data.id] {
+                // Buffers can only be written to with data of the same length
+                // and tessellated lines change length whenever the line does
+                let same_length = self.buffers[data.id]
+                    .as_ref()
+                    .is_some_and(|buffer| buffer.len() == data.primitive_data.len());
+
+                if same_length {
+                    let buffer = self.buffers[data.id].as_mut().unwrap();
                     buffer.write(&data.primitive_data);
                 } else {
                     let buffer =
@@ -171,9 +265,28 @@ impl Renderer {
             self.buffers[data.id].as_ref().unwrap()
         };
 
+        // The triangles of a stroke overlap at joins, caps and wherever the
+        // line crosses itself, which would blend translucent strokes more
+        // than once there. The stencil lets each pixel be drawn only once
+        let translucent = data.style.color.rgba()[3] < 1.0;
+        let stencil = if translucent {
+            render_params.target.clear_stencil(0);
+            let once = StencilTest::IfEqual { mask: 0xff };
+            Stencil {
+                test_clockwise: once,
+                test_counter_clockwise: once,
+                depth_pass_operation_clockwise: StencilOperation::Increment,
+                depth_pass_operation_counter_clockwise: StencilOperation::Increment,
+                ..Default::default()
+            }
+        } else {
+            Stencil::default()
+        };
+
         let params = DrawParameters {
-            point_size: Some(data.size),
-            line_width: Some(data.size),
+            point_size: Some(data.style.width),
+            blend: glium::Blend::alpha_blending(),
+            stencil,
             ..Default::default()
         };
 
@@ -193,9 +306,10 @@ impl Renderer {
 #[derive(Component)]
 pub struct Primatives {
     id: usize,
-    size: f32,
+    style: StrokeStyle,
     primitive_type: Type,
 
+    positions: Vec<Position>,
     primitive_data: Vec<Vertex>,
     buffer_needs_refresh: bool,
 }
@@ -204,21 +318,65 @@ impl Primatives {
     pub fn new(positions: &[Position], primitive_type: Type, size: f32) -> Self {
         let id = DATA_ID_COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
 
-        let primitive_data = positions.into_iter().map(|&x| Vertex::from(x)).collect();
-
-        Self {
+        let mut primatives = Self {
             id,
-            size,
-            primitive_data,
+            style: StrokeStyle {
+                width: size,
+                ..Default::default()
+            },
+            positions: positions.to_vec(),
+            primitive_data: Vec::new(),
             primitive_type,
             buffer_needs_refresh: true,
-        }
+        };
+        primatives.rebuild();
+        primatives
     }
 
     pub fn set_positions<'a, Iter: IntoIterator<Item = Position>>(&mut self, positions: Iter) {
+        self.positions.clear();
+        self.positions.extend(positions);
+        self.rebuild();
+    }
+
+    pub fn style(&self) -> StrokeStyle {
+        self.style
+    }
+
+    pub fn set_style(&mut self, style: StrokeStyle) {
+        self.style = style;
+        self.rebuild();
+    }
+
+    /// Regenerates the vertex data from the positions and style
+    fn rebuild(&mut self) {
+        let StrokeStyle {
+            width, join, cap, ..
+        } = self.style;
+
+        let vertices = match self.primitive_type {
+            Type::Point => self.positions.clone(),
+            Type::Line => tessellation::stroke_lines(&self.positions, width, cap),
+            Type::LineStrip => tessellation::stroke_line_strip(&self.positions, width, join, cap),
+        };
+
         self.primitive_data.clear();
         self.primitive_data
-            .extend(positions.into_iter().map(|x| Vertex::from(x)));
+            .extend(vertices.into_iter().map(Vertex::from));
         self.buffer_needs_refresh = true;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn immediate_buffer_grows() {
+        assert_eq!(immediate_capacity(None, 0), Some(INITIAL_LINES * 2));
+        assert_eq!(immediate_capacity(Some(256), 200), None);
+        assert_eq!(immediate_capacity(Some(256), 256), None);
+        assert_eq!(immediate_capacity(Some(256), 257), Some(512));
+        assert_eq!(immediate_capacity(Some(256), 100_000), Some(131_072));
+    }
+}
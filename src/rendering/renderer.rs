@@ -2,11 +2,13 @@ use std::{num::NonZeroU32, time::Instant};
 
 use bevy::{
     app::Plugin,
+    ecs::system::SystemParam,
     ecs::{
         event::EventReader,
         system::{Commands, Resource},
         world::World,
     },
+    input::mouse::{MouseScrollUnit, MouseWheel},
     prelude::{FromWorld, IntoSystemConfigs, Local, Query, Res, ResMut, With},
     window::{PrimaryWindow, RequestRedraw, Window, WindowResized},
     winit::WinitWindows,
@@ -21,6 +23,7 @@ use super::{
     point::{self, PointsData},
     primitives::{self, LinesData},
 };
+use crate::input_map::{Action, ActionInput, InputMap};
 use crate::matrix::Mat3;
 use crate::position::Position;
 
@@ -42,9 +45,85 @@ struct WindowSize(pub Position);
 #[derive(Resource)]
 pub struct WorldToView(pub Mat3);
 
+/// World position of the top left corner of the window
 #[derive(Default, Resource)]
 pub struct CameraPosition(pub Position);
 
+/// How many screen pixels one world unit takes up
+#[derive(Resource, Clone, Copy)]
+pub struct CameraZoom(pub f32);
+
+impl Default for CameraZoom {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+/// A length that is either fixed in the world or fixed on the
+/// screen no matter how far the camera is zoomed in. Screen lengths
+/// are in logical pixels so they also stay the same physical size
+/// on screens with a different scale factor
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Size {
+    World(f32),
+    Screen(f32),
+}
+
+impl Size {
+    pub fn to_world(self, zoom: &CameraZoom) -> f32 {
+        match self {
+            Size::World(size) => size,
+            Size::Screen(size) => size / zoom.0,
+        }
+    }
+}
+
+/// Read only access to the camera for converting
+/// between screen and world space
+#[derive(SystemParam)]
+pub struct Camera<'w> {
+    pub position: Res<'w, CameraPosition>,
+    pub zoom: Res<'w, CameraZoom>,
+}
+
+impl Camera<'_> {
+    /// Converts a position in logical pixels from the top left
+    /// of the window into world space
+    pub fn screen_to_world(&self, screen_position: Position) -> Position {
+        screen_position * (1.0 / self.zoom.0) + self.position.0
+    }
+
+    pub fn to_world(&self, size: Size) -> f32 {
+        size.to_world(&self.zoom)
+    }
+}
+
+/// Mutable access to the camera for systems that move it
+#[derive(SystemParam)]
+pub struct CameraMut<'w> {
+    pub position: ResMut<'w, CameraPosition>,
+    pub zoom: ResMut<'w, CameraZoom>,
+}
+
+impl CameraMut<'_> {
+    /// Moves the camera so that the world follows
+    /// the mouse moving by screen_offset
+    pub fn pan(&mut self, screen_offset: Position) {
+        self.position.0 = self.position.0 - screen_offset * (1.0 / self.zoom.0);
+    }
+
+    /// Multiplies the zoom by factor while keeping the point under
+    /// screen_position in the same place on the screen
+    pub fn zoom_about(&mut self, screen_position: Position, factor: f32) {
+        let old_zoom = self.zoom.0;
+        let new_zoom = (old_zoom * factor).clamp(0.1, 10.0);
+        let world_position = screen_position * (1.0 / old_zoom) + self.position.0;
+
+        self.zoom.0 = new_zoom;
+        self.position.0 = world_position - screen_position * (1.0 / new_zoom);
+    }
+}
+
 impl Renderer<'_> {
     pub fn new(display: Display<WindowSurface>) -> Self {
         let primitives_renderer = primitives::Renderer::new(&display);
@@ -88,7 +167,8 @@ fn initialize_renderer(world: &mut World) {
     let event_loop = world.non_send_resource::<winit::event_loop::EventLoop<RequestRedraw>>();
 
     let display_builder = glutin_winit::DisplayBuilder::new();
-    let config_template_builder = glutin::config::ConfigTemplateBuilder::new();
+    // Translucent strokes use the stencil to avoid blending overlaps twice
+    let config_template_builder = glutin::config::ConfigTemplateBuilder::new().with_stencil_size(8);
     let (_, gl_config) = display_builder
         .build(&event_loop, config_template_builder, |mut configs| {
             // Just use the first configuration since we don't have any special preferences here
@@ -137,8 +217,10 @@ fn initialize_renderer(world: &mut World) {
 fn render_system(world: &mut World) {
     let world_to_view = world.resource::<WorldToView>().0.clone();
     let camera_position = world.resource::<CameraPosition>().0.clone();
-    let world_to_view =
-        world_to_view.multiply(&Mat3::translate(-camera_position.x(), -camera_position.y()));
+    let camera_zoom = world.resource::<CameraZoom>().0;
+    let world_to_view = world_to_view
+        .multiply(&Mat3::scale(camera_zoom))
+        .multiply(&Mat3::translate(-camera_position.x(), -camera_position.y()));
     let mut renderer = world.remove_non_send_resource::<Renderer>().unwrap();
     renderer.draw(world, &world_to_view);
     world.insert_non_send_resource(renderer);
@@ -169,10 +251,50 @@ impl FromWorld for UpdateCameraPositionData {
     }
 }
 
+/// Zooms the camera with the mouse wheel while keeping the
+/// point under the mouse in the same place on the screen.
+/// The zoom keys zoom in on the center of the window instead
+fn update_camera_zoom(
+    q_windows: Query<&Window, With<PrimaryWindow>>,
+    input: ActionInput,
+    window_size: Res<WindowSize>,
+    mut camera: CameraMut,
+    mut mouse_wheel: EventReader<MouseWheel>,
+) {
+    let center = window_size.0 * 0.5;
+    if input.just_pressed(Action::ZoomIn) {
+        camera.zoom_about(center, 2.0);
+    }
+    if input.just_pressed(Action::ZoomOut) {
+        camera.zoom_about(center, 0.5);
+    }
+
+    let lines: f32 = mouse_wheel
+        .read()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y,
+            // Roughly how many pixels a line of scrolling is
+            MouseScrollUnit::Pixel => event.y / 20.0,
+        })
+        .sum();
+    if lines == 0.0 {
+        return;
+    }
+
+    let Some(cursor_position) = q_windows.single().cursor_position() else {
+        return;
+    };
+    let cursor_position = Position::from([cursor_position.x, cursor_position.y]);
+
+    camera.zoom_about(cursor_position, 1.1f32.powf(lines));
+}
+
 fn update_camera_position(
     q_windows: Query<&Window, With<PrimaryWindow>>,
     mut camera_position: ResMut<CameraPosition>,
+    camera_zoom: Res<CameraZoom>,
     window_size: Res<WindowSize>,
+    input_map: Res<InputMap>,
     mut data: Local<UpdateCameraPositionData>,
 ) {
     let now = Instant::now();
@@ -183,9 +305,9 @@ fn update_camera_position(
         return;
     };
     let mouse_position = Position::from([new_position.x, new_position.y]);
-    let margin = 50.0;
-    let speed = 150.0;
-    let distance = speed * delta;
+    let margin = input_map.edge_scroll.margin;
+    // Speed is in screen pixels so scrolling feels the same at any zoom
+    let distance = input_map.edge_scroll.speed * delta / camera_zoom.0;
 
     if mouse_position.x() < margin {
         camera_position.0 = camera_position.0 + Position::from([-distance, 0.0]);
@@ -207,9 +329,15 @@ impl Plugin for RenderingPlugin {
         app.init_resource::<PointsData>();
         app.init_resource::<LinesData>();
         app.init_resource::<CameraPosition>();
+        app.init_resource::<CameraZoom>();
         app.add_systems(
             bevy::app::First,
-            (update_window_size, update_camera_position).chain(),
+            (
+                update_window_size,
+                update_camera_zoom,
+                update_camera_position,
+            )
+                .chain(),
         );
         app.add_systems(bevy::app::Last, render_system);
     }
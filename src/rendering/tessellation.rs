@@ -0,0 +1,298 @@
+use std::f32::consts::PI;
+
+use crate::position::Position;
+
+use super::primitives::{LineCap, LineJoin};
+
+/// Miters longer than this many half widths are drawn as bevels
+/// so that sharp corners don't produce long spikes
+const MITER_LIMIT: f32 = 4.0;
+
+/// Angle covered by each triangle of a round join or cap
+const ROUND_STEP: f32 = PI / 8.0;
+
+fn length(vector: Position) -> f32 {
+    vector.distance(&Position::default())
+}
+
+fn normalize(vector: Position) -> Position {
+    vector * (1.0 / length(vector))
+}
+
+/// Left hand perpendicular of a vector
+fn perpendicular(vector: Position) -> Position {
+    Position::new(-vector.y(), vector.x())
+}
+
+fn dot(a: Position, b: Position) -> f32 {
+    a.x() * b.x() + a.y() * b.y()
+}
+
+fn cross(a: Position, b: Position) -> f32 {
+    a.x() * b.y() - a.y() * b.x()
+}
+
+/// Builds triangles for a stroke made of connected line segments
+struct StrokeBuilder {
+    half_width: f32,
+    join: LineJoin,
+    cap: LineCap,
+    triangles: Vec<Position>,
+}
+
+impl StrokeBuilder {
+    fn triangle(&mut self, a: Position, b: Position, c: Position) {
+        self.triangles.extend([a, b, c]);
+    }
+
+    fn segment(&mut self, from: Position, to: Position) {
+        let offset = perpendicular(normalize(to - from)) * self.half_width;
+        self.triangle(from + offset, from - offset, to + offset);
+        self.triangle(from - offset, to - offset, to + offset);
+    }
+
+    /// Fills the arc around center going from the from offset
+    /// to the to offset the short way around
+    fn fan(&mut self, center: Position, from: Position, to: Position) {
+        let angle = cross(from, to).atan2(dot(from, to));
+        let steps = (angle.abs() / ROUND_STEP).ceil().max(1.0) as usize;
+        let mut previous = from;
+
+        for i in 1..=steps {
+            let (sin, cos) = (angle * i as f32 / steps as f32).sin_cos();
+            let next = Position::new(
+                from.x() * cos - from.y() * sin,
+                from.x() * sin + from.y() * cos,
+            );
+            self.triangle(center, center + previous, center + next);
+            previous = next;
+        }
+    }
+
+    /// Fills the gap on the outside of the corner at point
+    /// between a segment going in direction and one going in next_direction
+    fn join(&mut self, point: Position, direction: Position, next_direction: Position) {
+        let turn = cross(direction, next_direction);
+        if turn.abs() < f32::EPSILON {
+            return;
+        }
+
+        // The gap is on the opposite side to the way the path turns
+        let side = if turn > 0.0 { -1.0 } else { 1.0 };
+        let from = perpendicular(direction) * (self.half_width * side);
+        let to = perpendicular(next_direction) * (self.half_width * side);
+
+        match self.join {
+            LineJoin::Bevel => self.triangle(point, point + from, point + to),
+            LineJoin::Round => self.fan(point, from, to),
+            LineJoin::Miter => {
+                let miter_direction = normalize(from + to);
+                let cos_half_angle = dot(miter_direction, from) / self.half_width;
+                let miter_length = self.half_width / cos_half_angle;
+
+                if miter_length > MITER_LIMIT * self.half_width {
+                    self.triangle(point, point + from, point + to);
+                } else {
+                    let miter = point + miter_direction * miter_length;
+                    self.triangle(point, point + from, miter);
+                    self.triangle(point, miter, point + to);
+                }
+            }
+        }
+    }
+
+    /// Caps the end of a line at point where the line
+    /// leaves point in direction
+    fn cap(&mut self, point: Position, direction: Position) {
+        let offset = perpendicular(direction) * self.half_width;
+        let extension = direction * self.half_width;
+
+        match self.cap {
+            LineCap::Butt => {}
+            LineCap::Square => {
+                self.triangle(point + offset, point - offset, point + offset + extension);
+                self.triangle(
+                    point - offset,
+                    point - offset + extension,
+                    point + offset + extension,
+                );
+            }
+            LineCap::Round => {
+                self.fan(point, offset, extension);
+                self.fan(point, extension, -offset);
+            }
+        }
+    }
+
+    fn polyline(&mut self, points: &[Position]) {
+        // Repeated points have no direction so they are skipped
+        let mut points = points.to_vec();
+        points.dedup_by(|a, b| a.distance_squared(b) < f32::EPSILON);
+
+        if points.len() < 2 {
+            return;
+        }
+
+        let directions: Vec<_> = points
+            .windows(2)
+            .map(|pair| normalize(pair[1] - pair[0]))
+            .collect();
+
+        for pair in points.windows(2) {
+            self.segment(pair[0], pair[1]);
+        }
+
+        for (i, pair) in directions.windows(2).enumerate() {
+            self.join(points[i + 1], pair[0], pair[1]);
+        }
+
+        self.cap(points[0], -directions[0]);
+        self.cap(points[points.len() - 1], directions[directions.len() - 1]);
+    }
+}
+
+/// Triangulates a stroke of width along points
+/// returning a triangle list
+pub fn stroke_line_strip(
+    points: &[Position],
+    width: f32,
+    join: LineJoin,
+    cap: LineCap,
+) -> Vec<Position> {
+    let mut builder = StrokeBuilder {
+        half_width: width / 2.0,
+        join,
+        cap,
+        triangles: Vec::new(),
+    };
+    builder.polyline(points);
+    builder.triangles
+}
+
+/// Triangulates a stroke of width for each pair of points
+/// returning a triangle list
+pub fn stroke_lines(points: &[Position], width: f32, cap: LineCap) -> Vec<Position> {
+    let mut builder = StrokeBuilder {
+        half_width: width / 2.0,
+        join: LineJoin::Bevel,
+        cap,
+        triangles: Vec::new(),
+    };
+    for pair in points.chunks_exact(2) {
+        builder.polyline(pair);
+    }
+    builder.triangles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area(triangles: &[Position]) -> f32 {
+        triangles
+            .chunks_exact(3)
+            .map(|triangle| cross(triangle[1] - triangle[0], triangle[2] - triangle[0]).abs() / 2.0)
+            .sum()
+    }
+
+    #[test]
+    fn butt_segment_area() {
+        let triangles = stroke_line_strip(
+            &[Position::new(0.0, 0.0), Position::new(10.0, 0.0)],
+            2.0,
+            LineJoin::Miter,
+            LineCap::Butt,
+        );
+
+        assert_eq!(triangles.len(), 6);
+        assert!((area(&triangles) - 20.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn square_caps_extend_by_half_width() {
+        let triangles = stroke_line_strip(
+            &[Position::new(0.0, 0.0), Position::new(10.0, 0.0)],
+            2.0,
+            LineJoin::Miter,
+            LineCap::Square,
+        );
+
+        assert!((area(&triangles) - 24.0).abs() < 0.001);
+        let min_x = triangles.iter().map(Position::x).fold(f32::MAX, f32::min);
+        let max_x = triangles.iter().map(Position::x).fold(f32::MIN, f32::max);
+        assert!((min_x + 1.0).abs() < 0.001);
+        assert!((max_x - 11.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn round_caps_approximate_circle() {
+        let triangles = stroke_line_strip(
+            &[Position::new(0.0, 0.0), Position::new(10.0, 0.0)],
+            2.0,
+            LineJoin::Miter,
+            LineCap::Round,
+        );
+
+        // Two half circles of radius 1 make one full circle
+        let expected = 20.0 + PI;
+        assert!((area(&triangles) - expected).abs() < 0.1);
+    }
+
+    #[test]
+    fn right_angle_joins() {
+        let points = [
+            Position::new(0.0, 0.0),
+            Position::new(10.0, 0.0),
+            Position::new(10.0, 10.0),
+        ];
+        let segments = 40.0;
+
+        let bevel = stroke_line_strip(&points, 2.0, LineJoin::Bevel, LineCap::Butt);
+        assert!((area(&bevel) - (segments + 0.5)).abs() < 0.001);
+
+        let miter = stroke_line_strip(&points, 2.0, LineJoin::Miter, LineCap::Butt);
+        assert!((area(&miter) - (segments + 1.0)).abs() < 0.001);
+
+        let round = stroke_line_strip(&points, 2.0, LineJoin::Round, LineCap::Butt);
+        assert!((area(&round) - (segments + PI / 4.0)).abs() < 0.05);
+    }
+
+    #[test]
+    fn sharp_miter_falls_back_to_bevel() {
+        let points = [
+            Position::new(0.0, 0.0),
+            Position::new(10.0, 0.0),
+            Position::new(0.0, 0.5),
+        ];
+
+        let bevel = stroke_line_strip(&points, 2.0, LineJoin::Bevel, LineCap::Butt);
+        let miter = stroke_line_strip(&points, 2.0, LineJoin::Miter, LineCap::Butt);
+        assert_eq!(bevel.len(), miter.len());
+    }
+
+    #[test]
+    fn degenerate_lines_are_empty() {
+        let point = Position::new(5.0, 5.0);
+        assert!(stroke_line_strip(&[point], 2.0, LineJoin::Miter, LineCap::Round).is_empty());
+        assert!(
+            stroke_line_strip(&[point, point], 2.0, LineJoin::Miter, LineCap::Round).is_empty()
+        );
+    }
+
+    #[test]
+    fn separate_lines() {
+        let triangles = stroke_lines(
+            &[
+                Position::new(0.0, 0.0),
+                Position::new(10.0, 0.0),
+                Position::new(0.0, 5.0),
+                Position::new(10.0, 5.0),
+            ],
+            2.0,
+            LineCap::Butt,
+        );
+
+        assert_eq!(triangles.len(), 12);
+        assert!((area(&triangles) - 40.0).abs() < 0.001);
+    }
+}
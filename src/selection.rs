@@ -1,32 +1,235 @@
 use bevy::{
     app::{Plugin, Update},
     ecs::{
+        change_detection::{DetectChanges, DetectChangesMut},
         component::Component,
-        entity::Entity,
+        entity::{Entities, Entity},
         event::EventReader,
         query::{With, Without},
-        schedule::IntoSystemConfigs,
-        system::{Commands, Local, ParamSet, Query, Res, ResMut, Resource},
+        schedule::{IntoSystemConfigs, SystemSet},
+        system::{Commands, ParamSet, Query, Res, ResMut, Resource},
     },
-    input::{mouse::MouseButton, ButtonInput},
+    utils::HashSet,
     window::CursorMoved,
 };
 
-use crate::{hidden::Hidden, position::Position, rendering::CameraPosition};
+use crate::{
+    gestures::Gesture,
+    hidden::Hidden,
+    input_map::{Action, ActionInput},
+    position::Position,
+    rendering::{Camera, Size},
+};
+
+mod constraints;
+mod events;
+mod lasso;
+mod marquee;
+mod snapping;
+mod spatial;
+mod tools;
+mod transform;
+
+#[cfg(test)]
+pub(crate) use constraints::resolve_constraints_system;
+use constraints::ConnectionHosts;
+pub use constraints::{Connected, Connection, Constraint};
+use events::SelectionEvents;
+pub use events::{
+    DragEnded, DragMoved, DragStarted, EntitiesDeselected, EntitiesSelected, HoverEntered,
+    HoverExited,
+};
+pub use marquee::cursor_world_position;
+use snapping::SnapGuides;
+pub use snapping::SnapSettings;
+pub use spatial::SpatialIndex;
+pub use tools::Tool;
+pub use transform::{NudgeSettings, TransformSelection};
+
+/// Every system that changes the selection or moves selected
+/// entities, so other plugins can run before or after them
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SelectionSystems;
 
 #[derive(Resource, Default)]
 struct SelectionData {
     held_items: Vec<Entity>,
-    selected_item: Option<Entity>,
+    /// The held entity that was grabbed by the mouse along with
+    /// where it would be if it wasn't being snapped
+    drag_target: Option<(Entity, Position)>,
+    /// World position of the mouse as of the last cursor event
+    mouse_position: Position,
+    /// What was under the mouse when the left button was pressed
+    pressed: Option<PressedItems>,
+    /// How far the held items have been moved since the drag started
+    drag_offset: Position,
+}
+
+struct PressedItems {
+    draggable: Vec<Entity>,
+    drag_target: Option<(Entity, Position)>,
+    selectable: Option<Entity>,
+}
+
+/// How a new group of entities is combined with the current selection
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SelectionMode {
+    /// Only the new entities are selected
+    Replace,
+    /// The new entities are added to the selection
+    Add,
+    /// Selected entities are deselected and unselected entities are selected
+    Toggle,
+    /// The new entities are removed from the selection
+    Remove,
+}
+
+impl SelectionMode {
+    /// Picks the mode based on the held modifiers, [Action::AddToSelection]
+    /// adds, [Action::ToggleSelection] toggles and both together remove
+    pub fn from_modifiers(input: &ActionInput) -> Self {
+        let add = input.pressed(Action::AddToSelection);
+        let toggle = input.pressed(Action::ToggleSelection);
+
+        match (add, toggle) {
+            (false, false) => Self::Replace,
+            (true, false) => Self::Add,
+            (false, true) => Self::Toggle,
+            (true, true) => Self::Remove,
+        }
+    }
+}
+
+/// The shape that is drawn when dragging on empty canvas
+/// to select every [Selectable] inside of it
+#[derive(Resource, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RegionSelectMode {
+    #[default]
+    Marquee,
+    Lasso,
+}
+
+fn toggle_region_select_mode(input: ActionInput, mut mode: ResMut<RegionSelectMode>) {
+    if input.just_pressed(Action::ToggleLasso) {
+        *mode = match *mode {
+            RegionSelectMode::Marquee => RegionSelectMode::Lasso,
+            RegionSelectMode::Lasso => RegionSelectMode::Marquee,
+        };
+    }
+}
+
+/// The set of selected entities. This is the source of truth
+/// for the selection and [Selected] is added and removed to
+/// match it whenever it changes, so code that wants to change
+/// the selection should do so through this resource
+#[derive(Resource, Default)]
+pub struct SelectionSet(HashSet<Entity>);
+
+#[allow(dead_code)]
+impl SelectionSet {
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.0.contains(&entity)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.0.iter().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    pub fn insert(&mut self, entity: Entity) {
+        self.0.insert(entity);
+    }
+
+    pub fn remove(&mut self, entity: Entity) {
+        self.0.remove(&entity);
+    }
+
+    pub fn toggle(&mut self, entity: Entity) {
+        if !self.0.remove(&entity) {
+            self.0.insert(entity);
+        }
+    }
+
+    /// Combines entities with the current selection based on mode
+    pub fn apply(&mut self, mode: SelectionMode, entities: impl IntoIterator<Item = Entity>) {
+        if mode == SelectionMode::Replace {
+            self.clear();
+        }
+
+        for entity in entities {
+            match mode {
+                SelectionMode::Replace | SelectionMode::Add => self.insert(entity),
+                SelectionMode::Toggle => self.toggle(entity),
+                SelectionMode::Remove => self.remove(entity),
+            }
+        }
+    }
 }
 
 /// If this component is added to an entity
 /// If gains the Hovered component when the
 /// mouse is within radius of the entities
 /// [Position]
+///
+/// Only one entity is hovered at a time, entities with a
+/// higher priority win over closer entities with a lower one
 #[derive(Component)]
 pub struct Hoverable {
-    pub radius: f32,
+    pub radius: Size,
+    pub priority: u8,
+}
+
+/// Entities with this and a [Hoverable] are hovered by how close the
+/// mouse is to the line through these positions instead of by their
+/// [Position], which suits long thin things like curves
+#[derive(Component, Default)]
+pub struct HoverShape(pub Vec<Position>);
+
+impl HoverShape {
+    fn distance_squared(&self, position: &Position) -> f32 {
+        match self.0.as_slice() {
+            [] => f32::INFINITY,
+            [point] => point.distance_squared(position),
+            points => points
+                .windows(2)
+                .map(|line| position.distance_squared_to_segment(line[0], line[1]))
+                .fold(f32::INFINITY, f32::min),
+        }
+    }
+}
+
+struct HoverCandidate {
+    entity: Entity,
+    distance_squared: f32,
+    radius: f32,
+    priority: u8,
+}
+
+/// Picks the entity that should be hovered out of every candidate
+/// that is within its radius of the mouse. Entities at the same
+/// priority and distance, like coincident points, go to the lowest
+/// [Entity] so that the same one always wins
+fn resolve_hover(candidates: impl Iterator<Item = HoverCandidate>) -> Option<Entity> {
+    candidates
+        .filter(|candidate| candidate.distance_squared < candidate.radius.powi(2))
+        .max_by(|a, b| {
+            a.priority
+                .cmp(&b.priority)
+                .then(b.distance_squared.total_cmp(&a.distance_squared))
+                .then(b.entity.cmp(&a.entity))
+        })
+        .map(|candidate| candidate.entity)
 }
 
 /// This component is added and removed to
@@ -51,16 +254,79 @@ pub struct Selectable;
 #[component(storage = "SparseSet")]
 pub struct Selected;
 
-/// This entity is connected to another entity
-/// Any hover or drag events are mirrored for
-/// the host and the connected entity
-#[derive(Component)]
-pub struct Connection(pub Vec<Entity>);
+/// Distance in screen pixels around the drag target
+/// that other points are snapped to
+const SNAP_SEARCH_RADIUS: f32 = 1000.0;
+
+/// Works out where the drag target should move to when the mouse
+/// moves by difference, snapping it if snapping is enabled
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn drag_target_position(
+    held: &mut SelectionData,
+    difference: Position,
+    input: &ActionInput,
+    snap_settings: &SnapSettings,
+    camera: &Camera,
+    spatial_index: &SpatialIndex,
+    connection_hosts: &ConnectionHosts,
+    snap_guides: &mut SnapGuides,
+    snap_query: &Query<
+        (Entity, &Position, Option<&Connection>),
+        (With<Draggable>, Without<Hidden>),
+    >,
+) -> Option<(Entity, Position)> {
+    let (entity, unsnapped) = held.drag_target.as_mut()?;
+    *unsnapped = *unsnapped + difference;
+
+    snap_guides.0.clear();
+    if !snapping::snapping_enabled(input) {
+        return Some((*entity, *unsnapped));
+    }
+
+    // Anything being dragged or carried along with the target can't be
+    // snapped to
+    let carried: Vec<_> = snap_query
+        .iter_many(&held.held_items)
+        .filter_map(|(_, _, connection)| connection)
+        .flat_map(|connection| connection.entities())
+        .collect();
+    let moving = |other: &Entity| held.held_items.contains(other) || carried.contains(other);
+
+    // Points further away than this can't be seen to be aligned with
+    let search_radius = camera.to_world(Size::Screen(SNAP_SEARCH_RADIUS));
+    let others: Vec<_> = spatial_index
+        .query_circle(*unsnapped, search_radius)
+        .filter(|(other, _)| !moving(other) && snap_query.contains(*other))
+        .map(|(_, position)| position)
+        .collect();
 
+    // The anchor of a handle is the entity connected to it
+    let anchor = connection_hosts
+        .hosts(*entity)
+        .filter(|other| !moving(other))
+        .find_map(|other| snap_query.get(other).ok())
+        .map(|(_, position, _)| *position);
+
+    let snap_settings = SnapSettings {
+        distance: camera.to_world(Size::Screen(snap_settings.distance)),
+        ..snap_settings.clone()
+    };
+    let snapped = snapping::snap_position(*unsnapped, &others, anchor, &snap_settings);
+    snap_guides.0 = snapped.guides;
+
+    Some((*entity, snapped.position))
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 fn mouse_moved(
     mut commands: Commands,
-    held: Res<SelectionData>,
-    camera_position: Res<CameraPosition>,
+    mut held: ResMut<SelectionData>,
+    camera: Camera,
+    input: ActionInput,
+    snap_settings: Res<SnapSettings>,
+    spatial_index: Res<SpatialIndex>,
+    connection_hosts: Res<ConnectionHosts>,
+    mut snap_guides: ResMut<SnapGuides>,
     mut queries: ParamSet<(
         Query<
             (
@@ -73,9 +339,12 @@ fn mouse_moved(
             Without<Hidden>,
         >,
         Query<&mut Position>,
+        Query<(Entity, &Position, Option<&Connection>), (With<Draggable>, Without<Hidden>)>,
     )>,
+    shape_query: Query<(Entity, &HoverShape, &Hoverable), Without<Hidden>>,
+    hovered_query: Query<(Entity, &Hovered)>,
     mut cursor_evr: EventReader<CursorMoved>,
-    mut mouse_position_previous: Local<Position>,
+    mut events: SelectionEvents,
 ) {
     // Only update when there are new mouse events
     let new_mouse_position = cursor_evr.read().last();
@@ -84,88 +353,294 @@ fn mouse_moved(
     };
     let mouse_screen_position =
         Position::from([mouse_position.position.x, mouse_position.position.y]);
-    let mouse_world_position = mouse_screen_position + camera_position.0;
+    let mouse_world_position = camera.screen_to_world(mouse_screen_position);
     // if we are holding items handle hover logic
     if held.held_items.is_empty() {
         let hover_query = queries.p0();
-        for (entity, position, hoverable, hovered, connection) in hover_query.iter() {
-            let distance_squared = position.distance_squared(&mouse_world_position);
-            let radius_squared = hoverable.radius.powi(2);
+        let search_radius = spatial_index.hover_search_radius(&camera.zoom);
+        let candidates = spatial_index
+            .query_circle(mouse_world_position, search_radius)
+            .filter_map(|(entity, _)| hover_query.get(entity).ok())
+            .map(|(entity, position, hoverable, _, _)| HoverCandidate {
+                entity,
+                distance_squared: position.distance_squared(&mouse_world_position),
+                radius: camera.to_world(hoverable.radius),
+                priority: hoverable.priority,
+            })
+            .chain(
+                spatial_index
+                    .query_shapes(mouse_world_position, search_radius)
+                    .into_iter()
+                    .filter_map(|entity| shape_query.get(entity).ok())
+                    .map(|(entity, shape, hoverable)| HoverCandidate {
+                        entity,
+                        distance_squared: shape.distance_squared(&mouse_world_position),
+                        radius: camera.to_world(hoverable.radius),
+                        priority: hoverable.priority,
+                    }),
+            );
 
-            if distance_squared < radius_squared {
-                commands.entity(entity).insert(Hovered::default());
+        // Only the winning entity is hovered directly and anything
+        // it is connected to is hovered along with it
+        let mut hovered = Vec::new();
+        if let Some(entity) = resolve_hover(candidates) {
+            hovered.push((entity, false));
 
-                if let Some(Connection(other)) = connection {
-                    for other in other {
-                        commands.entity(*other).insert(Hovered { connected: true });
-                    }
-                }
-            } else if let Some(Hovered { connected }) = hovered {
-                // Connected hovers should be taken care of when
-                // the host entity loses hover rather than handling
-                // it on their own
-                if *connected {
-                    continue;
-                }
+            if let Ok((_, _, _, _, Some(connection))) = hover_query.get(entity) {
+                hovered.extend(connection.entities().map(|other| (other, true)));
+            }
+        }
 
-                commands.entity(entity).remove::<Hovered>();
+        for (entity, Hovered { connected }) in hovered_query.iter() {
+            if hovered.contains(&(entity, *connected)) {
+                continue;
+            }
 
-                if let Some(Connection(other)) = connection {
-                    for other in other {
-                        commands.entity(*other).remove::<Hovered>();
-                    }
+            commands.entity(entity).remove::<Hovered>();
+            // Entities that are only changing how they are
+            // hovered are still hovered
+            if !hovered.iter().any(|(other, _)| *other == entity) {
+                events.hover_exited.send(HoverExited(entity));
+            }
+        }
+
+        for (entity, connected) in hovered {
+            let previous = hovered_query.get(entity).ok();
+            if previous.is_some_and(|(_, hovered)| hovered.connected == connected) {
+                continue;
+            }
+
+            // Connected entities aren't owned by this one
+            // so they may have been despawned
+            if let Some(mut entity_commands) = commands.get_entity(entity) {
+                entity_commands.insert(Hovered { connected });
+                if previous.is_none() {
+                    events
+                        .hover_entered
+                        .send(HoverEntered { entity, connected });
                 }
             }
         }
     // otherwise handle moving held items
     } else {
+        let difference = mouse_world_position - held.mouse_position;
+        let target = drag_target_position(
+            &mut held,
+            difference,
+            &input,
+            &snap_settings,
+            &camera,
+            &spatial_index,
+            &connection_hosts,
+            &mut snap_guides,
+            &queries.p2(),
+        );
+
         let mut drag_query = queries.p1();
-        let difference = mouse_world_position - *mouse_position_previous;
-        for entity in &held.held_items {
-            // The saved entity could have been removed
-            // in between selection and mouse_moved
-            // so we can't just unwrap
-            if let Ok(mut position) = drag_query.get_mut(*entity) {
-                *position = *position + difference;
-            }
-        }
+        // Move everything by however far the target has to move
+        // so that the whole drag snaps together
+        let offset = target
+            .and_then(|(entity, position)| {
+                let current = drag_query.get(entity).ok()?;
+                Some(position - *current)
+            })
+            .unwrap_or(difference);
+        transform::translate_entities(&held.held_items, offset, &mut drag_query);
+
+        held.drag_offset = held.drag_offset + offset;
+        events.drag_moved.send(DragMoved {
+            entities: held.held_items.clone(),
+            delta: offset,
+        });
     }
 
-    *mouse_position_previous = mouse_world_position
+    held.mouse_position = mouse_world_position
 }
 
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 fn grab_selection(
-    mut commands: Commands,
-    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    input: ActionInput,
+    tool: Res<Tool>,
     mut selection: ResMut<SelectionData>,
+    mut selection_set: ResMut<SelectionSet>,
+    mut snap_guides: ResMut<SnapGuides>,
+    mut gestures: EventReader<Gesture>,
+    mut events: SelectionEvents,
     mut selection_queries: ParamSet<(
-        Query<Entity, (With<Hovered>, With<Draggable>)>,
+        Query<(Entity, &Position, &Hovered), With<Draggable>>,
         Query<Entity, (With<Hovered>, (With<Selectable>, Without<Hidden>))>,
+        Query<(), With<Draggable>>,
+        Query<&mut Position>,
     )>,
 ) {
-    if mouse_buttons.just_pressed(MouseButton::Left) {
-        selection.held_items.extend(selection_queries.p0().iter());
+    // Remember what was pressed on so that it can be selected if the
+    // press turns into a click or picked up if it turns into a drag
+    if input.just_pressed(Action::Select) {
+        let mouse_position = selection.mouse_position;
+        let hovered_query = selection_queries.p0();
+        // Entities hovered through a connection are
+        // moved by their constraints instead
+        let directly_hovered: Vec<_> = hovered_query
+            .iter()
+            .filter(|(_, _, hovered)| !hovered.connected)
+            .map(|(entity, position, _)| (entity, *position))
+            .collect();
+        let draggable = directly_hovered.iter().map(|(entity, _)| *entity).collect();
+
+        // Snapping is based on whichever held entity is closest to the mouse
+        let drag_target = directly_hovered.into_iter().min_by(|(_, a), (_, b)| {
+            let a = a.distance_squared(&mouse_position);
+            let b = b.distance_squared(&mouse_position);
+            a.total_cmp(&b)
+        });
+
+        selection.pressed = Some(PressedItems {
+            draggable,
+            drag_target,
+            selectable: selection_queries.p1().iter().next(),
+        });
+    }
+
+    for gesture in gestures.read() {
+        let Some(pressed) = selection.pressed.take() else {
+            continue;
+        };
+
+        match *gesture {
+            Gesture::Click { button, .. } if input.is_button(Action::Select, button) => {
+                let mode = SelectionMode::from_modifiers(&input);
+                match pressed.selectable {
+                    Some(entity) => selection_set.apply(mode, [entity]),
+                    None if mode == SelectionMode::Replace && pressed.draggable.is_empty() => {
+                        selection_set.clear();
+                    }
+                    None => {}
+                }
+            }
+            Gesture::DragStart {
+                button,
+                start,
+                position,
+            } if input.is_button(Action::Select, button) => {
+                selection.held_items.extend(&pressed.draggable);
 
-        let old_entity = selection.selected_item;
-        let new_entity = selection_queries.p1().iter().next();
+                // Dragging any selected entity drags every selected
+                // entity unless only the entity itself is being moved
+                if *tool == Tool::Select
+                    && pressed
+                        .selectable
+                        .is_some_and(|entity| selection_set.contains(entity))
+                {
+                    transform::draggable_entities(
+                        selection_set.iter(),
+                        &selection_queries.p2(),
+                        &mut selection.held_items,
+                    );
+                }
 
-        let different_entity = matches!((old_entity, new_entity), (Some(a), Some(b)) if a != b);
-        let not_dragging = selection.held_items.is_empty();
+                // Catch up with the mouse movement it took to start the drag
+                let offset = position - start;
+                transform::translate_entities(
+                    &selection.held_items,
+                    offset,
+                    &mut selection_queries.p3(),
+                );
+                selection.drag_target = pressed
+                    .drag_target
+                    .map(|(entity, unsnapped)| (entity, unsnapped + offset));
+                selection.drag_offset = offset;
 
-        // Right now selection behavior only depends on just pressed,
-        // maybe this should be extended to handle the whole click instead
-        if let Some(entity) = old_entity {
-            if different_entity || not_dragging {
-                commands.entity(entity).remove::<Selected>();
+                if !selection.held_items.is_empty() {
+                    events.drag_started.send(DragStarted {
+                        entities: selection.held_items.clone(),
+                    });
+                    events.drag_moved.send(DragMoved {
+                        entities: selection.held_items.clone(),
+                        delta: offset,
+                    });
+                }
             }
+            _ => {}
         }
 
-        if let Some(entity) = new_entity {
-            commands.entity(entity).insert(Selected);
-            selection.selected_item = new_entity;
+        selection.pressed = Some(pressed);
+    }
+
+    if input.just_released(Action::Select) {
+        if !selection.held_items.is_empty() {
+            events.drag_ended.send(DragEnded {
+                entities: selection.held_items.clone(),
+                offset: selection.drag_offset,
+            });
         }
-    } else if mouse_buttons.just_released(MouseButton::Left) {
+
+        selection.pressed = None;
         selection.held_items.clear();
+        selection.drag_target = None;
+        snap_guides.0.clear();
+    }
+}
+
+/// Deleting is left to whatever owns the selected entities so that
+/// it can record how to undo it, like curves deleting themselves when
+/// one of their points is selected. This only forgets the selected
+/// entities that are gone afterwards, anything else stays selected
+fn delete_selection_system(
+    input: ActionInput,
+    entities: &Entities,
+    mut selection_set: ResMut<SelectionSet>,
+) {
+    if !input.just_pressed(Action::Delete) || selection_set.is_empty() {
+        return;
+    }
+
+    let deleted: Vec<_> = selection_set
+        .iter()
+        .filter(|entity| !entities.contains(*entity))
+        .collect();
+    for entity in deleted {
+        selection_set.remove(entity);
+    }
+}
+
+/// Adds and removes [Selected] so that it matches the [SelectionSet]
+fn sync_selected_system(
+    mut commands: Commands,
+    mut selection_set: ResMut<SelectionSet>,
+    selected_query: Query<Entity, With<Selected>>,
+    mut events: SelectionEvents,
+) {
+    if !selection_set.is_changed() {
+        return;
+    }
+
+    let mut deselected = Vec::new();
+    for entity in selected_query.iter() {
+        if !selection_set.contains(entity) {
+            commands.entity(entity).remove::<Selected>();
+            deselected.push(entity);
+        }
+    }
+
+    // Selected entities could have been despawned since they were selected
+    selection_set
+        .bypass_change_detection()
+        .0
+        .retain(|entity| commands.get_entity(*entity).is_some());
+
+    let mut selected = Vec::new();
+    for entity in selection_set.iter() {
+        if !selected_query.contains(entity) {
+            commands.entity(entity).insert(Selected);
+            selected.push(entity);
+        }
+    }
+
+    if !deselected.is_empty() {
+        events.deselected.send(EntitiesDeselected(deselected));
+    }
+    if !selected.is_empty() {
+        events.selected.send(EntitiesSelected(selected));
     }
 }
 
@@ -174,6 +649,229 @@ pub struct SelectionPlugin;
 impl Plugin for SelectionPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
         app.init_resource::<SelectionData>();
-        app.add_systems(Update, (mouse_moved, grab_selection).chain());
+        app.init_resource::<SelectionSet>();
+        app.init_resource::<RegionSelectMode>();
+        app.init_resource::<NudgeSettings>();
+        app.init_resource::<SnapSettings>();
+        app.init_resource::<SnapGuides>();
+        app.init_resource::<SpatialIndex>();
+        app.init_resource::<ConnectionHosts>();
+        app.init_resource::<Tool>();
+        app.init_resource::<tools::Measurement>();
+        app.add_event::<TransformSelection>();
+        app.add_event::<EntitiesSelected>();
+        app.add_event::<EntitiesDeselected>();
+        app.add_event::<HoverEntered>();
+        app.add_event::<HoverExited>();
+        app.add_event::<DragStarted>();
+        app.add_event::<DragMoved>();
+        app.add_event::<DragEnded>();
+        app.add_systems(
+            Update,
+            (
+                spatial::update_spatial_index_system,
+                constraints::update_connection_hosts_system,
+                tools::switch_tool_system,
+                mouse_moved.run_if(tools::selecting),
+                grab_selection.run_if(tools::selecting),
+                toggle_region_select_mode.run_if(tools::selecting),
+                marquee::marquee_selection_system.run_if(tools::selecting),
+                lasso::lasso_selection_system.run_if(tools::selecting),
+                delete_selection_system,
+                sync_selected_system,
+                transform::nudge_selection_system,
+                transform::transform_selection_system,
+                constraints::resolve_constraints_system,
+                snapping::draw_snap_guides_system,
+            )
+                .chain()
+                .in_set(SelectionSystems),
+        );
+        app.add_systems(
+            Update,
+            (
+                tools::tool_cursor_system,
+                tools::pan_tool_system,
+                tools::zoom_tool_system,
+                (tools::measure_tool_system, tools::measurement_title_system).chain(),
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entities() -> [Entity; 3] {
+        [
+            Entity::from_raw(0),
+            Entity::from_raw(1),
+            Entity::from_raw(2),
+        ]
+    }
+
+    #[test]
+    fn hover_prefers_priority_then_distance() {
+        let [a, b, c] = entities();
+        let candidate = |entity, distance: f32, priority| HoverCandidate {
+            entity,
+            distance_squared: distance.powi(2),
+            radius: 10.0,
+            priority,
+        };
+
+        let nearest = resolve_hover([candidate(a, 5.0, 0), candidate(b, 2.0, 0)].into_iter());
+        assert_eq!(nearest, Some(b));
+
+        let prioritized = resolve_hover(
+            [
+                candidate(a, 5.0, 1),
+                candidate(b, 2.0, 0),
+                candidate(c, 8.0, 1),
+            ]
+            .into_iter(),
+        );
+        assert_eq!(prioritized, Some(a));
+
+        // Out of range candidates never win no matter their priority
+        let out_of_range = resolve_hover([candidate(a, 20.0, 5), candidate(b, 9.0, 0)].into_iter());
+        assert_eq!(out_of_range, Some(b));
+
+        assert_eq!(resolve_hover([candidate(c, 11.0, 0)].into_iter()), None);
+
+        // Coincident candidates always resolve to the same entity
+        let tied = resolve_hover([candidate(c, 3.0, 1), candidate(b, 3.0, 1)].into_iter());
+        assert_eq!(tied, Some(b));
+        let tied = resolve_hover([candidate(b, 3.0, 1), candidate(c, 3.0, 1)].into_iter());
+        assert_eq!(tied, Some(b));
+    }
+
+    #[test]
+    fn hover_shape_distance() {
+        let shape = HoverShape(vec![
+            Position::new(0.0, 0.0),
+            Position::new(10.0, 0.0),
+            Position::new(10.0, 10.0),
+        ]);
+
+        assert_eq!(shape.distance_squared(&Position::new(5.0, 2.0)), 4.0);
+        assert_eq!(shape.distance_squared(&Position::new(13.0, 5.0)), 9.0);
+        // Past the end of the shape is measured to its end point
+        assert_eq!(shape.distance_squared(&Position::new(-3.0, 4.0)), 25.0);
+        assert_eq!(
+            HoverShape::default().distance_squared(&Position::new(0.0, 0.0)),
+            f32::INFINITY
+        );
+    }
+
+    #[test]
+    fn delete_only_forgets_deleted_entities() {
+        use crate::input_map::InputMap;
+        use bevy::{
+            ecs::{system::RunSystemOnce, world::World},
+            input::{keyboard::KeyCode, mouse::MouseButton, ButtonInput},
+        };
+
+        let mut world = World::new();
+        world.init_resource::<InputMap>();
+        world.init_resource::<ButtonInput<KeyCode>>();
+        world.init_resource::<ButtonInput<MouseButton>>();
+        let kept = world.spawn_empty().id();
+        let deleted = world.spawn_empty().id();
+        world.despawn(deleted);
+        let mut selection_set = SelectionSet::default();
+        selection_set.apply(SelectionMode::Add, [kept, deleted]);
+        world.insert_resource(selection_set);
+
+        world
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(KeyCode::Delete);
+        world.run_system_once(delete_selection_system);
+
+        // Entities nothing knows how to bring back aren't despawned
+        assert!(world.get_entity(kept).is_some());
+        let selection_set = world.resource::<SelectionSet>();
+        assert!(selection_set.contains(kept));
+        assert_eq!(selection_set.len(), 1);
+    }
+
+    #[test]
+    fn sync_sends_selection_events() {
+        use bevy::ecs::{
+            event::{Events, ManualEventReader},
+            schedule::Schedule,
+            world::World,
+        };
+
+        let mut world = World::new();
+        world.init_resource::<SelectionSet>();
+        world.init_resource::<Events<EntitiesSelected>>();
+        world.init_resource::<Events<EntitiesDeselected>>();
+        world.init_resource::<Events<HoverEntered>>();
+        world.init_resource::<Events<HoverExited>>();
+        world.init_resource::<Events<DragStarted>>();
+        world.init_resource::<Events<DragMoved>>();
+        world.init_resource::<Events<DragEnded>>();
+
+        let mut schedule = Schedule::default();
+        schedule.add_systems(sync_selected_system);
+        let mut selected_reader = ManualEventReader::<EntitiesSelected>::default();
+        let mut deselected_reader = ManualEventReader::<EntitiesDeselected>::default();
+
+        let a = world.spawn_empty().id();
+        let b = world.spawn_empty().id();
+
+        world
+            .resource_mut::<SelectionSet>()
+            .apply(SelectionMode::Replace, [a, b]);
+        schedule.run(&mut world);
+        assert!(world.get::<Selected>(a).is_some() && world.get::<Selected>(b).is_some());
+
+        let events = world.resource::<Events<EntitiesSelected>>();
+        let mut selected: Vec<_> = selected_reader
+            .read(events)
+            .flat_map(|EntitiesSelected(entities)| entities.clone())
+            .collect();
+        selected.sort();
+        assert_eq!(selected, vec![a, b]);
+
+        world
+            .resource_mut::<SelectionSet>()
+            .apply(SelectionMode::Remove, [a]);
+        schedule.run(&mut world);
+        assert!(world.get::<Selected>(a).is_none() && world.get::<Selected>(b).is_some());
+
+        let events = world.resource::<Events<EntitiesDeselected>>();
+        let deselected: Vec<_> = deselected_reader
+            .read(events)
+            .flat_map(|EntitiesDeselected(entities)| entities.clone())
+            .collect();
+        assert_eq!(deselected, vec![a]);
+
+        let events = world.resource::<Events<EntitiesSelected>>();
+        assert_eq!(selected_reader.read(events).count(), 0);
+    }
+
+    #[test]
+    fn selection_modes() {
+        let [a, b, c] = entities();
+        let mut selection_set = SelectionSet::default();
+
+        selection_set.apply(SelectionMode::Replace, [a, b]);
+        assert!(selection_set.contains(a) && selection_set.contains(b));
+
+        selection_set.apply(SelectionMode::Replace, [c]);
+        assert_eq!(selection_set.iter().collect::<Vec<_>>(), vec![c]);
+
+        selection_set.apply(SelectionMode::Add, [a]);
+        assert_eq!(selection_set.len(), 2);
+
+        selection_set.apply(SelectionMode::Toggle, [a, b]);
+        assert!(!selection_set.contains(a));
+        assert!(selection_set.contains(b) && selection_set.contains(c));
+
+        selection_set.apply(SelectionMode::Remove, [b, c]);
+        assert!(selection_set.is_empty());
     }
 }
@@ -0,0 +1,335 @@
+use bevy::{
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::Changed,
+        removal_detection::RemovedComponents,
+        system::{Local, ParamSet, Query, ResMut, Resource},
+    },
+    utils::{HashMap, HashSet},
+};
+
+use crate::position::Position;
+
+/// How a connected entity follows the entity it is connected to
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Constraint {
+    /// Keeps the same offset from the host so moving the host moves it too
+    Rigid,
+    /// Stays opposite the given entity on the other side of the host,
+    /// like the two handles of a smooth join. Moving the host
+    /// moves it too, so the two stay mirrored
+    Mirror(Entity),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Connected {
+    pub entity: Entity,
+    pub constraint: Constraint,
+}
+
+/// This entity is connected to other entities.
+/// Hovering it hovers the connected entities as well
+/// and moving it moves them based on their [Constraint]
+#[derive(Component)]
+pub struct Connection(pub Vec<Connected>);
+
+impl Connection {
+    /// Connects entities so that they move along with the host
+    pub fn rigid(entities: impl IntoIterator<Item = Entity>) -> Self {
+        Self(
+            entities
+                .into_iter()
+                .map(|entity| Connected {
+                    entity,
+                    constraint: Constraint::Rigid,
+                })
+                .collect(),
+        )
+    }
+
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.0.iter().map(|connected| connected.entity)
+    }
+
+    /// Swaps every reference to old for new
+    pub fn replace(&mut self, old: Entity, new: Entity) {
+        for connected in self.0.iter_mut() {
+            if connected.entity == old {
+                connected.entity = new;
+            }
+            if connected.constraint == Constraint::Mirror(old) {
+                connected.constraint = Constraint::Mirror(new);
+            }
+        }
+    }
+}
+
+/// The hosts of every connected entity, which is the reverse of every
+/// [Connection], so that what an entity is connected to can be found
+/// without looking through every connection
+#[derive(Resource, Default)]
+pub struct ConnectionHosts {
+    hosts: HashMap<Entity, Vec<Entity>>,
+    /// What each host was connected to when it was last seen,
+    /// so those entities can be forgotten when it changes
+    connected: HashMap<Entity, Vec<Entity>>,
+}
+
+impl ConnectionHosts {
+    /// Every entity that has entity in its [Connection]
+    pub fn hosts(&self, entity: Entity) -> impl Iterator<Item = Entity> + '_ {
+        self.hosts.get(&entity).into_iter().flatten().copied()
+    }
+
+    fn remove_host(&mut self, host: Entity) {
+        for entity in self.connected.remove(&host).into_iter().flatten() {
+            if let Some(hosts) = self.hosts.get_mut(&entity) {
+                hosts.retain(|other| *other != host);
+                if hosts.is_empty() {
+                    self.hosts.remove(&entity);
+                }
+            }
+        }
+    }
+
+    fn insert_host(&mut self, host: Entity, connection: &Connection) {
+        self.remove_host(host);
+        let connected: Vec<_> = connection.entities().collect();
+        for entity in connected.iter() {
+            self.hosts.entry(*entity).or_default().push(host);
+        }
+        self.connected.insert(host, connected);
+    }
+}
+
+pub(super) fn update_connection_hosts_system(
+    mut connection_hosts: ResMut<ConnectionHosts>,
+    changed_query: Query<(Entity, &Connection), Changed<Connection>>,
+    mut removed_connections: RemovedComponents<Connection>,
+) {
+    for host in removed_connections.read() {
+        connection_hosts.remove_host(host);
+    }
+    for (host, connection) in changed_query.iter() {
+        connection_hosts.insert_host(host, connection);
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct ConstraintState {
+    /// Where each host was the last time constraints were resolved
+    host_positions: HashMap<Entity, Position>,
+    /// The host and mirroring entity for every entity that is mirrored
+    mirrored_by: HashMap<Entity, Vec<(Entity, Entity)>>,
+    moved: Vec<Entity>,
+    visited: HashSet<Entity>,
+}
+
+/// Moves connected entities to satisfy their [Constraint] after anything
+/// else has moved. Entities that were moved directly this frame keep where
+/// they were put, and every entity is moved at most once so cycles of
+/// connections can't move entities forever
+#[allow(clippy::type_complexity)]
+pub(crate) fn resolve_constraints_system(
+    mut queries: ParamSet<(Query<Entity, Changed<Position>>, Query<&mut Position>)>,
+    connection_query: Query<(Entity, &Connection)>,
+    changed_connections: Query<(), Changed<Connection>>,
+    mut removed_connections: RemovedComponents<Connection>,
+    mut state: Local<ConstraintState>,
+) {
+    let state = &mut *state;
+
+    let mut rebuild = !changed_connections.is_empty();
+    for entity in removed_connections.read() {
+        state.host_positions.remove(&entity);
+        rebuild = true;
+    }
+
+    if rebuild {
+        state.mirrored_by.clear();
+        for (host, connection) in connection_query.iter() {
+            for connected in connection.0.iter() {
+                if let Constraint::Mirror(mirrored) = connected.constraint {
+                    state
+                        .mirrored_by
+                        .entry(mirrored)
+                        .or_default()
+                        .push((host, connected.entity));
+                }
+            }
+        }
+    }
+
+    state.moved.clear();
+    state.moved.extend(queries.p0().iter());
+    state.visited.clear();
+    state.visited.extend(state.moved.iter().copied());
+
+    let mut positions_query = queries.p1();
+    while let Some(entity) = state.moved.pop() {
+        let Ok(position) = positions_query.get(entity).copied() else {
+            continue;
+        };
+
+        if let Ok((_, connection)) = connection_query.get(entity) {
+            // A new host has nothing to move its connections relative to
+            if let Some(previous) = state.host_positions.insert(entity, position) {
+                // Every kind of connection is carried along by its host
+                let offset = position - previous;
+                for Connected { entity: other, .. } in connection.0.iter() {
+                    if !state.visited.insert(*other) {
+                        continue;
+                    }
+
+                    if let Ok(mut other_position) = positions_query.get_mut(*other) {
+                        *other_position = *other_position + offset;
+                        state.moved.push(*other);
+                    }
+                }
+            }
+        }
+
+        let Some(mirrors) = state.mirrored_by.get(&entity) else {
+            continue;
+        };
+        for (host, mirror) in mirrors {
+            let Ok(host_position) = positions_query.get(*host).copied() else {
+                continue;
+            };
+            if !state.visited.insert(*mirror) {
+                continue;
+            }
+
+            if let Ok(mut mirror_position) = positions_query.get_mut(*mirror) {
+                *mirror_position = host_position * 2.0 - position;
+                state.moved.push(*mirror);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::{schedule::Schedule, world::World};
+
+    fn position(world: &World, entity: Entity) -> Position {
+        *world.get::<Position>(entity).unwrap()
+    }
+
+    #[test]
+    fn rigid_connections_follow_host() {
+        let mut world = World::new();
+        let mut schedule = Schedule::default();
+        schedule.add_systems(resolve_constraints_system);
+
+        let handle = world.spawn(Position::new(10.0, 0.0)).id();
+        let host = world
+            .spawn((Position::new(0.0, 0.0), Connection::rigid([handle])))
+            .id();
+        // Connected back to the host to make a cycle
+        let other_host = world.spawn(Position::new(5.0, 5.0)).id();
+        world
+            .entity_mut(host)
+            .get_mut::<Connection>()
+            .unwrap()
+            .0
+            .push(Connected {
+                entity: other_host,
+                constraint: Constraint::Rigid,
+            });
+        world
+            .entity_mut(other_host)
+            .insert(Connection::rigid([host]));
+        schedule.run(&mut world);
+
+        *world.get_mut::<Position>(host).unwrap() = Position::new(1.0, 2.0);
+        schedule.run(&mut world);
+
+        assert_eq!(position(&world, host), Position::new(1.0, 2.0));
+        assert_eq!(position(&world, handle), Position::new(11.0, 2.0));
+        assert_eq!(position(&world, other_host), Position::new(6.0, 7.0));
+
+        // Moving a connected entity on its own leaves the host where it is
+        *world.get_mut::<Position>(handle).unwrap() = Position::new(0.0, 0.0);
+        schedule.run(&mut world);
+        assert_eq!(position(&world, host), Position::new(1.0, 2.0));
+
+        // Moving both together doesn't move the connected entity twice
+        *world.get_mut::<Position>(host).unwrap() = Position::new(2.0, 2.0);
+        *world.get_mut::<Position>(handle).unwrap() = Position::new(1.0, 0.0);
+        schedule.run(&mut world);
+        assert_eq!(position(&world, handle), Position::new(1.0, 0.0));
+        assert_eq!(position(&world, other_host), Position::new(7.0, 7.0));
+    }
+
+    #[test]
+    fn hosts_follow_connections() {
+        let mut world = World::new();
+        world.init_resource::<ConnectionHosts>();
+        let mut schedule = Schedule::default();
+        schedule.add_systems(update_connection_hosts_system);
+        let hosts = |world: &World, entity| {
+            let mut hosts: Vec<_> = world.resource::<ConnectionHosts>().hosts(entity).collect();
+            hosts.sort();
+            hosts
+        };
+
+        let handle = world.spawn_empty().id();
+        let other = world.spawn_empty().id();
+        let first = world.spawn(Connection::rigid([handle])).id();
+        let second = world.spawn(Connection::rigid([handle, other])).id();
+        schedule.run(&mut world);
+        assert_eq!(hosts(&world, handle), vec![first, second]);
+        assert_eq!(hosts(&world, other), vec![second]);
+
+        world.get_mut::<Connection>(second).unwrap().0.pop();
+        schedule.run(&mut world);
+        assert_eq!(hosts(&world, other), vec![]);
+
+        world.despawn(first);
+        schedule.run(&mut world);
+        assert_eq!(hosts(&world, handle), vec![second]);
+    }
+
+    #[test]
+    fn mirrored_handles() {
+        let mut world = World::new();
+        let mut schedule = Schedule::default();
+        schedule.add_systems(resolve_constraints_system);
+
+        let in_handle = world.spawn(Position::new(-10.0, 0.0)).id();
+        let out_handle = world.spawn(Position::new(10.0, 0.0)).id();
+        let host = world
+            .spawn((
+                Position::new(0.0, 0.0),
+                Connection(vec![
+                    Connected {
+                        entity: in_handle,
+                        constraint: Constraint::Mirror(out_handle),
+                    },
+                    Connected {
+                        entity: out_handle,
+                        constraint: Constraint::Mirror(in_handle),
+                    },
+                ]),
+            ))
+            .id();
+        schedule.run(&mut world);
+
+        *world.get_mut::<Position>(out_handle).unwrap() = Position::new(3.0, 4.0);
+        schedule.run(&mut world);
+        assert_eq!(position(&world, in_handle), Position::new(-3.0, -4.0));
+
+        *world.get_mut::<Position>(in_handle).unwrap() = Position::new(0.0, 5.0);
+        schedule.run(&mut world);
+        assert_eq!(position(&world, out_handle), Position::new(0.0, -5.0));
+
+        // Moving the host carries both handles along so they stay mirrored
+        *world.get_mut::<Position>(host).unwrap() = Position::new(1.0, 1.0);
+        schedule.run(&mut world);
+        assert_eq!(position(&world, in_handle), Position::new(1.0, 6.0));
+        assert_eq!(position(&world, out_handle), Position::new(1.0, -4.0));
+    }
+}
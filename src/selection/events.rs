@@ -0,0 +1,66 @@
+use bevy::ecs::{
+    entity::Entity,
+    event::{Event, EventWriter},
+    system::SystemParam,
+};
+
+use crate::position::Position;
+
+/// Entities that were added to the selection
+#[derive(Event, Clone, Debug)]
+pub struct EntitiesSelected(pub Vec<Entity>);
+
+/// Entities that were removed from the selection
+#[derive(Event, Clone, Debug)]
+pub struct EntitiesDeselected(pub Vec<Entity>);
+
+/// An entity started being hovered
+#[derive(Event, Clone, Copy, Debug)]
+#[allow(dead_code)]
+pub struct HoverEntered {
+    pub entity: Entity,
+    /// If the entity is hovered because it is connected
+    /// to the entity under the mouse
+    pub connected: bool,
+}
+
+/// An entity stopped being hovered
+#[derive(Event, Clone, Copy, Debug)]
+#[allow(dead_code)]
+pub struct HoverExited(pub Entity);
+
+/// The mouse started dragging entities
+#[derive(Event, Clone, Debug)]
+#[allow(dead_code)]
+pub struct DragStarted {
+    pub entities: Vec<Entity>,
+}
+
+/// Dragged entities were moved by delta
+#[derive(Event, Clone, Debug)]
+#[allow(dead_code)]
+pub struct DragMoved {
+    pub entities: Vec<Entity>,
+    pub delta: Position,
+}
+
+/// The mouse let go of dragged entities after moving them by offset in total
+#[derive(Event, Clone, Debug)]
+#[allow(dead_code)]
+pub struct DragEnded {
+    pub entities: Vec<Entity>,
+    pub offset: Position,
+}
+
+/// Writers for every selection event so systems
+/// don't need a parameter for each one
+#[derive(SystemParam)]
+pub(super) struct SelectionEvents<'w> {
+    pub selected: EventWriter<'w, EntitiesSelected>,
+    pub deselected: EventWriter<'w, EntitiesDeselected>,
+    pub hover_entered: EventWriter<'w, HoverEntered>,
+    pub hover_exited: EventWriter<'w, HoverExited>,
+    pub drag_started: EventWriter<'w, DragStarted>,
+    pub drag_moved: EventWriter<'w, DragMoved>,
+    pub drag_ended: EventWriter<'w, DragEnded>,
+}
@@ -0,0 +1,174 @@
+use bevy::{
+    ecs::{
+        entity::Entity,
+        query::{With, Without},
+        system::{Commands, Local, Query, Res, ResMut},
+    },
+    window::{PrimaryWindow, Window},
+};
+
+use crate::{
+    hidden::Hidden,
+    input_map::{Action, ActionInput},
+    position::Position,
+    rendering::{primitives, Camera},
+};
+
+use super::{
+    marquee::{cursor_world_position, SelectionRect},
+    Hovered, RegionSelectMode, Selectable, SelectionMode, SelectionSet, SpatialIndex,
+};
+
+/// Points closer together than this are not added to the
+/// lasso so that holding the mouse still doesn't grow it
+const MIN_POINT_DISTANCE: f32 = 3.0;
+
+/// Checks if position is inside of the closed polygon
+/// with the given vertices using the even-odd rule
+pub fn polygon_contains(polygon: &[Position], position: &Position) -> bool {
+    let (x, y) = (position.x(), position.y());
+    let mut inside = false;
+
+    // Each edge the horizontal ray going right from
+    // position crosses flips whether we are inside
+    let previous = polygon.iter().cycle().skip(polygon.len().saturating_sub(1));
+    for (a, b) in polygon.iter().zip(previous) {
+        if (a.y() > y) != (b.y() > y) {
+            let crossing_x = a.x() + (y - a.y()) / (b.y() - a.y()) * (b.x() - a.x());
+            if x < crossing_x {
+                inside = !inside;
+            }
+        }
+    }
+
+    inside
+}
+
+#[derive(Default)]
+pub(super) struct Lasso {
+    points: Vec<Position>,
+    /// Entity that draws the outline of the lasso
+    outline: Option<Entity>,
+}
+
+/// Pressing on empty canvas and dragging draws a freeform shape, and
+/// every [Selectable] inside of it is selected when the mouse is released
+#[allow(clippy::too_many_arguments)]
+pub(super) fn lasso_selection_system(
+    mut commands: Commands,
+    input: ActionInput,
+    mode: Res<RegionSelectMode>,
+    camera: Camera,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    hovered_query: Query<(), (With<Hovered>, Without<Hidden>)>,
+    selectable_query: Query<(), (With<Selectable>, Without<Hidden>)>,
+    spatial_index: Res<SpatialIndex>,
+    mut primitives_query: Query<&mut primitives::Primatives>,
+    mut selection_set: ResMut<SelectionSet>,
+    mut lasso: Local<Lasso>,
+) {
+    let cursor_position = cursor_world_position(&window_query, &camera);
+
+    if let Some(cursor_position) = cursor_position {
+        if input.just_pressed(Action::Select)
+            && *mode == RegionSelectMode::Lasso
+            && hovered_query.is_empty()
+        {
+            if let Some(old_outline) = lasso.outline.take() {
+                commands.entity(old_outline).despawn();
+            }
+            let outline = primitives::Primatives::new(&[], primitives::Type::LineStrip, 2.0);
+            lasso.outline = Some(commands.spawn(outline).id());
+            lasso.points.clear();
+            lasso.points.push(cursor_position);
+        }
+    }
+
+    let Some(outline) = lasso.outline else {
+        return;
+    };
+
+    // Releasing and cancelling are still handled when the mouse has
+    // left the window so that the lasso doesn't get stuck to it
+    let released = input.just_released(Action::Select);
+    if released || input.just_pressed(Action::Cancel) {
+        commands.entity(outline).despawn();
+        lasso.outline = None;
+        let points = std::mem::take(&mut lasso.points);
+        if !released {
+            return;
+        }
+
+        // Only entities inside of the bounds of the lasso can be inside of it
+        let bounds = SelectionRect::bounding(&points).unwrap();
+        let inside = spatial_index
+            .query_rect(bounds)
+            .filter(|(entity, position)| {
+                selectable_query.contains(*entity) && polygon_contains(&points, position)
+            })
+            .map(|(entity, _)| entity);
+        selection_set.apply(SelectionMode::from_modifiers(&input), inside);
+        return;
+    }
+
+    let Some(cursor_position) = cursor_position else {
+        return;
+    };
+    let last_point = *lasso.points.last().unwrap();
+    if last_point.distance(&cursor_position) >= MIN_POINT_DISTANCE {
+        lasso.points.push(cursor_position);
+    }
+
+    // The outline is only spawned this frame when the lasso starts
+    if let Ok(mut outline) = primitives_query.get_mut(outline) {
+        let first_point = lasso.points[0];
+        outline.set_positions(lasso.points.iter().copied().chain([first_point]));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn square_contains() {
+        let square = [
+            Position::new(0.0, 0.0),
+            Position::new(10.0, 0.0),
+            Position::new(10.0, 10.0),
+            Position::new(0.0, 10.0),
+        ];
+
+        assert!(polygon_contains(&square, &Position::new(5.0, 5.0)));
+        assert!(!polygon_contains(&square, &Position::new(15.0, 5.0)));
+        assert!(!polygon_contains(&square, &Position::new(5.0, -5.0)));
+    }
+
+    #[test]
+    fn concave_polygon() {
+        // A U shape opening upwards
+        let u_shape = [
+            Position::new(0.0, 0.0),
+            Position::new(30.0, 0.0),
+            Position::new(30.0, 30.0),
+            Position::new(20.0, 30.0),
+            Position::new(20.0, 10.0),
+            Position::new(10.0, 10.0),
+            Position::new(10.0, 30.0),
+            Position::new(0.0, 30.0),
+        ];
+
+        assert!(polygon_contains(&u_shape, &Position::new(5.0, 20.0)));
+        assert!(polygon_contains(&u_shape, &Position::new(25.0, 20.0)));
+        assert!(polygon_contains(&u_shape, &Position::new(15.0, 5.0)));
+        assert!(!polygon_contains(&u_shape, &Position::new(15.0, 20.0)));
+    }
+
+    #[test]
+    fn degenerate_polygons() {
+        let point = Position::new(1.0, 1.0);
+        assert!(!polygon_contains(&[], &point));
+        assert!(!polygon_contains(&[point], &point));
+        assert!(!polygon_contains(&[point, Position::new(2.0, 2.0)], &point));
+    }
+}
@@ -0,0 +1,183 @@
+use bevy::{
+    ecs::{
+        query::{With, Without},
+        system::{Local, Query, Res, ResMut},
+    },
+    window::{PrimaryWindow, Window},
+};
+
+use crate::{
+    hidden::Hidden,
+    input_map::{Action, ActionInput},
+    position::Position,
+    rendering::{primitives::Lines, Camera},
+};
+
+use super::{Hovered, RegionSelectMode, Selectable, SelectionMode, SelectionSet, SpatialIndex};
+
+/// An axis aligned rectangle in world space
+#[derive(Clone, Copy, Debug)]
+pub struct SelectionRect {
+    min: Position,
+    max: Position,
+}
+
+impl SelectionRect {
+    pub fn from_corners(a: Position, b: Position) -> Self {
+        Self {
+            min: Position::new(a.x().min(b.x()), a.y().min(b.y())),
+            max: Position::new(a.x().max(b.x()), a.y().max(b.y())),
+        }
+    }
+
+    /// Smallest rectangle that contains every position
+    pub fn bounding(positions: &[Position]) -> Option<Self> {
+        let (first, rest) = positions.split_first()?;
+        Some(
+            rest.iter()
+                .fold(Self::from_corners(*first, *first), |rect, position| {
+                    Self::from_corners(
+                        Position::new(
+                            rect.min.x().min(position.x()),
+                            rect.min.y().min(position.y()),
+                        ),
+                        Position::new(
+                            rect.max.x().max(position.x()),
+                            rect.max.y().max(position.y()),
+                        ),
+                    )
+                }),
+        )
+    }
+
+    pub fn contains(&self, position: &Position) -> bool {
+        (self.min.x()..=self.max.x()).contains(&position.x())
+            && (self.min.y()..=self.max.y()).contains(&position.y())
+    }
+
+    /// Whether the rectangles overlap, touching counts
+    pub fn intersects(&self, other: &SelectionRect) -> bool {
+        self.min.x() <= other.max.x()
+            && other.min.x() <= self.max.x()
+            && self.min.y() <= other.max.y()
+            && other.min.y() <= self.max.y()
+    }
+
+    pub fn corners(&self) -> [Position; 4] {
+        [
+            self.min,
+            Position::new(self.max.x(), self.min.y()),
+            self.max,
+            Position::new(self.min.x(), self.max.y()),
+        ]
+    }
+}
+
+#[derive(Default)]
+pub(super) struct Marquee {
+    /// Where the mouse was pressed to start a marquee
+    /// if one is being drawn
+    start: Option<Position>,
+    /// Where the mouse was last seen in the window, which is used
+    /// for the other corner when the mouse is released outside of it
+    end: Position,
+}
+
+/// Returns the world position of the mouse if it is in the window
+pub fn cursor_world_position(
+    window_query: &Query<&Window, With<PrimaryWindow>>,
+    camera: &Camera,
+) -> Option<Position> {
+    let cursor_position = window_query.get_single().ok()?.cursor_position()?;
+    Some(camera.screen_to_world(Position::from([cursor_position.x, cursor_position.y])))
+}
+
+/// Pressing on empty canvas and dragging draws a rectangle, and every
+/// [Selectable] inside of it is selected when the mouse is released
+#[allow(clippy::too_many_arguments)]
+pub(super) fn marquee_selection_system(
+    input: ActionInput,
+    mode: Res<RegionSelectMode>,
+    camera: Camera,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    hovered_query: Query<(), (With<Hovered>, Without<Hidden>)>,
+    selectable_query: Query<(), (With<Selectable>, Without<Hidden>)>,
+    spatial_index: Res<SpatialIndex>,
+    mut selection_set: ResMut<SelectionSet>,
+    mut lines: Lines,
+    mut marquee: Local<Marquee>,
+) {
+    if input.just_pressed(Action::Cancel) {
+        marquee.start = None;
+    }
+
+    // Releasing and cancelling are still handled when the mouse has
+    // left the window so that the marquee doesn't get stuck to it
+    if let Some(cursor_position) = cursor_world_position(&window_query, &camera) {
+        if input.just_pressed(Action::Select)
+            && *mode == RegionSelectMode::Marquee
+            && hovered_query.is_empty()
+        {
+            marquee.start = Some(cursor_position);
+        }
+        marquee.end = cursor_position;
+    }
+
+    let Some(start_position) = marquee.start else {
+        return;
+    };
+    let rect = SelectionRect::from_corners(start_position, marquee.end);
+
+    if input.just_released(Action::Select) {
+        marquee.start = None;
+
+        let inside = spatial_index
+            .query_rect(rect)
+            .map(|(entity, _)| entity)
+            .filter(|entity| selectable_query.contains(*entity));
+        selection_set.apply(SelectionMode::from_modifiers(&input), inside);
+    } else {
+        let [a, b, c, d] = rect.corners();
+        lines.draw_line(a, b);
+        lines.draw_line(b, c);
+        lines.draw_line(c, d);
+        lines.draw_line(d, a);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rect_from_any_corners() {
+        let rect = SelectionRect::from_corners(Position::new(10.0, 0.0), Position::new(0.0, 10.0));
+
+        assert!(rect.contains(&Position::new(5.0, 5.0)));
+        assert!(rect.contains(&Position::new(0.0, 0.0)));
+        assert!(rect.contains(&Position::new(10.0, 10.0)));
+        assert!(!rect.contains(&Position::new(11.0, 5.0)));
+        assert!(!rect.contains(&Position::new(5.0, -1.0)));
+    }
+
+    #[test]
+    fn bounding_rect() {
+        assert!(SelectionRect::bounding(&[]).is_none());
+
+        let rect = SelectionRect::bounding(&[
+            Position::new(3.0, -2.0),
+            Position::new(-1.0, 4.0),
+            Position::new(0.0, 0.0),
+        ])
+        .unwrap();
+        assert_eq!(
+            rect.corners(),
+            [
+                Position::new(-1.0, -2.0),
+                Position::new(3.0, -2.0),
+                Position::new(3.0, 4.0),
+                Position::new(-1.0, 4.0),
+            ]
+        );
+    }
+}
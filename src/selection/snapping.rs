@@ -0,0 +1,276 @@
+use bevy::ecs::system::{Res, Resource};
+
+use crate::{
+    input_map::{Action, ActionInput},
+    position::Position,
+    rendering::primitives::Lines,
+};
+
+/// Length of the guide drawn along a grid line
+const GRID_GUIDE_LENGTH: f32 = 40.0;
+
+/// Controls what dragged entities snap to. Each kind of
+/// snap can be turned off by setting it to None or false
+#[derive(Resource, Clone)]
+pub struct SnapSettings {
+    /// Spacing of the grid to snap to
+    pub grid: Option<f32>,
+    /// Snap onto other draggable points
+    pub points: bool,
+    /// Angle step in degrees that handles snap to around their anchor
+    pub angle: Option<f32>,
+    /// Snap horizontally or vertically in line with other points
+    pub alignment: bool,
+    /// How close in screen pixels a snap target has to be to snap to it
+    pub distance: f32,
+}
+
+impl Default for SnapSettings {
+    fn default() -> Self {
+        Self {
+            grid: Some(20.0),
+            points: true,
+            angle: Some(15.0),
+            alignment: true,
+            distance: 6.0,
+        }
+    }
+}
+
+/// The result of snapping a position along with
+/// the guide lines that explain the snap
+#[derive(Default, Debug)]
+pub struct Snapped {
+    pub position: Position,
+    pub guides: Vec<(Position, Position)>,
+}
+
+/// Finds the value in candidates closest to value if
+/// it is within distance
+fn closest_within(value: f32, candidates: impl Iterator<Item = f32>, distance: f32) -> Option<f32> {
+    candidates
+        .map(|candidate| (candidate, (candidate - value).abs()))
+        .filter(|(_, difference)| *difference < distance)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(candidate, _)| candidate)
+}
+
+/// Snaps a single axis either into alignment with one of the other
+/// points or onto the grid, alignment taking priority
+fn snap_axis(
+    value: f32,
+    others: impl Iterator<Item = f32>,
+    settings: &SnapSettings,
+) -> Option<(f32, bool)> {
+    if settings.alignment {
+        if let Some(aligned) = closest_within(value, others, settings.distance) {
+            return Some((aligned, true));
+        }
+    }
+
+    let grid = settings.grid?;
+    let grid_value = (value / grid).round() * grid;
+    ((grid_value - value).abs() < settings.distance).then_some((grid_value, false))
+}
+
+/// Snaps position to the closest target allowed by settings.
+/// Others are the positions of entities that can be snapped to
+/// and anchor is the point that the angle of position is measured
+/// from, usually the anchor of a handle
+pub fn snap_position(
+    position: Position,
+    others: &[Position],
+    anchor: Option<Position>,
+    settings: &SnapSettings,
+) -> Snapped {
+    // Snapping onto a point fixes both axes so it beats everything else
+    if settings.points {
+        let closest = others
+            .iter()
+            .map(|other| (*other, other.distance(&position)))
+            .filter(|(_, distance)| *distance < settings.distance)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b));
+
+        if let Some((other, _)) = closest {
+            return Snapped {
+                position: other,
+                guides: Vec::new(),
+            };
+        }
+    }
+
+    if let (Some(step), Some(anchor)) = (settings.angle, anchor) {
+        let offset = position - anchor;
+        let length = offset.distance(&Position::default());
+        let angle = offset.y().atan2(offset.x());
+        let step = step.to_radians();
+        let snapped_angle = (angle / step).round() * step;
+
+        // Compare the distance along the arc so that the snap
+        // feels the same no matter how long the handle is
+        if length > 0.0 && (snapped_angle - angle).abs() * length < settings.distance {
+            let (sin, cos) = snapped_angle.sin_cos();
+            let snapped = anchor + Position::new(cos, sin) * length;
+            return Snapped {
+                position: snapped,
+                guides: vec![(anchor, anchor + Position::new(cos, sin) * (length * 2.0))],
+            };
+        }
+    }
+
+    let mut snapped = Snapped {
+        position,
+        guides: Vec::new(),
+    };
+
+    let x = snap_axis(position.x(), others.iter().map(Position::x), settings);
+    let y = snap_axis(position.y(), others.iter().map(Position::y), settings);
+    let x_value = x.map_or(position.x(), |(x, _)| x);
+    let y_value = y.map_or(position.y(), |(y, _)| y);
+    snapped.position = Position::new(x_value, y_value);
+
+    if let Some((x, aligned)) = x {
+        let aligned_with = others.iter().filter(|other| other.x() == x).min_by(|a, b| {
+            let a = (a.y() - y_value).abs();
+            let b = (b.y() - y_value).abs();
+            a.total_cmp(&b)
+        });
+        let guide = match aligned_with {
+            Some(other) if aligned => (*other, snapped.position),
+            _ => (
+                Position::new(x, y_value - GRID_GUIDE_LENGTH / 2.0),
+                Position::new(x, y_value + GRID_GUIDE_LENGTH / 2.0),
+            ),
+        };
+        snapped.guides.push(guide);
+    }
+
+    if let Some((y, aligned)) = y {
+        let aligned_with = others.iter().filter(|other| other.y() == y).min_by(|a, b| {
+            let a = (a.x() - x_value).abs();
+            let b = (b.x() - x_value).abs();
+            a.total_cmp(&b)
+        });
+        let guide = match aligned_with {
+            Some(other) if aligned => (*other, snapped.position),
+            _ => (
+                Position::new(x_value - GRID_GUIDE_LENGTH / 2.0, y),
+                Position::new(x_value + GRID_GUIDE_LENGTH / 2.0, y),
+            ),
+        };
+        snapped.guides.push(guide);
+    }
+
+    snapped
+}
+
+/// Snapping is skipped while [Action::DisableSnapping] is held
+pub fn snapping_enabled(input: &ActionInput) -> bool {
+    !input.pressed(Action::DisableSnapping)
+}
+
+/// Guide lines of the snap that is currently applied to the drag
+#[derive(Resource, Default)]
+pub struct SnapGuides(pub Vec<(Position, Position)>);
+
+pub(super) fn draw_snap_guides_system(guides: Res<SnapGuides>, mut lines: Lines) {
+    for (from, to) in &guides.0 {
+        lines.draw_line(*from, *to);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> SnapSettings {
+        SnapSettings {
+            grid: None,
+            points: false,
+            angle: None,
+            alignment: false,
+            distance: 5.0,
+        }
+    }
+
+    #[test]
+    fn no_snapping() {
+        let position = Position::new(12.0, 13.0);
+        let snapped = snap_position(position, &[position], None, &settings());
+        assert_eq!(snapped.position, position);
+        assert!(snapped.guides.is_empty());
+    }
+
+    #[test]
+    fn grid_snapping() {
+        let settings = SnapSettings {
+            grid: Some(20.0),
+            ..settings()
+        };
+
+        let snapped = snap_position(Position::new(22.0, 30.0), &[], None, &settings);
+        assert_eq!(snapped.position, Position::new(20.0, 30.0));
+        assert_eq!(snapped.guides.len(), 1);
+
+        let snapped = snap_position(Position::new(38.0, 41.0), &[], None, &settings);
+        assert_eq!(snapped.position, Position::new(40.0, 40.0));
+        assert_eq!(snapped.guides.len(), 2);
+    }
+
+    #[test]
+    fn point_snapping() {
+        let settings = SnapSettings {
+            points: true,
+            grid: Some(20.0),
+            ..settings()
+        };
+
+        let others = [Position::new(103.0, 97.0), Position::new(300.0, 300.0)];
+        let snapped = snap_position(Position::new(100.0, 100.0), &others, None, &settings);
+        assert_eq!(snapped.position, others[0]);
+    }
+
+    #[test]
+    fn alignment_snapping() {
+        let settings = SnapSettings {
+            alignment: true,
+            grid: Some(20.0),
+            ..settings()
+        };
+
+        let others = [Position::new(61.0, 0.0)];
+        let position = Position::new(58.0, 200.0);
+        let snapped = snap_position(position, &others, None, &settings);
+
+        // Alignment beats the grid line at 60 even though the grid line is closer
+        assert_eq!(snapped.position, Position::new(61.0, 200.0));
+        assert_eq!(snapped.guides[0], (others[0], snapped.position));
+
+        let grid_only = SnapSettings {
+            alignment: false,
+            ..settings
+        };
+        let snapped = snap_position(position, &others, None, &grid_only);
+        assert_eq!(snapped.position, Position::new(60.0, 200.0));
+    }
+
+    #[test]
+    fn angle_snapping() {
+        let settings = SnapSettings {
+            angle: Some(15.0),
+            ..settings()
+        };
+        let anchor = Position::new(10.0, 10.0);
+
+        // Just off of 45 degrees
+        let snapped = snap_position(Position::new(110.0, 112.0), &[], Some(anchor), &settings);
+        let offset = snapped.position - anchor;
+        assert!((offset.x() - offset.y()).abs() < 0.001);
+        assert!((offset.distance(&Position::default()) - 100.0f32.hypot(102.0)).abs() < 0.001);
+
+        // Too far away from any step to snap
+        let position = Position::new(110.0, 60.0);
+        let snapped = snap_position(position, &[], Some(anchor), &settings);
+        assert_eq!(snapped.position, position);
+    }
+}
@@ -0,0 +1,551 @@
+use bevy::{
+    ecs::{
+        entity::Entity,
+        query::{Added, Changed, Or, With},
+        removal_detection::RemovedComponents,
+        system::{Query, ResMut, Resource},
+    },
+    utils::HashMap,
+};
+
+use crate::{
+    position::Position,
+    rendering::{CameraZoom, Size},
+};
+
+use super::{marquee::SelectionRect, HoverShape, Hoverable, Selectable};
+
+type Cell = (i32, i32);
+
+/// Shapes whose bounds cover more cells than this are kept in a list
+/// that every query looks through, so that one huge shape doesn't
+/// end up in thousands of cells
+const MAX_SHAPE_CELLS: i64 = 64;
+
+struct Entry {
+    position: Position,
+    cell: Cell,
+    /// Only entities that are [Hoverable] have a hover radius
+    hover_radius: Option<Size>,
+}
+
+struct ShapeEntry {
+    bounds: SelectionRect,
+    /// The first and last cell the bounds are in, or None
+    /// when the shape is one of the large shapes
+    cells: Option<(Cell, Cell)>,
+    hover_radius: Option<Size>,
+}
+
+/// Uniform grid of every [Hoverable] or [Selectable] entity so that
+/// hit testing only has to look at entities near the point being
+/// tested. It is kept up to date from changes to [Position]. Entities
+/// with a [HoverShape] rather than a position are indexed by the
+/// bounds of their shape in every cell the bounds cover
+#[derive(Resource)]
+pub struct SpatialIndex {
+    cell_size: f32,
+    cells: HashMap<Cell, Vec<Entity>>,
+    entries: HashMap<Entity, Entry>,
+    shape_cells: HashMap<Cell, Vec<Entity>>,
+    shapes: HashMap<Entity, ShapeEntry>,
+    large_shapes: Vec<Entity>,
+    /// Largest hover radius of every indexed entity in each kind of size,
+    /// used as the search radius when looking for entities under the mouse
+    max_world_radius: f32,
+    max_screen_radius: f32,
+    /// Set when an entity that may have had the largest
+    /// hover radius is removed so it gets worked out again
+    radius_removed: bool,
+}
+
+impl Default for SpatialIndex {
+    fn default() -> Self {
+        Self::new(64.0)
+    }
+}
+
+impl SpatialIndex {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::default(),
+            entries: HashMap::default(),
+            shape_cells: HashMap::default(),
+            shapes: HashMap::default(),
+            large_shapes: Vec::new(),
+            max_world_radius: 0.0,
+            max_screen_radius: 0.0,
+            radius_removed: false,
+        }
+    }
+
+    fn cell(&self, position: Position) -> Cell {
+        (
+            (position.x() / self.cell_size).floor() as i32,
+            (position.y() / self.cell_size).floor() as i32,
+        )
+    }
+
+    #[allow(dead_code)]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Adds entity to the index or moves it if it is already indexed
+    pub fn insert(&mut self, entity: Entity, position: Position, hover_radius: Option<Size>) {
+        let cell = self.cell(position);
+        self.grow_radius(hover_radius);
+
+        if let Some(entry) = self.entries.get_mut(&entity) {
+            entry.position = position;
+            let old_radius = std::mem::replace(&mut entry.hover_radius, hover_radius);
+            if old_radius.is_some() && old_radius != hover_radius {
+                self.radius_removed = true;
+            }
+            if entry.cell == cell {
+                return;
+            }
+
+            let old_cell = std::mem::replace(&mut entry.cell, cell);
+            self.remove_from_cell(entity, old_cell);
+        } else {
+            self.entries.insert(
+                entity,
+                Entry {
+                    position,
+                    cell,
+                    hover_radius,
+                },
+            );
+        }
+
+        self.cells.entry(cell).or_default().push(entity);
+    }
+
+    pub fn remove(&mut self, entity: Entity) {
+        if let Some(entry) = self.entries.remove(&entity) {
+            self.remove_from_cell(entity, entry.cell);
+            if entry.hover_radius.is_some() {
+                self.radius_removed = true;
+            }
+        }
+    }
+
+    fn remove_from_cell(&mut self, entity: Entity, cell: Cell) {
+        remove_from_cell(&mut self.cells, entity, cell);
+    }
+
+    /// Adds the shape of entity to the index or replaces it if it is already
+    /// indexed. Empty shapes can't be hovered so they aren't indexed
+    pub fn insert_shape(&mut self, entity: Entity, shape: &[Position], hover_radius: Option<Size>) {
+        if let Some(old) = self.remove_shape_entry(entity) {
+            if old.hover_radius.is_some() && old.hover_radius != hover_radius {
+                self.radius_removed = true;
+            }
+        }
+        let Some(bounds) = SelectionRect::bounding(shape) else {
+            return;
+        };
+        self.grow_radius(hover_radius);
+
+        let [min, _, max, _] = bounds.corners();
+        let (min_x, min_y) = self.cell(min);
+        let (max_x, max_y) = self.cell(max);
+        let cell_count =
+            (max_x as i64 - min_x as i64 + 1).saturating_mul(max_y as i64 - min_y as i64 + 1);
+        let cells = if cell_count > MAX_SHAPE_CELLS {
+            self.large_shapes.push(entity);
+            None
+        } else {
+            for x in min_x..=max_x {
+                for y in min_y..=max_y {
+                    self.shape_cells.entry((x, y)).or_default().push(entity);
+                }
+            }
+            Some(((min_x, min_y), (max_x, max_y)))
+        };
+
+        self.shapes.insert(
+            entity,
+            ShapeEntry {
+                bounds,
+                cells,
+                hover_radius,
+            },
+        );
+    }
+
+    pub fn remove_shape(&mut self, entity: Entity) {
+        if let Some(entry) = self.remove_shape_entry(entity) {
+            if entry.hover_radius.is_some() {
+                self.radius_removed = true;
+            }
+        }
+    }
+
+    fn remove_shape_entry(&mut self, entity: Entity) -> Option<ShapeEntry> {
+        let entry = self.shapes.remove(&entity)?;
+        match entry.cells {
+            Some(((min_x, min_y), (max_x, max_y))) => {
+                for x in min_x..=max_x {
+                    for y in min_y..=max_y {
+                        remove_from_cell(&mut self.shape_cells, entity, (x, y));
+                    }
+                }
+            }
+            None => self.large_shapes.retain(|other| *other != entity),
+        }
+        Some(entry)
+    }
+
+    fn grow_radius(&mut self, radius: Option<Size>) {
+        match radius {
+            Some(Size::World(radius)) => self.max_world_radius = self.max_world_radius.max(radius),
+            Some(Size::Screen(radius)) => {
+                self.max_screen_radius = self.max_screen_radius.max(radius)
+            }
+            None => {}
+        }
+    }
+
+    /// Works out the largest hover radius again if it could have shrunk
+    fn shrink_radius(&mut self) {
+        if !std::mem::take(&mut self.radius_removed) {
+            return;
+        }
+
+        self.max_world_radius = 0.0;
+        self.max_screen_radius = 0.0;
+        let radii: Vec<_> = self
+            .entries
+            .values()
+            .map(|entry| entry.hover_radius)
+            .chain(self.shapes.values().map(|entry| entry.hover_radius))
+            .collect();
+        for radius in radii {
+            self.grow_radius(radius);
+        }
+    }
+
+    /// Radius around the mouse that contains every
+    /// entity that could be hovered
+    pub fn hover_search_radius(&self, zoom: &CameraZoom) -> f32 {
+        let screen_radius = Size::Screen(self.max_screen_radius).to_world(zoom);
+        self.max_world_radius.max(screen_radius)
+    }
+
+    /// Every indexed entity whose position is inside rect
+    pub fn query_rect(&self, rect: SelectionRect) -> impl Iterator<Item = (Entity, Position)> + '_ {
+        let [min, _, max, _] = rect.corners();
+        let (min_x, min_y) = self.cell(min);
+        let (max_x, max_y) = self.cell(max);
+        let cell_count =
+            (max_x as i64 - min_x as i64 + 1).saturating_mul(max_y as i64 - min_y as i64 + 1);
+
+        // Large areas with only a few entities in them are quicker
+        // to search by looking at every entity than every cell
+        let candidates: Box<dyn Iterator<Item = Entity>> = if cell_count > self.entries.len() as i64
+        {
+            Box::new(self.entries.keys().copied())
+        } else {
+            Box::new(
+                (min_x..=max_x)
+                    .flat_map(move |x| (min_y..=max_y).map(move |y| (x, y)))
+                    .filter_map(|cell| self.cells.get(&cell))
+                    .flatten()
+                    .copied(),
+            )
+        };
+
+        candidates
+            .map(|entity| (entity, self.entries[&entity].position))
+            .filter(move |(_, position)| rect.contains(position))
+    }
+
+    /// Every indexed entity within radius of center
+    pub fn query_circle(
+        &self,
+        center: Position,
+        radius: f32,
+    ) -> impl Iterator<Item = (Entity, Position)> + '_ {
+        let offset = Position::new(radius, radius);
+        let rect = SelectionRect::from_corners(center - offset, center + offset);
+        let radius_squared = radius.powi(2);
+
+        self.query_rect(rect)
+            .filter(move |(_, position)| position.distance_squared(&center) <= radius_squared)
+    }
+
+    /// Every indexed shape whose bounds are within radius of center,
+    /// which are the only shapes that can be that close to it
+    pub fn query_shapes(&self, center: Position, radius: f32) -> Vec<Entity> {
+        let offset = Position::new(radius, radius);
+        let rect = SelectionRect::from_corners(center - offset, center + offset);
+        let (min_x, min_y) = self.cell(center - offset);
+        let (max_x, max_y) = self.cell(center + offset);
+        let cell_count =
+            (max_x as i64 - min_x as i64 + 1).saturating_mul(max_y as i64 - min_y as i64 + 1);
+
+        let mut shapes: Vec<Entity> = if cell_count > self.shapes.len() as i64 {
+            self.shapes.keys().copied().collect()
+        } else {
+            (min_x..=max_x)
+                .flat_map(|x| (min_y..=max_y).map(move |y| (x, y)))
+                .filter_map(|cell| self.shape_cells.get(&cell))
+                .flatten()
+                .chain(self.large_shapes.iter())
+                .copied()
+                .collect()
+        };
+        // Shapes are in every cell that their bounds cover
+        shapes.sort_unstable();
+        shapes.dedup();
+        shapes.retain(|entity| self.shapes[entity].bounds.intersects(&rect));
+        shapes
+    }
+}
+
+fn remove_from_cell(cells: &mut HashMap<Cell, Vec<Entity>>, entity: Entity, cell: Cell) {
+    if let Some(entities) = cells.get_mut(&cell) {
+        entities.retain(|other| *other != entity);
+        if entities.is_empty() {
+            cells.remove(&cell);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub(super) fn update_spatial_index_system(
+    mut index: ResMut<SpatialIndex>,
+    changed_query: Query<
+        (Entity, &Position, Option<&Hoverable>),
+        (
+            Or<(With<Hoverable>, With<Selectable>)>,
+            Or<(Changed<Position>, Changed<Hoverable>, Added<Selectable>)>,
+        ),
+    >,
+    mut removed_positions: RemovedComponents<Position>,
+    mut removed_hoverables: RemovedComponents<Hoverable>,
+    mut removed_selectables: RemovedComponents<Selectable>,
+    indexed_query: Query<(&Position, Option<&Hoverable>), Or<(With<Hoverable>, With<Selectable>)>>,
+    changed_shapes: Query<
+        (Entity, &HoverShape, Option<&Hoverable>),
+        Or<(Changed<HoverShape>, Changed<Hoverable>)>,
+    >,
+    mut removed_shapes: RemovedComponents<HoverShape>,
+) {
+    let removed = removed_positions
+        .read()
+        .chain(removed_hoverables.read())
+        .chain(removed_selectables.read());
+    for entity in removed {
+        // The entity could still be indexed because of its other
+        // component or the component could have been added back again
+        match indexed_query.get(entity) {
+            Ok((position, hoverable)) => index.insert(
+                entity,
+                *position,
+                hoverable.map(|hoverable| hoverable.radius),
+            ),
+            Err(_) => index.remove(entity),
+        }
+    }
+
+    for (entity, position, hoverable) in changed_query.iter() {
+        index.insert(
+            entity,
+            *position,
+            hoverable.map(|hoverable| hoverable.radius),
+        );
+    }
+
+    for entity in removed_shapes.read() {
+        index.remove_shape(entity);
+    }
+    for (entity, shape, hoverable) in changed_shapes.iter() {
+        index.insert_shape(
+            entity,
+            &shape.0,
+            hoverable.map(|hoverable| hoverable.radius),
+        );
+    }
+
+    index.shrink_radius();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(entities: impl Iterator<Item = (Entity, Position)>) -> Vec<Entity> {
+        let mut entities: Vec<_> = entities.map(|(entity, _)| entity).collect();
+        entities.sort();
+        entities
+    }
+
+    #[test]
+    fn query_after_moves() {
+        let mut index = SpatialIndex::new(10.0);
+        let a = Entity::from_raw(0);
+        let b = Entity::from_raw(1);
+        let c = Entity::from_raw(2);
+
+        index.insert(a, Position::new(5.0, 5.0), None);
+        index.insert(b, Position::new(-25.0, 40.0), None);
+        index.insert(c, Position::new(100.0, 100.0), None);
+
+        let circle = index.query_circle(Position::new(0.0, 0.0), 10.0);
+        assert_eq!(sorted(circle), vec![a]);
+
+        // Moving into another cell
+        index.insert(a, Position::new(95.0, 102.0), None);
+        let circle = index.query_circle(Position::new(100.0, 100.0), 10.0);
+        assert_eq!(sorted(circle), vec![a, c]);
+        assert_eq!(index.query_circle(Position::new(0.0, 0.0), 10.0).count(), 0);
+
+        let rect = SelectionRect::from_corners(Position::new(-30.0, 0.0), Position::new(0.0, 50.0));
+        assert_eq!(sorted(index.query_rect(rect)), vec![b]);
+
+        index.remove(b);
+        assert_eq!(index.query_rect(rect).count(), 0);
+        assert_eq!(index.len(), 2);
+    }
+
+    #[test]
+    fn huge_queries_match_small_ones() {
+        let mut index = SpatialIndex::new(1.0);
+        for i in 0..10 {
+            let position = Position::new(i as f32 * 3.0, 0.0);
+            index.insert(Entity::from_raw(i), position, None);
+        }
+
+        // Covers far more cells than there are entities
+        let huge = SelectionRect::from_corners(
+            Position::new(-10000.0, -10000.0),
+            Position::new(10000.0, 10000.0),
+        );
+        assert_eq!(index.query_rect(huge).count(), 10);
+
+        let circle = index.query_circle(Position::new(4.0, 0.0), 2.5);
+        assert_eq!(
+            sorted(circle),
+            vec![Entity::from_raw(1), Entity::from_raw(2)]
+        );
+    }
+
+    #[test]
+    fn circle_is_round() {
+        let mut index = SpatialIndex::new(10.0);
+        let corner = Entity::from_raw(0);
+        index.insert(corner, Position::new(9.0, 9.0), None);
+
+        // Inside the bounding box of the circle but outside of the circle
+        assert_eq!(index.query_circle(Position::new(0.0, 0.0), 10.0).count(), 0);
+    }
+
+    #[test]
+    fn extreme_rect_does_not_overflow() {
+        let mut index = SpatialIndex::new(1.0);
+        index.insert(Entity::from_raw(0), Position::new(0.0, 0.0), None);
+
+        let everything = SelectionRect::from_corners(
+            Position::new(f32::MIN, f32::MIN),
+            Position::new(f32::MAX, f32::MAX),
+        );
+        assert_eq!(index.query_rect(everything).count(), 1);
+    }
+
+    #[test]
+    fn query_shapes_by_bounds() {
+        let mut index = SpatialIndex::new(10.0);
+        let small = Entity::from_raw(0);
+        let large = Entity::from_raw(1);
+        let far = Entity::from_raw(2);
+
+        // Spread over a few cells
+        index.insert_shape(
+            small,
+            &[Position::new(0.0, 0.0), Position::new(25.0, 15.0)],
+            None,
+        );
+        // Covers too many cells to be put in every one of them
+        index.insert_shape(
+            large,
+            &[Position::new(-500.0, -500.0), Position::new(500.0, 500.0)],
+            None,
+        );
+        index.insert_shape(far, &[Position::new(1000.0, 1000.0)], None);
+
+        assert_eq!(
+            index.query_shapes(Position::new(15.0, 5.0), 1.0),
+            vec![small, large]
+        );
+        assert_eq!(
+            index.query_shapes(Position::new(-405.0, 5.0), 1.0),
+            vec![large]
+        );
+        // Outside of the bounds but within the radius of them
+        assert_eq!(
+            index.query_shapes(Position::new(1003.0, 1000.0), 5.0),
+            vec![far]
+        );
+        // A radius that covers more cells than there are shapes
+        assert_eq!(
+            index.query_shapes(Position::new(0.0, 0.0), 10000.0).len(),
+            3
+        );
+
+        // Moving a shape takes it out of the cells it was in
+        index.insert_shape(small, &[Position::new(-195.0, 305.0)], None);
+        assert_eq!(
+            index.query_shapes(Position::new(15.0, 5.0), 1.0),
+            vec![large]
+        );
+        assert_eq!(
+            index.query_shapes(Position::new(-195.0, 305.0), 1.0),
+            vec![small, large]
+        );
+
+        index.remove_shape(large);
+        index.remove_shape(small);
+        assert!(index
+            .query_shapes(Position::new(-195.0, 305.0), 1.0)
+            .is_empty());
+        // Empty shapes aren't indexed
+        index.insert_shape(small, &[], None);
+        assert_eq!(
+            index.query_shapes(Position::new(0.0, 0.0), 10000.0).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn search_radius_shrinks() {
+        let zoom = CameraZoom::default();
+        let mut index = SpatialIndex::new(10.0);
+        let small = Entity::from_raw(0);
+        let large = Entity::from_raw(1);
+
+        index.insert(small, Position::new(0.0, 0.0), Some(Size::World(5.0)));
+        index.insert(large, Position::new(0.0, 0.0), Some(Size::World(50.0)));
+        assert_eq!(index.hover_search_radius(&zoom), 50.0);
+
+        index.remove(large);
+        index.shrink_radius();
+        assert_eq!(index.hover_search_radius(&zoom), 5.0);
+
+        // Entities that stop being hoverable no longer count either
+        index.insert(small, Position::new(0.0, 0.0), None);
+        index.shrink_radius();
+        assert_eq!(index.hover_search_radius(&zoom), 0.0);
+
+        // Shapes count as well
+        let shape = Entity::from_raw(2);
+        index.insert_shape(shape, &[Position::new(0.0, 0.0)], Some(Size::World(8.0)));
+        assert_eq!(index.hover_search_radius(&zoom), 8.0);
+        index.remove_shape(shape);
+        index.shrink_radius();
+        assert_eq!(index.hover_search_radius(&zoom), 0.0);
+    }
+}
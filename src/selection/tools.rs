@@ -0,0 +1,331 @@
+use bevy::{
+    ecs::{
+        change_detection::DetectChanges,
+        entity::Entity,
+        event::{EventReader, EventWriter},
+        query::With,
+        system::{Commands, Local, Query, Res, ResMut, Resource},
+    },
+    window::{CursorIcon, PrimaryWindow, Window},
+};
+
+use crate::{
+    gestures::Gesture,
+    input_map::{Action, ActionInput},
+    position::Position,
+    rendering::{primitives::Lines, CameraMut},
+    title::TitleMessages,
+};
+
+use super::{HoverExited, Hovered};
+
+/// The tool that decides what the mouse does on the canvas
+#[derive(Resource, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Tool {
+    /// Selects terminal points and drags the whole selection
+    #[default]
+    Select,
+    /// Selects terminal points but only drags the point under the mouse
+    DirectSelect,
+    /// Clicking places points that are joined up with new curves
+    Pen,
+    /// Dragging moves the camera
+    Pan,
+    /// Clicking zooms in, or out while alt is held
+    Zoom,
+    /// Dragging measures the distance between two points
+    Measure,
+}
+
+impl Tool {
+    pub const ALL: [Tool; 6] = [
+        Tool::Select,
+        Tool::DirectSelect,
+        Tool::Pen,
+        Tool::Pan,
+        Tool::Zoom,
+        Tool::Measure,
+    ];
+
+    /// Action that switches to this tool
+    pub fn action(self) -> Action {
+        match self {
+            Tool::Select => Action::SelectTool,
+            Tool::DirectSelect => Action::DirectSelectTool,
+            Tool::Pen => Action::PenTool,
+            Tool::Pan => Action::PanTool,
+            Tool::Zoom => Action::ZoomTool,
+            Tool::Measure => Action::MeasureTool,
+        }
+    }
+
+    /// Whether hovering, clicking and dragging entities is
+    /// handled by the selection systems while this tool is active
+    pub fn selects(self) -> bool {
+        matches!(self, Tool::Select | Tool::DirectSelect)
+    }
+
+    fn cursor(self, input: &ActionInput) -> CursorIcon {
+        match self {
+            Tool::Select => CursorIcon::Default,
+            Tool::DirectSelect => CursorIcon::Pointer,
+            Tool::Pen | Tool::Measure => CursorIcon::Crosshair,
+            Tool::Pan if input.pressed(Action::Select) => CursorIcon::Grabbing,
+            Tool::Pan => CursorIcon::Grab,
+            Tool::Zoom if input.pressed(Action::ZoomToolOut) => CursorIcon::ZoomOut,
+            Tool::Zoom => CursorIcon::ZoomIn,
+        }
+    }
+}
+
+/// Run condition for systems that only run while a selection tool is active
+pub(super) fn selecting(tool: Res<Tool>) -> bool {
+    tool.selects()
+}
+
+/// The last distance measured with [Tool::Measure]
+#[derive(Resource, Default, Clone, Copy, Debug)]
+pub struct Measurement(pub Option<(Position, Position)>);
+
+impl Measurement {
+    pub fn distance(&self) -> Option<f32> {
+        self.0.map(|(start, end)| start.distance(&end))
+    }
+}
+
+pub(super) fn switch_tool_system(
+    mut commands: Commands,
+    input: ActionInput,
+    mut tool: ResMut<Tool>,
+    hovered_query: Query<Entity, With<Hovered>>,
+    mut hover_exited: EventWriter<HoverExited>,
+) {
+    // Switching in the middle of a drag would leave it unfinished
+    if input.any_button_pressed() {
+        return;
+    }
+
+    let Some(new_tool) = Tool::ALL
+        .into_iter()
+        .find(|tool| input.just_pressed(tool.action()))
+    else {
+        return;
+    };
+    if new_tool == *tool {
+        return;
+    }
+
+    // Other tools don't hover so nothing should be left hovered
+    if !new_tool.selects() {
+        for entity in hovered_query.iter() {
+            commands.entity(entity).remove::<Hovered>();
+            hover_exited.send(HoverExited(entity));
+        }
+    }
+
+    *tool = new_tool;
+}
+
+pub(super) fn tool_cursor_system(
+    tool: Res<Tool>,
+    input: ActionInput,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let Ok(mut window) = window_query.get_single_mut() else {
+        return;
+    };
+
+    let icon = tool.cursor(&input);
+    // Only touch the window when the icon changes so it isn't
+    // marked as changed every frame
+    if window.cursor.icon != icon {
+        window.cursor.icon = icon;
+    }
+}
+
+/// Dragging with [Tool::Pan] or the [Action::Pan] button moves the camera
+pub(super) fn pan_tool_system(
+    tool: Res<Tool>,
+    input: ActionInput,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut camera: CameraMut,
+    mut last_cursor: Local<Option<Position>>,
+) {
+    let panning =
+        input.pressed(Action::Pan) || (*tool == Tool::Pan && input.pressed(Action::Select));
+    let cursor = window_query
+        .get_single()
+        .ok()
+        .and_then(Window::cursor_position)
+        .map(|cursor| Position::from([cursor.x, cursor.y]));
+
+    let (true, Some(cursor)) = (panning, cursor) else {
+        *last_cursor = None;
+        return;
+    };
+
+    // Screen positions are used since the world
+    // moves under the mouse while panning
+    if let Some(last_cursor) = *last_cursor {
+        camera.pan(cursor - last_cursor);
+    }
+    *last_cursor = Some(cursor);
+}
+
+/// Clicking with [Tool::Zoom] zooms in on the mouse, or out
+/// while [Action::ZoomToolOut] is held
+pub(super) fn zoom_tool_system(
+    tool: Res<Tool>,
+    input: ActionInput,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut gestures: EventReader<Gesture>,
+    mut camera: CameraMut,
+) {
+    if *tool != Tool::Zoom {
+        gestures.clear();
+        return;
+    }
+
+    let factor = if input.pressed(Action::ZoomToolOut) {
+        0.5
+    } else {
+        2.0
+    };
+
+    for gesture in gestures.read() {
+        let Gesture::Click { button, .. } = *gesture else {
+            continue;
+        };
+        if !input.is_button(Action::Select, button) {
+            continue;
+        }
+
+        let Some(cursor) = window_query
+            .get_single()
+            .ok()
+            .and_then(Window::cursor_position)
+        else {
+            continue;
+        };
+        camera.zoom_about(Position::from([cursor.x, cursor.y]), factor);
+    }
+}
+
+/// Dragging with [Tool::Measure] measures from where the drag
+/// started to the mouse and draws a line between them
+pub(super) fn measure_tool_system(
+    tool: Res<Tool>,
+    input: ActionInput,
+    mut gestures: EventReader<Gesture>,
+    mut measurement: ResMut<Measurement>,
+    mut lines: Lines,
+) {
+    if *tool != Tool::Measure {
+        gestures.clear();
+        if measurement.0.is_some() {
+            measurement.0 = None;
+        }
+        return;
+    }
+
+    let gestures = gestures.read().filter(|gesture| match **gesture {
+        Gesture::DragStart { button, .. } | Gesture::Drag { button, .. } => {
+            input.is_button(Action::Select, button)
+        }
+        _ => false,
+    });
+
+    for gesture in gestures {
+        match *gesture {
+            Gesture::DragStart {
+                start, position, ..
+            } => measurement.0 = Some((start, position)),
+            Gesture::Drag { position, .. } => {
+                if let Some((_, end)) = measurement.0.as_mut() {
+                    *end = position;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if let Some((start, end)) = measurement.0 {
+        lines.draw_line(start, end);
+    }
+}
+
+/// Shows the [Measurement] in the window title while there is one
+pub(super) fn measurement_title_system(
+    measurement: Res<Measurement>,
+    mut messages: ResMut<TitleMessages>,
+) {
+    if !measurement.is_changed() {
+        return;
+    }
+
+    match measurement.distance() {
+        Some(distance) => messages.set("measurement", format!("Measured distance: {distance:.2}")),
+        None => messages.clear("measurement"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input_map::InputMap;
+    use bevy::{
+        ecs::{event::Events, system::RunSystemOnce, world::World},
+        input::{keyboard::KeyCode, mouse::MouseButton, ButtonInput},
+    };
+
+    #[test]
+    fn switching_tools() {
+        let mut world = World::new();
+        world.init_resource::<Tool>();
+        world.init_resource::<InputMap>();
+        world.init_resource::<ButtonInput<KeyCode>>();
+        world.init_resource::<ButtonInput<MouseButton>>();
+        world.init_resource::<Events<HoverExited>>();
+        let hovered = world.spawn(Hovered::default()).id();
+
+        let press = |world: &mut World, key| {
+            let mut keys = world.resource_mut::<ButtonInput<KeyCode>>();
+            keys.clear();
+            keys.press(key);
+            world.run_system_once(switch_tool_system);
+        };
+
+        press(&mut world, KeyCode::KeyA);
+        assert_eq!(*world.resource::<Tool>(), Tool::DirectSelect);
+        assert!(world.get::<Hovered>(hovered).is_some());
+
+        press(&mut world, KeyCode::KeyH);
+        assert_eq!(*world.resource::<Tool>(), Tool::Pan);
+        assert!(world.get::<Hovered>(hovered).is_none());
+
+        // Held mouse buttons block switching
+        world
+            .resource_mut::<ButtonInput<MouseButton>>()
+            .press(MouseButton::Left);
+        press(&mut world, KeyCode::KeyV);
+        assert_eq!(*world.resource::<Tool>(), Tool::Pan);
+    }
+
+    #[test]
+    fn measurement_in_title() {
+        let mut world = World::new();
+        world.init_resource::<Measurement>();
+        world.init_resource::<TitleMessages>();
+        let title = |world: &mut World| {
+            world.run_system_once(measurement_title_system);
+            world.resource::<TitleMessages>().title("Editor")
+        };
+
+        world.resource_mut::<Measurement>().0 =
+            Some((Position::new(0.0, 0.0), Position::new(3.0, 4.0)));
+        assert_eq!(title(&mut world), "Editor - Measured distance: 5.00");
+
+        world.resource_mut::<Measurement>().0 = None;
+        assert_eq!(title(&mut world), "Editor");
+    }
+}
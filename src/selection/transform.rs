@@ -0,0 +1,221 @@
+use bevy::ecs::{
+    entity::Entity,
+    event::{Event, EventReader, EventWriter},
+    query::With,
+    system::{Local, ParamSet, Query, Res, Resource},
+};
+
+use crate::{
+    input_map::{Action, ActionInput},
+    position::Position,
+};
+
+use super::{Draggable, SelectionSet};
+
+/// Changes the position of every selected entity. Any entities
+/// connected to the selection are moved based on their
+/// [Constraint](super::constraints::Constraint), the same as when the
+/// selection is dragged
+#[derive(Event, Clone, Copy, Debug)]
+#[allow(dead_code)]
+pub enum TransformSelection {
+    /// Moves the selection by an offset
+    Translate(Position),
+    /// Moves the selection so that its center is at a position,
+    /// which places a single selected entity exactly at the position
+    SetPosition(Position),
+}
+
+/// How far the arrow keys move the selection
+#[derive(Resource)]
+pub struct NudgeSettings {
+    pub step: f32,
+    /// Step used while [Action::LargeNudge] is held
+    pub large_step: f32,
+}
+
+impl Default for NudgeSettings {
+    fn default() -> Self {
+        Self {
+            step: 1.0,
+            large_step: 10.0,
+        }
+    }
+}
+
+/// Appends every draggable entity out of entities onto out
+pub(super) fn draggable_entities(
+    entities: impl IntoIterator<Item = Entity>,
+    draggable_query: &Query<(), With<Draggable>>,
+    out: &mut Vec<Entity>,
+) {
+    out.extend(
+        entities
+            .into_iter()
+            .filter(|entity| draggable_query.contains(*entity)),
+    );
+
+    // Hovered entities can also be part of the selection
+    // so avoid moving them twice
+    out.sort_unstable();
+    out.dedup();
+}
+
+/// Moves every entity by offset
+pub(super) fn translate_entities(
+    entities: &[Entity],
+    offset: Position,
+    positions_query: &mut Query<&mut Position>,
+) {
+    for entity in entities {
+        // The saved entity could have been removed
+        // in between selection and moving it
+        // so we can't just unwrap
+        if let Ok(mut position) = positions_query.get_mut(*entity) {
+            *position = *position + offset;
+        }
+    }
+}
+
+pub(super) fn nudge_selection_system(
+    input: ActionInput,
+    settings: Res<NudgeSettings>,
+    mut transform_events: EventWriter<TransformSelection>,
+) {
+    let step = if input.pressed(Action::LargeNudge) {
+        settings.large_step
+    } else {
+        settings.step
+    };
+
+    let directions = [
+        (Action::NudgeLeft, Position::new(-step, 0.0)),
+        (Action::NudgeRight, Position::new(step, 0.0)),
+        // Y goes down the screen
+        (Action::NudgeUp, Position::new(0.0, -step)),
+        (Action::NudgeDown, Position::new(0.0, step)),
+    ];
+
+    for (action, offset) in directions {
+        if input.just_pressed(action) {
+            transform_events.send(TransformSelection::Translate(offset));
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub(super) fn transform_selection_system(
+    selection_set: Res<SelectionSet>,
+    mut transform_events: EventReader<TransformSelection>,
+    mut queries: ParamSet<(Query<(), With<Draggable>>, Query<&mut Position>)>,
+    mut entities: Local<Vec<Entity>>,
+) {
+    if transform_events.is_empty() {
+        return;
+    }
+
+    entities.clear();
+    draggable_entities(selection_set.iter(), &queries.p0(), &mut entities);
+
+    let mut positions_query = queries.p1();
+    for event in transform_events.read() {
+        let offset = match *event {
+            TransformSelection::Translate(offset) => offset,
+            TransformSelection::SetPosition(position) => {
+                // Only the selection itself decides the center
+                // and not the entities connected to it
+                let selected: Vec<_> = positions_query
+                    .iter_many(selection_set.iter())
+                    .copied()
+                    .collect();
+                if selected.is_empty() {
+                    continue;
+                }
+
+                let sum = selected
+                    .iter()
+                    .fold(Position::default(), |sum, position| sum + *position);
+                let center = sum * (1.0 / selected.len() as f32);
+                position - center
+            }
+        };
+
+        translate_entities(&entities, offset, &mut positions_query);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::selection::{constraints::resolve_constraints_system, Connection};
+    use bevy::ecs::{
+        event::Events,
+        schedule::{IntoSystemConfigs, Schedule},
+        world::World,
+    };
+
+    #[test]
+    fn transform_moves_connections() {
+        let mut world = World::new();
+        world.init_resource::<Events<TransformSelection>>();
+
+        let handle = world.spawn((Position::new(5.0, 0.0), Draggable)).id();
+        let point = world
+            .spawn((
+                Position::new(0.0, 0.0),
+                Draggable,
+                Connection::rigid([handle]),
+            ))
+            .id();
+        let other_point = world.spawn((Position::new(10.0, 0.0), Draggable)).id();
+        let unselected = world.spawn((Position::new(0.0, 0.0), Draggable)).id();
+
+        let mut selection_set = SelectionSet::default();
+        selection_set.insert(point);
+        selection_set.insert(other_point);
+        world.insert_resource(selection_set);
+
+        let mut schedule = Schedule::default();
+        schedule.add_systems((transform_selection_system, resolve_constraints_system).chain());
+        // Lets the constraints see where everything starts
+        schedule.run(&mut world);
+
+        world.send_event(TransformSelection::Translate(Position::new(1.0, 2.0)));
+        schedule.run(&mut world);
+
+        assert_eq!(
+            *world.get::<Position>(point).unwrap(),
+            Position::new(1.0, 2.0)
+        );
+        assert_eq!(
+            *world.get::<Position>(handle).unwrap(),
+            Position::new(6.0, 2.0)
+        );
+        assert_eq!(
+            *world.get::<Position>(other_point).unwrap(),
+            Position::new(11.0, 2.0)
+        );
+        assert_eq!(
+            *world.get::<Position>(unselected).unwrap(),
+            Position::new(0.0, 0.0)
+        );
+
+        // The center of the two selected points is moved
+        // without the handle affecting it
+        world.send_event(TransformSelection::SetPosition(Position::new(100.0, 100.0)));
+        schedule.run(&mut world);
+
+        assert_eq!(
+            *world.get::<Position>(point).unwrap(),
+            Position::new(95.0, 100.0)
+        );
+        assert_eq!(
+            *world.get::<Position>(other_point).unwrap(),
+            Position::new(105.0, 100.0)
+        );
+        assert_eq!(
+            *world.get::<Position>(handle).unwrap(),
+            Position::new(100.0, 100.0)
+        );
+    }
+}
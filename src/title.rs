@@ -0,0 +1,105 @@
+use std::collections::BTreeMap;
+
+use bevy::{
+    app::{Last, Plugin},
+    ecs::{
+        change_detection::DetectChanges,
+        query::With,
+        system::{Local, Query, Res, Resource},
+    },
+    window::{PrimaryWindow, Window},
+};
+
+/// Short messages shown in the window title after the title the window
+/// was created with, for anything that needs to tell the user something
+/// while there is no other way to show text. Each message has a key so
+/// that whatever set it can replace or clear it without touching others
+#[derive(Resource, Default)]
+pub struct TitleMessages(BTreeMap<&'static str, String>);
+
+impl TitleMessages {
+    pub fn set(&mut self, key: &'static str, message: impl Into<String>) {
+        self.0.insert(key, message.into());
+    }
+
+    pub fn clear(&mut self, key: &'static str) {
+        self.0.remove(key);
+    }
+
+    /// The title made from original_title and every message, which
+    /// are ordered by key so the title doesn't shuffle around
+    pub fn title(&self, original_title: &str) -> String {
+        let mut title = original_title.to_owned();
+        for message in self.0.values() {
+            title.push_str(" - ");
+            title.push_str(message);
+        }
+        title
+    }
+}
+
+fn update_title_system(
+    messages: Res<TitleMessages>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+    mut original_title: Local<Option<String>>,
+) {
+    if !messages.is_changed() {
+        return;
+    }
+    let Ok(mut window) = window_query.get_single_mut() else {
+        return;
+    };
+
+    let original_title = original_title.get_or_insert_with(|| window.title.clone());
+    let title = messages.title(original_title);
+    // Only touch the window when the title actually changes
+    if window.title != title {
+        window.title = title;
+    }
+}
+
+pub struct TitlePlugin;
+
+impl Plugin for TitlePlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<TitleMessages>();
+        app.add_systems(Last, update_title_system);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::{schedule::Schedule, world::World};
+
+    #[test]
+    fn messages_follow_original_title() {
+        let mut world = World::new();
+        world.init_resource::<TitleMessages>();
+        let window = world
+            .spawn((
+                Window {
+                    title: "Editor".to_owned(),
+                    ..Default::default()
+                },
+                PrimaryWindow,
+            ))
+            .id();
+        let mut schedule = Schedule::default();
+        schedule.add_systems(update_title_system);
+        let title = |world: &World| world.get::<Window>(window).unwrap().title.clone();
+
+        schedule.run(&mut world);
+        assert_eq!(title(&world), "Editor");
+
+        let mut messages = world.resource_mut::<TitleMessages>();
+        messages.set("b", "Second");
+        messages.set("a", "First");
+        schedule.run(&mut world);
+        assert_eq!(title(&world), "Editor - First - Second");
+
+        world.resource_mut::<TitleMessages>().clear("a");
+        schedule.run(&mut world);
+        assert_eq!(title(&world), "Editor - Second");
+    }
+}